@@ -0,0 +1,358 @@
+use std::collections::{HashMap, HashSet};
+use rayon::prelude::*;
+use crate::grid_graph::{add_edge, Edge, GridGraph, Node};
+use crate::nearest_neighbor::NearestNeighbor;
+use crate::polygon_test::PointInPolygonTest;
+use crate::region::Region;
+use crate::segment_index::SegmentIndex;
+
+/// Area of the sphere in square degrees
+const SPHERE_AREA: f64 = 41_252.96;
+/// Offsets of the eight cells around a cell of the same level
+const NEIGHBOR_DIRECTIONS: [(i64, i64); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// Square lon/lat cell of a refinement level, whose width is 1 / 2^level degrees
+#[derive(Clone, Copy)]
+struct Cell {
+    x: u32,
+    y: u32,
+    level: u32,
+}
+
+impl Cell {
+    fn size(&self) -> f64 {
+        0.5_f64.powi(self.level as i32)
+    }
+
+    fn bounds(&self) -> ((f64, f64), (f64, f64)) {
+        let size = self.size();
+        let (lon_min, lat_min) = (-180.0 + self.x as f64 * size, -90.0 + self.y as f64 * size);
+        ((lon_min, lon_min + size), (lat_min, lat_min + size))
+    }
+
+    fn center(&self) -> Node {
+        let ((lon_min, lon_max), (lat_min, lat_max)) = self.bounds();
+        Node { lon: (lon_min + lon_max) / 2.0, lat: (lat_min + lat_max) / 2.0 }
+    }
+
+    fn key(&self) -> (u32, u32, u32) {
+        (self.level, self.x, self.y)
+    }
+}
+
+/**
+Graph, which is dense near the coasts and sparse on the open sea.
+The equally distributed nodes of Deserno are the coarse level. Every cell of the grid of the polygon test with points of the coastline
+and every cell around it is replaced by a quadtree, whose cells are split as long as the coastline passes through them and the maximum level
+is not reached. The cells are split at least until they are as small as the distance of the coarse nodes, so the refined areas are never coarser.
+The quadtrees are balanced, so the levels of neighboring leaves differ by at most one and the refinement fades out towards the coarse nodes.
+Every leaf of the quadtrees in the water is a node, which is connected to the leaves around it, also across the levels, so a node has at most twelve of these edges.
+The coarse nodes in the refined cells are removed. Every leaf at the outside of a refined area is connected to the coarse nodes nearest
+to the points just outside of it, and every remaining coarse node, which lost a neighbor, to the leaf at the outside nearest to that neighbor.
+**/
+pub(crate) fn adaptive_nodes_and_edges(polygon_test: &PointInPolygonTest, region: Option<&Region>, maximum_number_of_nodes: usize, refinement_levels: u32) -> (Vec<Node>, Vec<Vec<Edge>>) {
+    let (coarse_nodes, coarse_edges) = GridGraph::deserno_nodes_and_edges(polygon_test, region, maximum_number_of_nodes);
+    let coarse_distance = (SPHERE_AREA / maximum_number_of_nodes.max(1) as f64).sqrt();
+    let minimum_level = (1.0 / coarse_distance).log2().ceil().max(0.0) as u32;
+    let maximum_level = minimum_level + refinement_levels;
+
+    // split the cells of the polygon test with points of the coastline and the cells around them, which take up the balancing
+    let segment_index = SegmentIndex::new(polygon_test.polygons());
+    let in_region = |cell: &Cell| region.map_or(true, |region| {
+        let (lon_range, lat_range) = cell.bounds();
+        region.overlaps(lon_range, lat_range)
+    });
+    let border_cells: Vec<Cell> = (0..180).flat_map(|y| (0..360).map(move |x| Cell { x, y, level: 0 }))
+        .filter(|cell| in_region(cell))
+        .filter(|cell| polygon_test.is_border((cell.center().lon, cell.center().lat)))
+        .collect();
+    let refined_cells: HashSet<(u32, u32)> = border_cells.iter()
+        .flat_map(|cell| NEIGHBOR_DIRECTIONS.iter().chain([(0, 0)].iter()).filter_map(move |(dx, dy)| {
+            let y = cell.y as i64 + dy;
+            if !(0..180).contains(&y) {
+                return None;
+            }
+            Some(Cell { x: (cell.x as i64 + dx).rem_euclid(360) as u32, y: y as u32, level: 0 })
+        }))
+        .filter(|cell| in_region(cell))
+        .map(|cell| (cell.x, cell.y))
+        .collect();
+    let mut sorted_refined_cells: Vec<(u32, u32)> = refined_cells.iter().copied().collect();
+    sorted_refined_cells.sort_unstable_by_key(|(x, y)| (*y, *x));
+    let leaves: Vec<Cell> = sorted_refined_cells.par_iter().flat_map(|(x, y)| {
+        let mut leaves = Vec::new();
+        refine(Cell { x: *x, y: *y, level: 0 }, &segment_index, minimum_level, maximum_level, &mut leaves);
+        leaves
+    }).collect();
+    drop(segment_index);
+    let leaves = balance(leaves, minimum_level);
+    let leaf_indices: HashMap<(u32, u32, u32), usize> = leaves.iter().enumerate().map(|(i, leaf)| (leaf.key(), i)).collect();
+    let find_leaf = |lon: f64, lat: f64| -> Option<usize> {
+        (minimum_level..=maximum_level).find_map(|level| {
+            let size = 0.5_f64.powi(level as i32);
+            let (x, y) = (((lon + 180.0) / size).floor() as u32, ((lat + 90.0) / size).floor() as u32);
+            leaf_indices.get(&(level, x, y)).copied()
+        })
+    };
+
+    // the coarse nodes come first, followed by the leaves in the water
+    let coarse_to_index: Vec<Option<u32>> = {
+        let mut number_nodes = 0;
+        coarse_nodes.iter().map(|node| {
+            let cell = (((node.lon + 180.0).floor() as u32) % 360, ((node.lat + 90.0).floor() as u32).min(179));
+            if refined_cells.contains(&cell) {
+                None
+            } else {
+                number_nodes += 1;
+                Some(number_nodes - 1)
+            }
+        }).collect()
+    };
+    let mut nodes: Vec<Node> = coarse_nodes.iter().zip(coarse_to_index.iter()).filter(|(_, index)| index.is_some()).map(|(node, _)| *node).collect();
+    let number_coarse_nodes = nodes.len();
+    let leaf_in_water: Vec<bool> = leaves.par_iter().map(|leaf| {
+        let center = leaf.center();
+        region.map_or(true, |region| region.contains((center.lon, center.lat))) && !polygon_test.check_intersection((center.lon, center.lat))
+    }).collect();
+    let leaf_to_index: Vec<Option<u32>> = leaf_in_water.iter().zip(leaves.iter()).map(|(in_water, leaf)| {
+        if *in_water {
+            nodes.push(leaf.center());
+            Some(nodes.len() as u32 - 1)
+        } else {
+            None
+        }
+    }).collect();
+
+    let mut node_pairs: Vec<(u32, u32)> = Vec::new();
+    // edges of the coarse nodes outside of the refined cells
+    for (coarse_node, node_edges) in coarse_edges.iter().enumerate() {
+        if let Some(node) = coarse_to_index[coarse_node] {
+            node_pairs.extend(node_edges.iter().filter_map(|edge| coarse_to_index[edge.target as usize]).filter(|target| node < *target).map(|target| (node, target)));
+        }
+    }
+    // edges between neighboring leaves. The points outside of the refined cells belong to the leaves at the outside of a refined area
+    let leaf_neighbors: Vec<(Vec<u32>, Vec<Node>)> = leaves.par_iter().zip(leaf_to_index.par_iter()).map(|(leaf, node)| {
+        let node = match node {
+            Some(node) => *node,
+            None => return (Vec::new(), Vec::new())
+        };
+        let ((lon_min, lon_max), (lat_min, lat_max)) = leaf.bounds();
+        // points just outside of the leaf in the middle of the cells of the next level along its sides and at its corners.
+        // The levels of neighboring leaves differ by at most one, so every neighbor contains one of these points
+        let quarter = leaf.size() / 4.0;
+        let samples = [
+            (lon_min - quarter, lat_min - quarter), (lon_max + quarter, lat_min - quarter), (lon_max + quarter, lat_max + quarter), (lon_min - quarter, lat_max + quarter),
+            (lon_min + quarter, lat_min - quarter), (lon_max - quarter, lat_min - quarter), (lon_min + quarter, lat_max + quarter), (lon_max - quarter, lat_max + quarter),
+            (lon_min - quarter, lat_min + quarter), (lon_min - quarter, lat_max - quarter), (lon_max + quarter, lat_min + quarter), (lon_max + quarter, lat_max - quarter),
+        ];
+        let mut neighbors = Vec::new();
+        let mut outside_points = Vec::new();
+        for (lon, lat) in samples.iter().copied() {
+            if lat <= -90.0 || lat >= 90.0 {
+                continue;
+            }
+            let lon = if lon < -180.0 { lon + 360.0 } else if lon >= 180.0 { lon - 360.0 } else { lon };
+            match find_leaf(lon, lat) {
+                Some(neighbor_leaf) => neighbors.extend(leaf_to_index[neighbor_leaf].filter(|neighbor| node < *neighbor)),
+                None => outside_points.push(Node { lon, lat })
+            }
+        }
+        (neighbors, outside_points)
+    }).collect();
+    let mut outside_leaf_nodes: Vec<(u32, Vec<Node>)> = Vec::new();
+    for (leaf, (neighbors, outside_points)) in leaf_neighbors.into_iter().enumerate() {
+        if let Some(node) = leaf_to_index[leaf] {
+            node_pairs.extend(neighbors.into_iter().map(|neighbor| (node, neighbor)));
+            if !outside_points.is_empty() {
+                outside_leaf_nodes.push((node, outside_points));
+            }
+        }
+    }
+
+    // stitch the coarse nodes and the refined areas together. The leaves at the outside are about as large as the distance
+    // of the coarse nodes, so every stitched node only gets a few edges
+    let stitched_coarse_nodes: Vec<(u32, Vec<usize>)> = coarse_edges.iter().enumerate().filter_map(|(coarse_node, node_edges)| {
+        let removed_neighbors: Vec<usize> = node_edges.iter().map(|edge| edge.target as usize).filter(|target| coarse_to_index[*target].is_none()).collect();
+        match coarse_to_index[coarse_node] {
+            Some(node) if !removed_neighbors.is_empty() => Some((node, removed_neighbors)),
+            _ => None
+        }
+    }).collect();
+    if !outside_leaf_nodes.is_empty() && number_coarse_nodes > 0 {
+        let outside_leaves: Vec<Node> = outside_leaf_nodes.iter().map(|(node, _)| nodes[*node as usize]).collect();
+        let nearest_outside_leaf = NearestNeighbor::new(&outside_leaves);
+        for (node, removed_neighbors) in &stitched_coarse_nodes {
+            for removed_neighbor in removed_neighbors {
+                let (leaf_node, _) = outside_leaf_nodes[nearest_outside_leaf.find_nearest_neighbor(&coarse_nodes[*removed_neighbor]) as usize];
+                node_pairs.push((*node, leaf_node));
+            }
+        }
+        let nearest_coarse_node = NearestNeighbor::new(&nodes[..number_coarse_nodes].to_vec());
+        for (leaf_node, outside_points) in &outside_leaf_nodes {
+            for point in outside_points {
+                node_pairs.push((nearest_coarse_node.find_nearest_neighbor(point), *leaf_node));
+            }
+        }
+    }
+
+    // sorted, so the graph is the same for every build
+    node_pairs.sort_unstable();
+    node_pairs.dedup();
+    let mut edges: Vec<Vec<Edge>> = vec![Vec::new(); nodes.len()];
+    for (node1, node2) in node_pairs {
+        add_edge(&mut edges, &nodes, node1 as usize, &Some(node2));
+    }
+    println!("number refined cells {} around {} cells with coastlines with {} leaves between level {} and {}", refined_cells.len(), border_cells.len(), leaves.len(), minimum_level, maximum_level);
+    println!("number coarse nodes {} and refined nodes {}", number_coarse_nodes, nodes.len() - number_coarse_nodes);
+    (nodes, edges)
+}
+
+/// Splits the cell as long as the coastline passes through it and collects the leaves
+fn refine(cell: Cell, segment_index: &SegmentIndex, minimum_level: u32, maximum_level: u32, leaves: &mut Vec<Cell>) {
+    let (lon_range, lat_range) = cell.bounds();
+    if cell.level < minimum_level || (cell.level < maximum_level && segment_index.intersects_rectangle(lon_range, lat_range)) {
+        for (dx, dy) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            refine(Cell { x: 2 * cell.x + dx, y: 2 * cell.y + dy, level: cell.level + 1 }, segment_index, minimum_level, maximum_level, leaves);
+        }
+    } else {
+        leaves.push(cell);
+    }
+}
+
+/// Splits the leaves with a neighbor, which is more than one level finer, until the levels of all neighboring leaves differ by at most one
+fn balance(mut leaves: Vec<Cell>, minimum_level: u32) -> Vec<Cell> {
+    let mut leaf_indices: HashMap<(u32, u32, u32), usize> = leaves.iter().enumerate().map(|(i, leaf)| (leaf.key(), i)).collect();
+    let mut is_split = vec![false; leaves.len()];
+    let mut queue: Vec<usize> = (0..leaves.len()).collect();
+    while let Some(i) = queue.pop() {
+        if is_split[i] {
+            continue;
+        }
+        let leaf = leaves[i];
+        for (dx, dy) in NEIGHBOR_DIRECTIONS.iter() {
+            let y = leaf.y as i64 + dy;
+            if y < 0 || y >= (180 << leaf.level) {
+                continue;
+            }
+            let (x, y) = ((leaf.x as i64 + dx).rem_euclid(360 << leaf.level) as u32, y as u32);
+            // leaf at the same or a coarser level, which contains the neighbor
+            let coarser_neighbor = (minimum_level..=leaf.level).rev()
+                .find_map(|level| leaf_indices.get(&(level, x >> (leaf.level - level), y >> (leaf.level - level))).copied());
+            if let Some(neighbor) = coarser_neighbor.filter(|neighbor| leaves[*neighbor].level + 1 < leaf.level) {
+                let cell = leaves[neighbor];
+                is_split[neighbor] = true;
+                leaf_indices.remove(&cell.key());
+                for (dx, dy) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let child = Cell { x: 2 * cell.x + dx, y: 2 * cell.y + dy, level: cell.level + 1 };
+                    leaf_indices.insert(child.key(), leaves.len());
+                    queue.push(leaves.len());
+                    leaves.push(child);
+                    is_split.push(false);
+                }
+                // the children may still be too coarse for this leaf
+                queue.push(i);
+                break;
+            }
+        }
+    }
+    leaves.into_iter().zip(is_split).filter(|(_, is_split)| !is_split).map(|(leaf, _)| leaf).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid_graph::Node;
+    use crate::polygon_test::PointInPolygonTest;
+    use crate::segment_index::SegmentIndex;
+    use super::{adaptive_nodes_and_edges, balance, refine, Cell};
+
+    /// Square island between 0 and 4 degrees east and north, whose outline has edges of 0.5 degrees like the coastlines
+    fn island() -> Vec<Vec<(f64, f64)>> {
+        let mut polygon = Vec::new();
+        for i in 0..8 {
+            polygon.push((i as f64 * 0.5, 0.0));
+        }
+        for i in 0..8 {
+            polygon.push((4.0, i as f64 * 0.5));
+        }
+        for i in 0..8 {
+            polygon.push((4.0 - i as f64 * 0.5, 4.0));
+        }
+        for i in 0..=8 {
+            polygon.push((0.0, 4.0 - i as f64 * 0.5));
+        }
+        vec![polygon]
+    }
+
+    /// Checks if both cells share a side or a corner
+    fn touch(a: &Cell, b: &Cell) -> bool {
+        let ((a_lon_min, a_lon_max), (a_lat_min, a_lat_max)) = a.bounds();
+        let ((b_lon_min, b_lon_max), (b_lat_min, b_lat_max)) = b.bounds();
+        a_lon_min <= b_lon_max && b_lon_min <= a_lon_max && a_lat_min <= b_lat_max && b_lat_min <= a_lat_max
+    }
+
+    #[test]
+    fn cells_are_split_where_the_coastline_passes() {
+        let polygons = island();
+        let segment_index = SegmentIndex::new(&polygons);
+        // the cell between 0 and 1 degrees, which contains the south western corner of the island
+        let mut leaves = Vec::new();
+        refine(Cell { x: 180, y: 90, level: 0 }, &segment_index, 0, 3, &mut leaves);
+        assert_eq!(leaves.iter().map(|leaf| leaf.size() * leaf.size()).sum::<f64>(), 1.0);
+        for leaf in &leaves {
+            let (lon_range, lat_range) = leaf.bounds();
+            assert!(!segment_index.intersects_rectangle(lon_range, lat_range) || leaf.level == 3, "leaf {:?}", leaf.key());
+        }
+        // the quarter in the north east does not touch the coastline
+        assert!(leaves.iter().any(|leaf| leaf.key() == (1, 361, 181)));
+
+        // the cells are split until the minimum level
+        let mut leaves = Vec::new();
+        refine(Cell { x: 0, y: 0, level: 0 }, &segment_index, 2, 3, &mut leaves);
+        assert_eq!(leaves.len(), 16);
+        assert!(leaves.iter().all(|leaf| leaf.level == 2));
+    }
+
+    #[test]
+    fn levels_of_neighboring_leaves_differ_by_at_most_one() {
+        let polygons = island();
+        let segment_index = SegmentIndex::new(&polygons);
+        let mut leaves = vec![Cell { x: 181, y: 90, level: 0 }, Cell { x: 180, y: 91, level: 0 }];
+        refine(Cell { x: 180, y: 90, level: 0 }, &segment_index, 0, 3, &mut leaves);
+        let area = leaves.iter().map(|leaf| leaf.size() * leaf.size()).sum::<f64>();
+        let leaves = balance(leaves, 0);
+        assert_eq!(leaves.iter().map(|leaf| leaf.size() * leaf.size()).sum::<f64>(), area);
+        assert!(leaves.iter().all(|leaf| leaf.level > 0));
+        for a in &leaves {
+            for b in &leaves {
+                assert!(!touch(a, b) || (a.level as i64 - b.level as i64).abs() <= 1, "leaves {:?} and {:?}", a.key(), b.key());
+            }
+        }
+    }
+
+    #[test]
+    fn graph_is_refined_near_the_coast_and_connected() {
+        let polygon_test = PointInPolygonTest::new(island());
+        let (nodes, edges) = adaptive_nodes_and_edges(&polygon_test, None, 10_000, 3);
+        assert!(nodes.iter().all(|node| !(node.lon > 0.0 && node.lon < 4.0 && node.lat > 0.0 && node.lat < 4.0)));
+        // leaves of the maximum level with a width of 0.125 degrees along the coast
+        let near_coast = |node: &&Node| node.lon > 4.0 && node.lon < 4.125 && node.lat > 1.0 && node.lat < 3.0;
+        assert_eq!(nodes.iter().filter(near_coast).count(), 16);
+        for (node, node_edges) in edges.iter().enumerate() {
+            assert!(node_edges.iter().all(|edge| edge.source == node as u32 && edges[edge.target as usize].iter().any(|reverse| reverse.target == edge.source)));
+        }
+        // the refined area is stitched to the coarse nodes. The coarse nodes around the poles are not always connected
+        let start = nodes.iter().position(|node| near_coast(&node)).unwrap();
+        let mut is_reached = vec![false; nodes.len()];
+        let mut stack = vec![start];
+        is_reached[start] = true;
+        while let Some(node) = stack.pop() {
+            for edge in &edges[node] {
+                if !is_reached[edge.target as usize] {
+                    is_reached[edge.target as usize] = true;
+                    stack.push(edge.target as usize);
+                }
+            }
+        }
+        assert!(nodes.iter().zip(is_reached).all(|(node, is_reached)| is_reached || node.lat.abs() > 80.0));
+    }
+}
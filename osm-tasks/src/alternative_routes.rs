@@ -0,0 +1,89 @@
+use std::collections::{HashMap, HashSet};
+use crate::dijkstra::{AdjacencyArray, Dijkstra};
+
+/// Factor by which the cost of an edge grows every time it is part of a calculated route
+const PENALTY_FACTOR: f64 = 0.5;
+/// Maximum number of penalized searches per requested alternative route
+const SEARCHES_PER_ALTERNATIVE: usize = 5;
+/// Maximum number of alternative routes, more requested alternatives are not calculated
+pub(crate) const MAXIMUM_ALTERNATIVES: usize = 5;
+/// Maximum number of penalties of an edge, which are applied to its cost, so the penalized cost stays finite
+const MAXIMUM_PENALTIES: i32 = 10;
+
+/// Limits for the routes accepted as alternative to the optimal route
+pub struct AlternativeRouteLimits {
+    // maximum share of the distance of an alternative, which overlaps with the optimal route or another alternative
+    pub maximum_overlap: f64,
+    // maximum distance of an alternative relative to the distance of the optimal route
+    pub maximum_stretch: f64,
+}
+
+/**
+Finds up to the given number of alternatives to the optimal route with the penalty method.
+After every search the costs of the edges on the found route are increased, so the next search is pushed
+to other parts of the graph. A found route is accepted if it is not too long compared to the optimal route
+and does not overlap too much with the routes accepted before.
+The penalties are applied to the edge costs of the cost model. The potential has to be a lower bound of the edge costs to the destination,
+so it is a lower bound of the penalized costs, too. The limits are checked with the distances of the routes.
+At most MAXIMUM_ALTERNATIVES routes are calculated. Returns the alternative routes with their distances.
+**/
+pub(crate) fn find_alternative_routes<F: Fn(u32) -> u32, C: Fn(u32, u32, u32) -> u32>(dijkstra: &mut Dijkstra, adjacency_array: &AdjacencyArray, optimal_route: &Vec<u32>, number_of_alternatives: usize, limits: &AlternativeRouteLimits, potential: F, base_cost: C) -> Vec<(Vec<u32>, u32)> {
+    let number_of_alternatives = number_of_alternatives.min(MAXIMUM_ALTERNATIVES);
+    let source_node = optimal_route[0];
+    let destination_node = optimal_route[optimal_route.len() - 1];
+    let optimal_distance = adjacency_array.route_distance(optimal_route);
+    let mut accepted_routes: Vec<(Vec<u32>, u32)> = vec![(optimal_route.clone(), optimal_distance)];
+    // number of times every undirected edge was part of a found route
+    let mut penalties: HashMap<(u32, u32), i32> = HashMap::new();
+    penalize_route(&mut penalties, optimal_route);
+    for _ in 0..(number_of_alternatives * SEARCHES_PER_ALTERNATIVE) {
+        if accepted_routes.len() > number_of_alternatives {
+            break;
+        }
+        let edge_cost = |from_node: u32, to_node: u32, distance: u32| {
+            let cost = base_cost(from_node, to_node, distance);
+            match penalties.get(&edge_key(from_node, to_node)) {
+                // blocked edges keep the cost u32::MAX, the penalized costs stay below it
+                Some(penalty) if cost != u32::MAX => (cost as f64 * (1.0 + PENALTY_FACTOR).powi((*penalty).min(MAXIMUM_PENALTIES))).min((u32::MAX - 1) as f64) as u32,
+                _ => cost
+            }
+        };
+        let route = match dijkstra.find_route_with_edge_costs(source_node, destination_node, &potential, edge_cost) {
+            Some((route, _)) => route,
+            None => break
+        };
+        penalize_route(&mut penalties, &route);
+        let distance = adjacency_array.route_distance(&route);
+        if distance as f64 > optimal_distance as f64 * limits.maximum_stretch {
+            continue;
+        }
+        let overlaps = accepted_routes.iter().any(|(accepted_route, _)| {
+            let accepted_edges = route_edges(accepted_route);
+            let overlap: u32 = route.windows(2)
+                .filter(|edge| accepted_edges.contains(&edge_key(edge[0], edge[1])))
+                .map(|edge| adjacency_array.get_edge_distance(edge[0], edge[1]).unwrap_or(0))
+                .sum();
+            overlap as f64 > distance as f64 * limits.maximum_overlap
+        });
+        if !overlaps && route.len() > 1 {
+            accepted_routes.push((route, distance));
+        }
+    }
+    accepted_routes.remove(0);
+    accepted_routes
+}
+
+fn penalize_route(penalties: &mut HashMap<(u32, u32), i32>, route: &Vec<u32>) {
+    for edge in route.windows(2) {
+        *penalties.entry(edge_key(edge[0], edge[1])).or_insert(0) += 1;
+    }
+}
+
+fn route_edges(route: &Vec<u32>) -> HashSet<(u32, u32)> {
+    route.windows(2).map(|edge| edge_key(edge[0], edge[1])).collect()
+}
+
+/// The edges are symmetric, so both directions of an edge share the same key
+fn edge_key(from_node: u32, to_node: u32) -> (u32, u32) {
+    (from_node.min(to_node), from_node.max(to_node))
+}
@@ -0,0 +1,213 @@
+use crate::grid_graph::Node;
+use crate::polygon_test::PointInPolygonTest;
+use crate::segment_index::SegmentIndex;
+use crate::cost_model::CostFunction;
+use crate::persistence::in_memory_routing_repo::GeoJsonPolygon;
+
+/// Maximum length of a polygon edge in degrees. The grid of the point in polygon test expects edges shorter than its cells
+const MAXIMUM_EDGE_DEGREES: f64 = 0.5;
+/// Margin in degrees around the bounding box of the areas, which covers great-circle segments bulging out of their lon/lat bounding box
+const BOUNDING_BOX_MARGIN: f64 = 1.0;
+/// Smallest area of an avoid area in square degrees, smaller areas are degenerated
+const MINIMUM_AREA: f64 = 1e-12;
+
+/**
+Areas of a single route request, which the route must not enter.
+A segment is blocked if one of its ends is inside an area or if it crosses the outline of an area.
+Only the segments near the areas are tested, so the areas can be checked while searching without touching the graph.
+**/
+pub struct AvoidAreas<'a> {
+    polygon_test: PointInPolygonTest,
+    segment_index: SegmentIndex<'a>,
+    // lon min, lon max, lat min, lat max of all areas including the margin
+    bounding_box: (f64, f64, f64, f64),
+}
+
+impl<'a> AvoidAreas<'a> {
+    /// Fails if a polygon has no area, e.g. fewer than three different points, or a point is outside of the valid latitudes and longitudes
+    pub fn new(polygons: &'a Vec<Vec<(f64, f64)>>) -> Result<AvoidAreas<'a>, String> {
+        for polygon in polygons {
+            // the polygons are closed, so the first point is repeated at the end
+            let area = polygon.windows(2).map(|edge| edge[0].0 * edge[1].1 - edge[1].0 * edge[0].1).sum::<f64>() / 2.0;
+            if polygon.len() < 4 || area.abs() < MINIMUM_AREA {
+                return Err(String::from("An avoid area needs at least three positions, which are not on a line"));
+            }
+            if let Some((lon, lat)) = polygon.iter().find(|(lon, lat)| !(-180.0..=180.0).contains(lon) || !(-90.0..=90.0).contains(lat)) {
+                return Err(format!("The position [{}, {}] of an avoid area is no valid [lon, lat] position", lon, lat));
+            }
+        }
+        let points = polygons.iter().flatten();
+        let bounding_box = points.fold((180.0_f64, -180.0_f64, 90.0_f64, -90.0_f64), |(lon_min, lon_max, lat_min, lat_max), (lon, lat)| {
+            (lon_min.min(*lon), lon_max.max(*lon), lat_min.min(*lat), lat_max.max(*lat))
+        });
+        Ok(AvoidAreas {
+            polygon_test: PointInPolygonTest::new(polygons.clone()),
+            segment_index: SegmentIndex::new(polygons),
+            bounding_box: (bounding_box.0 - BOUNDING_BOX_MARGIN, bounding_box.1 + BOUNDING_BOX_MARGIN, bounding_box.2 - BOUNDING_BOX_MARGIN, bounding_box.3 + BOUNDING_BOX_MARGIN),
+        })
+    }
+
+    pub fn contains(&self, point: &Node) -> bool {
+        self.is_near(point, point) && self.polygon_test.check_intersection((*point).into())
+    }
+
+    /// Checks if the great-circle segment between both nodes enters an area
+    pub fn is_segment_blocked(&self, from: &Node, to: &Node) -> bool {
+        self.is_near(from, to) && (self.contains(from) || self.contains(to) || self.segment_index.crosses_polygons(from, to))
+    }
+
+    /// Checks if the lon/lat bounding box of the segment overlaps the bounding box of the areas
+    fn is_near(&self, from: &Node, to: &Node) -> bool {
+        let (lon_min, lon_max, lat_min, lat_max) = self.bounding_box;
+        if from.lat.max(to.lat) < lat_min || from.lat.min(to.lat) > lat_max {
+            return false;
+        }
+        // segments crossing the antimeridian span all longitudes in the bounding box
+        (from.lon - to.lon).abs() > 180.0 || (from.lon.max(to.lon) >= lon_min && from.lon.min(to.lon) <= lon_max)
+    }
+}
+
+/// Converts the GeoJSON polygons of a route request into closed polygons with short edges.
+/// Only the outer ring of a polygon is used, holes are ignored
+pub fn avoid_area_polygons(areas: &Vec<GeoJsonPolygon>) -> Result<Vec<Vec<(f64, f64)>>, String> {
+    let mut polygons = Vec::with_capacity(areas.len());
+    for area in areas {
+        if area.geometry_type != "Polygon" {
+            return Err(format!("Avoid areas must be GeoJSON polygons, but got {}", area.geometry_type));
+        }
+        let outer_ring = match area.coordinates.first() {
+            Some(outer_ring) if outer_ring.len() >= 3 => outer_ring,
+            _ => return Err(String::from("The outer ring of an avoid area needs at least three positions"))
+        };
+
+        let mut ring: Vec<(f64, f64)> = outer_ring.iter().map(|position| (position[0], position[1])).collect();
+        if ring[0] != ring[ring.len() - 1] {
+            ring.push(ring[0]);
+        }
+        let mut polygon = vec![ring[0]];
+        for edge in ring.windows(2) {
+            let number_parts = ((edge[1].0 - edge[0].0).abs().max((edge[1].1 - edge[0].1).abs()) / MAXIMUM_EDGE_DEGREES).ceil().max(1.0) as usize;
+            for i in 1..=number_parts {
+                let fraction = i as f64 / number_parts as f64;
+                polygon.push((edge[0].0 + (edge[1].0 - edge[0].0) * fraction, edge[0].1 + (edge[1].1 - edge[0].1) * fraction));
+            }
+        }
+        polygons.push(polygon);
+    }
+    Ok(polygons)
+}
+
+/// Cost function, which blocks the segments entering the avoid areas and uses the costs of another cost function for all other segments
+pub struct AvoidAreaCost<'a> {
+    cost_function: Box<dyn CostFunction + 'a>,
+    avoid_areas: &'a AvoidAreas<'a>,
+}
+
+impl<'a> AvoidAreaCost<'a> {
+    pub fn new(cost_function: Box<dyn CostFunction + 'a>, avoid_areas: &'a AvoidAreas<'a>) -> AvoidAreaCost<'a> {
+        AvoidAreaCost { cost_function, avoid_areas }
+    }
+}
+
+impl<'a> CostFunction for AvoidAreaCost<'a> {
+    fn segment_cost(&self, from: &Node, to: &Node, distance: f64) -> f64 {
+        if self.avoid_areas.is_segment_blocked(from, to) {
+            // converted to the edge cost u32::MAX, so the edge is not used
+            return f64::INFINITY;
+        }
+        self.cost_function.segment_cost(from, to, distance)
+    }
+    fn minimum_cost_per_meter(&self) -> f64 {
+        self.cost_function.minimum_cost_per_meter()
+    }
+    fn waiting_cost(&self, seconds: f64) -> f64 {
+        self.cost_function.waiting_cost(seconds)
+    }
+    fn graph_edge_cost(&self, from_node: u32, to_node: u32, from: &Node, to: &Node, distance: u32) -> u32 {
+        if self.avoid_areas.is_segment_blocked(from, to) {
+            return u32::MAX;
+        }
+        self.cost_function.graph_edge_cost(from_node, to_node, from, to, distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cost_model::{CostFunction, DistanceCost};
+    use crate::grid_graph::Node;
+    use crate::persistence::in_memory_routing_repo::GeoJsonPolygon;
+    use super::{avoid_area_polygons, AvoidAreaCost, AvoidAreas};
+
+    fn geo_json_polygon(positions: &[[f64; 2]]) -> GeoJsonPolygon {
+        GeoJsonPolygon { geometry_type: String::from("Polygon"), coordinates: vec![positions.to_vec()] }
+    }
+
+    /// Square between 10 and 12 degrees east and 50 and 52 degrees north, whose outline is not closed
+    fn square() -> GeoJsonPolygon {
+        geo_json_polygon(&[[10.0, 50.0], [12.0, 50.0], [12.0, 52.0], [10.0, 52.0]])
+    }
+
+    #[test]
+    fn polygons_are_closed_and_split_into_short_edges() {
+        let polygons = avoid_area_polygons(&vec![square()]).unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(polygons.len(), 1);
+        let polygon = &polygons[0];
+        assert_eq!(polygon.first(), polygon.last());
+        // every side of 2 degrees is split into 4 edges
+        assert_eq!(polygon.len(), 17);
+        assert!(polygon.windows(2).all(|edge| (edge[1].0 - edge[0].0).abs() <= 0.5 && (edge[1].1 - edge[0].1).abs() <= 0.5));
+    }
+
+    #[test]
+    fn invalid_areas_are_rejected() {
+        let mut line_string = square();
+        line_string.geometry_type = String::from("LineString");
+        assert!(avoid_area_polygons(&vec![line_string]).is_err());
+        assert!(avoid_area_polygons(&vec![geo_json_polygon(&[[10.0, 50.0], [12.0, 50.0]])]).is_err());
+        assert!(avoid_area_polygons(&vec![GeoJsonPolygon { geometry_type: String::from("Polygon"), coordinates: vec![] }]).is_err());
+
+        let on_a_line = avoid_area_polygons(&vec![geo_json_polygon(&[[10.0, 50.0], [11.0, 50.0], [12.0, 50.0]])]).unwrap_or_else(|error| panic!("{}", error));
+        assert!(matches!(AvoidAreas::new(&on_a_line), Err(error) if error.contains("not on a line")));
+        let outside = avoid_area_polygons(&vec![geo_json_polygon(&[[10.0, 50.0], [12.0, 50.0], [12.0, 95.0]])]).unwrap_or_else(|error| panic!("{}", error));
+        assert!(matches!(AvoidAreas::new(&outside), Err(error) if error.contains("no valid [lon, lat] position")));
+    }
+
+    #[test]
+    fn segments_entering_an_area_are_blocked() {
+        let polygons = avoid_area_polygons(&vec![square()]).unwrap_or_else(|error| panic!("{}", error));
+        let avoid_areas = AvoidAreas::new(&polygons).unwrap_or_else(|error| panic!("{}", error));
+        assert!(avoid_areas.contains(&Node { lon: 11.2, lat: 51.2 }));
+        // below the vertex between two edges of the northern side
+        assert!(avoid_areas.contains(&Node { lon: 11.0, lat: 51.0 }));
+        assert!(!avoid_areas.contains(&Node { lon: 13.0, lat: 51.0 }));
+        // inside of the area
+        assert!(avoid_areas.is_segment_blocked(&Node { lon: 10.7, lat: 51.2 }, &Node { lon: 11.2, lat: 51.2 }));
+        // ending inside of the area
+        assert!(avoid_areas.is_segment_blocked(&Node { lon: 9.5, lat: 51.2 }, &Node { lon: 11.2, lat: 51.2 }));
+        // crossing the area with both ends outside
+        assert!(avoid_areas.is_segment_blocked(&Node { lon: 9.5, lat: 51.0 }, &Node { lon: 12.5, lat: 51.0 }));
+        assert!(!avoid_areas.is_segment_blocked(&Node { lon: 9.5, lat: 49.5 }, &Node { lon: 12.5, lat: 49.5 }));
+        assert!(!avoid_areas.is_segment_blocked(&Node { lon: -20.0, lat: 0.0 }, &Node { lon: -19.0, lat: 0.0 }));
+    }
+
+    #[test]
+    fn segments_across_the_antimeridian_are_blocked() {
+        let polygons = avoid_area_polygons(&vec![geo_json_polygon(&[[179.7, 0.0], [180.0, 0.0], [180.0, 1.0], [179.7, 1.0]])]).unwrap_or_else(|error| panic!("{}", error));
+        let avoid_areas = AvoidAreas::new(&polygons).unwrap_or_else(|error| panic!("{}", error));
+        assert!(avoid_areas.is_segment_blocked(&Node { lon: 179.5, lat: 0.5 }, &Node { lon: -179.5, lat: 0.5 }));
+        assert!(avoid_areas.is_segment_blocked(&Node { lon: -179.5, lat: 0.5 }, &Node { lon: 179.5, lat: 0.5 }));
+        assert!(!avoid_areas.is_segment_blocked(&Node { lon: 179.5, lat: 1.5 }, &Node { lon: -179.5, lat: 1.5 }));
+    }
+
+    #[test]
+    fn blocked_edges_have_the_maximum_cost() {
+        let polygons = avoid_area_polygons(&vec![square()]).unwrap_or_else(|error| panic!("{}", error));
+        let avoid_areas = AvoidAreas::new(&polygons).unwrap_or_else(|error| panic!("{}", error));
+        let cost_function = AvoidAreaCost::new(Box::new(DistanceCost), &avoid_areas);
+        let (west, east) = (Node { lon: 9.5, lat: 51.0 }, Node { lon: 12.5, lat: 51.0 });
+        assert_eq!(cost_function.graph_edge_cost(0, 1, &west, &east, 200_000), u32::MAX);
+        assert_eq!(cost_function.edge_cost(&west, &east, 200_000), u32::MAX);
+        let (south_west, south_east) = (Node { lon: 9.5, lat: 49.5 }, Node { lon: 12.5, lat: 49.5 });
+        assert_eq!(cost_function.graph_edge_cost(0, 1, &south_west, &south_east, 200_000), 200_000);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::grid_graph::{Edge, Node};
+
+/**
+Connected components of the graph, e.g. the oceans, enclosed seas like the Caspian Sea, lakes or pockets of water, which the grid does not connect.
+The components are numbered by their size, so the largest component has the id 0.
+A route can only be found between nodes of the same component.
+**/
+pub(crate) fn connected_components(edges: &Vec<Vec<Edge>>) -> Vec<u32> {
+    let mut components = vec![u32::MAX; edges.len()];
+    let mut component_sizes: Vec<u32> = Vec::new();
+    let mut stack = Vec::new();
+    for start_node in 0..edges.len() {
+        if components[start_node] != u32::MAX {
+            continue;
+        }
+        let component = component_sizes.len() as u32;
+        let mut component_size = 0;
+        components[start_node] = component;
+        stack.push(start_node);
+        while let Some(node) = stack.pop() {
+            component_size += 1;
+            for edge in &edges[node] {
+                let target = edge.target as usize;
+                if components[target] == u32::MAX {
+                    components[target] = component;
+                    stack.push(target);
+                }
+            }
+        }
+        component_sizes.push(component_size);
+    }
+    // renumber the components by their size, the order of components with the same size is kept
+    let mut order: Vec<usize> = (0..component_sizes.len()).collect();
+    order.sort_by_key(|component| std::cmp::Reverse(component_sizes[*component]));
+    let mut component_ids = vec![0; component_sizes.len()];
+    for (id, component) in order.into_iter().enumerate() {
+        component_ids[component] = id as u32;
+    }
+    components.iter().map(|component| component_ids[*component as usize]).collect()
+}
+
+/// Number of nodes of every component
+pub(crate) fn component_sizes(components: &Vec<u32>) -> Vec<u32> {
+    let mut sizes = vec![0; components.iter().max().map_or(0, |component| *component as usize + 1)];
+    for component in components {
+        sizes[*component as usize] += 1;
+    }
+    sizes
+}
+
+/// Removes the nodes of the components with fewer nodes than the minimum size and their edges. Returns the remaining nodes, edges and components
+pub(crate) fn remove_small_components(nodes: Vec<Node>, edges: Vec<Vec<Edge>>, components: Vec<u32>, minimum_size: u32) -> (Vec<Node>, Vec<Vec<Edge>>, Vec<u32>) {
+    let sizes = component_sizes(&components);
+    // the components are ordered by their size
+    let number_kept_components = sizes.iter().take_while(|size| **size >= minimum_size).count() as u32;
+    let mut new_indices: Vec<Option<u32>> = vec![None; nodes.len()];
+    let mut number_kept_nodes = 0;
+    for (node, component) in components.iter().enumerate() {
+        if *component < number_kept_components {
+            new_indices[node] = Some(number_kept_nodes);
+            number_kept_nodes += 1;
+        }
+    }
+    let mut kept_nodes = Vec::with_capacity(number_kept_nodes as usize);
+    let mut kept_edges = Vec::with_capacity(number_kept_nodes as usize);
+    let mut kept_components = Vec::with_capacity(number_kept_nodes as usize);
+    for ((node, mut node_edges), component) in nodes.into_iter().zip(edges.into_iter()).zip(components.into_iter()) {
+        if component >= number_kept_components {
+            continue;
+        }
+        // all neighbors of a node are in its component, so they are kept as well
+        for edge in node_edges.iter_mut() {
+            edge.source = new_indices[edge.source as usize].unwrap();
+            edge.target = new_indices[edge.target as usize].unwrap();
+        }
+        kept_nodes.push(node);
+        kept_edges.push(node_edges);
+        kept_components.push(component);
+    }
+    println!("Removed {} components with less than {} nodes and {} nodes in total", sizes.len() as u32 - number_kept_components, minimum_size, new_indices.len() - kept_nodes.len());
+    (kept_nodes, kept_edges, kept_components)
+}
+
+/// Components of the graph, which are joined by the extra edges like the passages
+pub struct ConnectedComponents {
+    node_components: Vec<u32>,
+    sizes: Vec<u32>,
+}
+
+impl ConnectedComponents {
+    pub fn new(components: &Vec<u32>, extra_edges: &Vec<(u32, u32, u32)>) -> ConnectedComponents {
+        let sizes = component_sizes(components);
+        // union find over the components, whose root is the joined component with the smallest id
+        let mut parents: Vec<u32> = (0..sizes.len() as u32).collect();
+        let find = |parents: &Vec<u32>, mut component: u32| {
+            while parents[component as usize] != component {
+                component = parents[component as usize];
+            }
+            component
+        };
+        for (source, target, _) in extra_edges {
+            let source_root = find(&parents, components[*source as usize]);
+            let target_root = find(&parents, components[*target as usize]);
+            parents[source_root.max(target_root) as usize] = source_root.min(target_root);
+        }
+        let roots: Vec<u32> = (0..sizes.len() as u32).map(|component| find(&parents, component)).collect();
+        let mut joined_sizes = vec![0; sizes.len()];
+        for (component, size) in sizes.iter().enumerate() {
+            joined_sizes[roots[component] as usize] += size;
+        }
+        let node_components = components.iter().map(|component| roots[*component as usize]).collect();
+        ConnectedComponents { node_components, sizes: joined_sizes }
+    }
+
+    pub fn component(&self, node: u32) -> u32 {
+        self.node_components[node as usize]
+    }
+
+    /// Number of nodes of the component
+    pub fn size(&self, component: u32) -> u32 {
+        self.sizes[component as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid_graph::{Edge, Node};
+    use super::{component_sizes, connected_components, remove_small_components, ConnectedComponents};
+
+    /// Edges in both directions between the pairs of nodes
+    fn edges(number_nodes: usize, pairs: &[(u32, u32)]) -> Vec<Vec<Edge>> {
+        let mut edges = vec![Vec::new(); number_nodes];
+        for (source, target) in pairs {
+            edges[*source as usize].push(Edge { source: *source, target: *target, distance: 1 });
+            edges[*target as usize].push(Edge { source: *target, target: *source, distance: 1 });
+        }
+        edges
+    }
+
+    /// Path of four nodes, a pair, a single node, a path of three nodes and another pair
+    fn graph() -> Vec<Vec<Edge>> {
+        edges(12, &[(0, 1), (1, 2), (2, 3), (4, 5), (7, 8), (8, 9), (10, 11)])
+    }
+
+    #[test]
+    fn components_are_numbered_by_their_size() {
+        let components = connected_components(&graph());
+        // components of the same size keep their order
+        assert_eq!(components, vec![0, 0, 0, 0, 2, 2, 4, 1, 1, 1, 3, 3]);
+        assert_eq!(component_sizes(&components), vec![4, 3, 2, 2, 1]);
+        assert!(connected_components(&Vec::new()).is_empty());
+        assert!(component_sizes(&Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn small_components_are_removed() {
+        let edges = graph();
+        let components = connected_components(&edges);
+        let nodes: Vec<Node> = (0..12).map(|i| Node { lon: i as f64, lat: 0.0 }).collect();
+        let (nodes, edges, components) = remove_small_components(nodes, edges, components, 3);
+        assert_eq!(nodes.iter().map(|node| node.lon).collect::<Vec<f64>>(), vec![0.0, 1.0, 2.0, 3.0, 7.0, 8.0, 9.0]);
+        assert_eq!(components, vec![0, 0, 0, 0, 1, 1, 1]);
+        // the edges are renumbered
+        for (node, node_edges) in edges.iter().enumerate() {
+            assert!(node_edges.iter().all(|edge| edge.source == node as u32 && components[edge.target as usize] == components[node]));
+        }
+        assert_eq!(edges[5].iter().map(|edge| edge.target).collect::<Vec<u32>>(), vec![4, 6]);
+    }
+
+    #[test]
+    fn extra_edges_join_components() {
+        let components = connected_components(&graph());
+        let unjoined = ConnectedComponents::new(&components, &Vec::new());
+        assert_eq!((unjoined.component(5), unjoined.size(2)), (2, 2));
+
+        // a passage between the first pair and the path of three nodes, and one between both pairs
+        let joined = ConnectedComponents::new(&components, &vec![(5, 8, 1_000), (11, 4, 1_000)]);
+        assert!([4, 5, 7, 8, 9, 10, 11].iter().all(|node| joined.component(*node) == 1));
+        assert_eq!(joined.size(1), 7);
+        assert_eq!((joined.component(0), joined.size(0)), (0, 4));
+        assert_eq!((joined.component(6), joined.size(4)), (4, 1));
+    }
+}
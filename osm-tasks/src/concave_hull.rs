@@ -0,0 +1,226 @@
+use std::f64::consts::PI;
+use crate::segment_index::{orientation, segments_intersect};
+
+const EPSILON: f64 = 1e-9;
+/// Upper bound for the number of nearest neighbors considered per step. If no valid hull is found with this
+/// number of neighbors, the convex hull is returned instead.
+const MAXIMUM_NEAREST_NEIGHBORS: usize = 50;
+/// Upper bound for the number of points, which are hulled. Every attempt takes quadratic time in the number of points,
+/// so larger sets of points are thinned out to about this number of points first
+const MAXIMUM_HULL_POINTS: usize = 1000;
+
+/**
+Concave hull of a set of (lon, lat) points, based on the k-nearest neighbours approach by Moreira and Santos.
+Starting at the southernmost point, the hull is walked counterclockwise and the next hull point is the one of the
+k nearest points, which turns furthest to the right without crossing the hull built so far.
+If the hull crosses itself or does not contain all points, the calculation is repeated with a k larger by half.
+Large sets of points are thinned out to one point per cell of a grid before, so the hull may cut off points by up to the size of a cell.
+Sets of points, which are too small for a concave hull, get their convex hull. The hull is empty, if all points lie on a line.
+The points are treated as coordinates in the plane, so the hull must not cross the 180 degree meridian.
+**/
+pub fn concave_hull(points: &Vec<(f64, f64)>, nearest_neighbors: usize) -> Vec<(f64, f64)> {
+    let mut unique_points = thin_out(points);
+    unique_points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    unique_points.dedup();
+    let mut k = nearest_neighbors.max(3);
+    while k <= MAXIMUM_NEAREST_NEIGHBORS.min(unique_points.len().saturating_sub(1)) {
+        if let Some(hull) = concave_hull_with_k(&unique_points, k) {
+            return hull;
+        }
+        k += k / 2;
+    }
+    let hull = convex_hull(&unique_points);
+    if hull.len() < 3 {
+        return Vec::new();
+    }
+    hull
+}
+
+/// Keeps one point of every cell of a grid over the bounding box, whose cells are chosen so about MAXIMUM_HULL_POINTS cells are occupied
+fn thin_out(points: &Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    if points.len() <= MAXIMUM_HULL_POINTS {
+        return points.clone();
+    }
+    let (lon_min, lon_max) = points.iter().fold((f64::MAX, f64::MIN), |(min, max), point| (min.min(point.0), max.max(point.0)));
+    let (lat_min, lat_max) = points.iter().fold((f64::MAX, f64::MIN), |(min, max), point| (min.min(point.1), max.max(point.1)));
+    let (width, height) = (lon_max - lon_min, lat_max - lat_min);
+    let cell_size = (width * height / MAXIMUM_HULL_POINTS as f64).sqrt().max(width.max(height) / MAXIMUM_HULL_POINTS as f64);
+    if cell_size <= 0.0 {
+        return vec![points[0]];
+    }
+    let mut cells: Vec<((i64, i64), (f64, f64))> = points.iter()
+        .map(|point| ((((point.0 - lon_min) / cell_size) as i64, ((point.1 - lat_min) / cell_size) as i64), *point))
+        .collect();
+    cells.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    cells.dedup_by_key(|(cell, _)| *cell);
+    cells.into_iter().map(|(_, point)| point).collect()
+}
+
+fn concave_hull_with_k(points: &Vec<(f64, f64)>, k: usize) -> Option<Vec<(f64, f64)>> {
+    let first_point = *points.iter().min_by(|a, b| (a.1, a.0).partial_cmp(&(b.1, b.0)).unwrap()).unwrap();
+    let mut remaining_points: Vec<(f64, f64)> = points.iter().filter(|p| **p != first_point).copied().collect();
+    let mut hull = vec![first_point];
+    let mut current_point = first_point;
+    // the walk starts to the east, as if the hull came from the west
+    let mut previous_angle = PI;
+    loop {
+        if hull.len() == 4 {
+            // allow to close the hull once it consists of at least a triangle
+            remaining_points.push(first_point);
+        }
+        let mut candidates = k_nearest_points(&remaining_points, current_point, k);
+        // sort by the counterclockwise angle from the direction back to the previous hull point
+        candidates.sort_by(|a, b| {
+            turn_angle(previous_angle, current_point, *a).partial_cmp(&turn_angle(previous_angle, current_point, *b)).unwrap()
+        });
+        let next_point = candidates.into_iter().find(|candidate| {
+            // the edge closing the hull touches the first hull edge at the first point
+            let skipped_edges = if *candidate == first_point { 1 } else { 0 };
+            !(skipped_edges..hull.len().saturating_sub(2)).any(|i| crosses_hull_edge(hull[i], hull[i + 1], current_point, *candidate))
+        })?;
+        if next_point == first_point {
+            break;
+        }
+        previous_angle = angle(next_point, current_point);
+        hull.push(next_point);
+        remaining_points.retain(|p| *p != next_point);
+        current_point = next_point;
+        if remaining_points.is_empty() {
+            return None;
+        }
+    }
+    if points.iter().all(|point| point_in_polygon_or_on_border(*point, &hull)) {
+        Some(hull)
+    } else {
+        None
+    }
+}
+
+fn k_nearest_points(points: &Vec<(f64, f64)>, point: (f64, f64), k: usize) -> Vec<(f64, f64)> {
+    let mut points_with_distance: Vec<(f64, (f64, f64))> = points.iter().map(|p| ((p.0 - point.0).powi(2) + (p.1 - point.1).powi(2), *p)).collect();
+    let k = k.min(points_with_distance.len());
+    if k == 0 {
+        return vec![];
+    }
+    points_with_distance.select_nth_unstable_by(k - 1, |a, b| a.0.partial_cmp(&b.0).unwrap());
+    points_with_distance.truncate(k);
+    points_with_distance.into_iter().map(|(_, p)| p).collect()
+}
+
+/// Angle of the direction from `from` to `to` in radians
+fn angle(from: (f64, f64), to: (f64, f64)) -> f64 {
+    (to.1 - from.1).atan2(to.0 - from.0)
+}
+
+/// Counterclockwise angle in (0, 2 PI] between the direction with the previous angle and the direction to the candidate
+fn turn_angle(previous_angle: f64, point: (f64, f64), candidate: (f64, f64)) -> f64 {
+    let mut turn = angle(point, candidate) - previous_angle;
+    while turn <= 0.0 {
+        turn += 2.0 * PI;
+    }
+    while turn > 2.0 * PI {
+        turn -= 2.0 * PI;
+    }
+    turn
+}
+
+/// Checks if the new hull edge intersects the hull edge in a point which is not a shared end point
+fn crosses_hull_edge(a1: (f64, f64), a2: (f64, f64), b1: (f64, f64), b2: (f64, f64)) -> bool {
+    if a1 == b1 || a1 == b2 || a2 == b1 || a2 == b2 {
+        return false;
+    }
+    segments_intersect(a1, a2, b1, b2)
+}
+
+fn point_on_segment(point: (f64, f64), a: (f64, f64), b: (f64, f64)) -> bool {
+    orientation(a, b, point).abs() <= EPSILON
+        && point.0 >= a.0.min(b.0) - EPSILON && point.0 <= a.0.max(b.0) + EPSILON
+        && point.1 >= a.1.min(b.1) - EPSILON && point.1 <= a.1.max(b.1) + EPSILON
+}
+
+fn point_in_polygon_or_on_border(point: (f64, f64), polygon: &Vec<(f64, f64)>) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if point_on_segment(point, a, b) {
+            return true;
+        }
+        if (a.1 > point.1) != (b.1 > point.1) && point.0 < (b.0 - a.0) * (point.1 - a.1) / (b.1 - a.1) + a.0 {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Convex hull with Andrew's monotone chain algorithm, expects sorted points without duplicates
+fn convex_hull(sorted_points: &Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity(sorted_points.len() + 1);
+    // lower hull
+    for point in sorted_points {
+        while hull.len() >= 2 && orientation(hull[hull.len() - 2], hull[hull.len() - 1], *point) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(*point);
+    }
+    // upper hull
+    let lower_hull_length = hull.len() + 1;
+    for point in sorted_points.iter().rev().skip(1) {
+        while hull.len() >= lower_hull_length && orientation(hull[hull.len() - 2], hull[hull.len() - 1], *point) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(*point);
+    }
+    hull.pop();
+    hull
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{concave_hull, point_in_polygon_or_on_border};
+
+    /// Points of a U on a grid with a spacing of 1, which is 7 points wide and high and open to the north between x = 2 and x = 4
+    fn u_shape() -> Vec<(f64, f64)> {
+        let mut points = Vec::new();
+        for x in 0..7 {
+            for y in 0..7 {
+                if !((2..=4).contains(&x) && y >= 2) {
+                    points.push((x as f64, y as f64));
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn hull_contains_all_points_and_follows_the_notch() {
+        let points = u_shape();
+        let hull = concave_hull(&points, 3);
+        assert!(hull.len() >= 3);
+        assert!(points.iter().all(|point| point_in_polygon_or_on_border(*point, &hull)));
+        // the convex hull would contain the notch of the U
+        assert!(!point_in_polygon_or_on_border((3.0, 5.0), &hull));
+    }
+
+    #[test]
+    fn large_point_sets_are_thinned_out() {
+        let points: Vec<(f64, f64)> = u_shape().iter()
+            .flat_map(|(x, y)| (0..10).flat_map(move |i| (0..10).map(move |j| (x + i as f64 * 0.1, y + j as f64 * 0.1))))
+            .collect();
+        assert!(points.len() > 1000);
+        let hull = concave_hull(&points, 3);
+        assert!(hull.len() >= 3);
+        assert!(!point_in_polygon_or_on_border((3.5, 5.5), &hull));
+    }
+
+    #[test]
+    fn small_and_degenerate_point_sets() {
+        assert!(concave_hull(&vec![], 3).is_empty());
+        assert!(concave_hull(&vec![(0.0, 0.0), (1.0, 1.0)], 3).is_empty());
+        assert!(concave_hull(&vec![(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (3.0, 3.0)], 3).is_empty());
+        let triangle = concave_hull(&vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 0.0)], 3);
+        assert_eq!(triangle.len(), 3);
+        assert!([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)].iter().all(|point| triangle.contains(point)));
+    }
+}
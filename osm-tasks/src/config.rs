@@ -1,8 +1,15 @@
 use clap::Clap;
 use once_cell::sync::OnceCell;
 use std::path::Path;
+use std::sync::Arc;
+use crate::landmarks::LandmarkSelection;
+use crate::alternative_routes::AlternativeRouteLimits;
+use crate::topology::GraphTopology;
+use crate::region::Region;
 
 static INSTANCE: OnceCell<Config> = OnceCell::new();
+/// Region of the configuration, which is parsed once when the configuration is loaded
+static REGION: OnceCell<Option<Arc<Region>>> = OnceCell::new();
 
 /// This doc string acts as a help message when the user runs '--help'
 /// OSM FaPra ship routing server
@@ -25,13 +32,85 @@ pub struct Config {
     #[clap(short = 'n', long = "nodes", default_value = "10000")]
     number_of_nodes: u32,
 
+    /// Distribution of the nodes and edges of the graph. Either deserno (equally distributed nodes), latlon4 or latlon8 (regular lon/lat grid with four or eight neighbors) hexagonal (icosahedral hexagonal grid) or adaptive (equally distributed nodes refined near the coasts). Graphs with another topology than deserno are saved as <coastlines_file>.<number_of_nodes>.<topology>.bin
+    #[clap(long, default_value = "deserno", possible_values = &["deserno", "latlon4", "latlon8", "hexagonal", "adaptive"])]
+    topology: String,
+
+    /// Components of the graph with fewer nodes, like lakes or pockets of water between the nodes, are removed from the graph. The size is added to the file name of the graph as <coastlines_file>.<number_of_nodes>.min<min_component_size>.bin. No components are removed if this is 0
+    #[clap(long, default_value = "0")]
+    min_component_size: u32,
+
+    /// Restricts the graph to a region, which is either a bounding box lon_min,lat_min,lon_max,lat_max, a GeoJSON polygon or the path of a file with a GeoJSON polygon. A bounding box with lon_min > lon_max wraps across the antimeridian. The graph is saved as <coastlines_file>.<number_of_nodes>.<region>.bin
+    #[clap(long)]
+    region: Option<String>,
+
+    /// Number of levels, which refine the adaptive topology near the coasts. Each level halves the distance between the nodes. The number of levels is added to the topology in the file name of the graph
+    #[clap(long, default_value = "3")]
+    refinement_levels: u32,
+
     /// Build graph on startup. Sets wether the graph generation should be triggered at startup. Generation trough REST API will be available anyway.
     #[clap(short, long)]
     build_graph_on_startup: bool,
 
+    /// Build the contraction hierarchy for the graph, which is required by the contractionHierarchies routing algorithm. The contracted graph is saved next to the graph as <coastlines_file>.<number_of_nodes>.ch.bin
+    #[clap(short, long)]
+    contraction_hierarchies: bool,
+
+    /// Number of landmarks used by the ALT routing algorithm. The landmarks are saved next to the graph as <coastlines_file>.<number_of_nodes>.landmarks.bin. No landmarks are selected if this is 0.
+    #[clap(short, long, default_value = "0")]
+    landmarks: u32,
+
+    /// Strategy used to select the landmarks. Either farthest or avoid.
+    #[clap(long, default_value = "avoid", possible_values = &["farthest", "avoid"])]
+    landmark_selection: String,
+
+    /// Smooth the routes by replacing zig-zagging parts of the route with direct great-circle segments, which do not cross land and keep the minimum distance to the coast of the route.
+    #[clap(short, long)]
+    smooth_routes: bool,
+
+    /// Maximum share of the distance of an alternative route, which may overlap with the optimal route or another alternative route.
+    #[clap(long, default_value = "0.5")]
+    alternative_max_overlap: f64,
+
+    /// Maximum distance of an alternative route relative to the distance of the optimal route.
+    #[clap(long, default_value = "1.3")]
+    alternative_max_stretch: f64,
+
+    /// Service speed of the ship in knots, which is used by the time and fuel cost models.
+    #[clap(long, default_value = "12")]
+    service_speed: f64,
+
+    /// Fuel consumption of the ship at the service speed in tonnes per day, which is used by the fuel cost model.
+    #[clap(long, default_value = "30")]
+    fuel_consumption: f64,
+
+    /// JSON file with an array of ship profiles, which can be selected by name in a route request. Each profile has a name, a speed in knots and optionally a fuelConsumption in tonnes per day, a maxDraft and a minCoastDistance in meters. Passages with a smaller maxDraft are closed for the profile. The default profile is built from the service speed and the fuel consumption.
+    #[clap(long)]
+    ship_profiles: Option<String>,
+
+    /// JSON file with an array of passages like canals and narrow straits, which are added to the graph as edges between the nearest nodes of their ends. Each passage has a name, coordinates with the [lon, lat] positions along the passage and optionally a waitingTime in seconds, a penalty and a maxDraft in meters. The open passages are part of the graph for all algorithms, closing a passage or changing its costs in a request needs Dijkstra or A*.
+    #[clap(long)]
+    passages: Option<String>,
+
+    /// CSV file with the currents for weather routing. Each line has the columns time,lon,lat,u,v with a unix timestamp in seconds and the eastward and northward speed in m/s on a regular lon/lat grid.
+    #[clap(long)]
+    current_field: Option<String>,
+
+    /// CSV file with the winds for weather routing in the same format as the currents.
+    #[clap(long)]
+    wind_field: Option<String>,
+
+    /// Share of the wind speed, which drifts the ship.
+    #[clap(long, default_value = "0.03")]
+    wind_factor: f64,
+
     #[clap(long="max-test")]
     max_test: bool,
 
+    /// Validates the graph instead of starting the server. Prints the degree distribution and the problems of the graph like asymmetric, duplicate or missing edges and saves the locations of the problems as GeoJSON to this path
+    #[clap(long)]
+    validate_graph: Option<String>,
+
     // Todo: Option for KML export
 
 }
@@ -50,6 +129,8 @@ impl Config {
         if !Path::new(config.coastlines_file()).is_file() {
             panic!("Could not open coastlines file: {}", config.coastlines_file());
         }
+        let region = config.region.as_ref().map(|region| Region::from_argument(region).unwrap_or_else(|error| panic!("Invalid region {}: {}", region, error)));
+        REGION.set(region.map(Arc::new)).ok();
         INSTANCE.set(config).unwrap();
     }
 
@@ -62,13 +143,64 @@ impl Config {
     pub fn number_of_nodes(&self) -> u32 {
         self.number_of_nodes
     }
+    pub fn topology(&self) -> GraphTopology {
+        self.topology.parse().unwrap()
+    }
+    pub fn min_component_size(&self) -> u32 {
+        self.min_component_size
+    }
+    pub fn region(&self) -> Option<Arc<Region>> {
+        REGION.get().and_then(|region| region.clone())
+    }
+    pub fn refinement_levels(&self) -> u32 {
+        self.refinement_levels
+    }
     pub fn build_graph_on_startup(&self) -> bool {
         self.build_graph_on_startup
     }
     pub fn geojson_export_path(&self) -> &Option<String> {
         &self.geojson_export_path
     }
+    pub fn contraction_hierarchies(&self) -> bool {
+        self.contraction_hierarchies
+    }
+    pub fn landmarks(&self) -> u32 {
+        self.landmarks
+    }
+    pub fn landmark_selection(&self) -> LandmarkSelection {
+        self.landmark_selection.parse().unwrap()
+    }
+    pub fn smooth_routes(&self) -> bool {
+        self.smooth_routes
+    }
+    pub fn alternative_route_limits(&self) -> AlternativeRouteLimits {
+        AlternativeRouteLimits { maximum_overlap: self.alternative_max_overlap, maximum_stretch: self.alternative_max_stretch }
+    }
+    pub fn service_speed(&self) -> f64 {
+        self.service_speed
+    }
+    pub fn fuel_consumption(&self) -> f64 {
+        self.fuel_consumption
+    }
+    pub fn ship_profiles(&self) -> &Option<String> {
+        &self.ship_profiles
+    }
+    pub fn passages(&self) -> &Option<String> {
+        &self.passages
+    }
+    pub fn current_field(&self) -> &Option<String> {
+        &self.current_field
+    }
+    pub fn wind_field(&self) -> &Option<String> {
+        &self.wind_field
+    }
+    pub fn wind_factor(&self) -> f64 {
+        self.wind_factor
+    }
     pub fn max_test(&self) -> bool {
         self.max_test
     }
+    pub fn validate_graph(&self) -> &Option<String> {
+        &self.validate_graph
+    }
 }
\ No newline at end of file
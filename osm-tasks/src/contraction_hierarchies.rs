@@ -0,0 +1,366 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::dijkstra::AdjacencyArray;
+
+/// Marks an edge of the original graph, which is not a shortcut
+const NO_MIDDLE_NODE: u32 = u32::MAX;
+/// Maximum number of nodes settled by a single witness search. A witness search which is
+/// stopped early may insert unnecessary shortcuts, but never misses a required one.
+const WITNESS_SEARCH_SETTLED_NODES_LIMIT: u32 = 100;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+pub struct ContractedEdge {
+    target: u32,
+    distance: u32,
+    // the contracted node this shortcut skips or NO_MIDDLE_NODE for edges of the original graph
+    middle_node: u32,
+}
+
+/// Graph augmented with the shortcuts of a contraction hierarchy.
+/// Every edge is stored once at the node with the lower rank. The edges of the grid graph are symmetric,
+/// so the same upward edges are used by the forward and the backward search.
+#[derive(Serialize, Deserialize)]
+pub struct ContractedGraph {
+    // index equals node id, nodes with a higher rank have been contracted later
+    ranks: Vec<u32>,
+    upward_offsets: Vec<u32>,
+    upward_edges: Vec<ContractedEdge>,
+}
+
+impl ContractedGraph {
+    /// Contracts all nodes of the graph in the order given by the edge difference, the number of contracted neighbors and the level
+    pub fn new(adjacency_array: &AdjacencyArray) -> ContractedGraph {
+        let start_time = Instant::now();
+        let number_of_nodes = adjacency_array.get_nodes_count() as usize;
+        // edges between nodes which are not yet contracted, including the shortcuts inserted so far
+        let mut edges: Vec<Vec<ContractedEdge>> = (0..number_of_nodes).map(|node| {
+            let mut node_edges = Vec::with_capacity(8);
+            let neighbors_and_distances = adjacency_array.get_neighbors_of_node_and_distances(node as u32);
+            for i in (0..neighbors_and_distances.len()).step_by(2) {
+                if neighbors_and_distances[i] != node as u32 {
+                    insert_or_decrease_edge(&mut node_edges, ContractedEdge { target: neighbors_and_distances[i], distance: neighbors_and_distances[i + 1], middle_node: NO_MIDDLE_NODE });
+                }
+            }
+            node_edges
+        }).collect();
+        let mut upward_edges: Vec<Vec<ContractedEdge>> = vec![Vec::new(); number_of_nodes];
+        let mut ranks = vec![u32::MAX; number_of_nodes];
+        // number of contracted neighbors and the depth of the node in the hierarchy, used to contract the nodes uniformly
+        let mut contracted_neighbors = vec![0i32; number_of_nodes];
+        let mut levels = vec![0i32; number_of_nodes];
+        let mut witness_search = WitnessSearch::new(number_of_nodes);
+
+        let mut queue: BinaryHeap<Reverse<(i32, u32)>> = (0..number_of_nodes as u32).map(|node| {
+            Reverse((calculate_priority(&edges, &mut witness_search, &contracted_neighbors, &levels, node).0, node))
+        }).collect();
+        println!("Calculated initial node order for contraction in {} seconds", start_time.elapsed().as_secs());
+        let mut number_of_shortcuts: usize = 0;
+        let mut rank = 0;
+        while let Some(Reverse((_, node))) = queue.pop() {
+            // lazy update: contract the node only if it is still the node with the lowest priority
+            let (priority, shortcuts) = calculate_priority(&edges, &mut witness_search, &contracted_neighbors, &levels, node);
+            if let Some(Reverse((next_priority, _))) = queue.peek() {
+                if priority > *next_priority {
+                    queue.push(Reverse((priority, node)));
+                    continue;
+                }
+            }
+            number_of_shortcuts += shortcuts.len();
+            // all remaining edges of the node lead to nodes, which will be contracted later
+            let node_edges = std::mem::take(&mut edges[node as usize]);
+            for edge in &node_edges {
+                edges[edge.target as usize].retain(|e| e.target != node);
+                contracted_neighbors[edge.target as usize] += 1;
+                levels[edge.target as usize] = levels[edge.target as usize].max(levels[node as usize] + 1);
+            }
+            for (source, target, distance) in shortcuts {
+                insert_or_decrease_edge(&mut edges[source as usize], ContractedEdge { target, distance, middle_node: node });
+                insert_or_decrease_edge(&mut edges[target as usize], ContractedEdge { target: source, distance, middle_node: node });
+            }
+            upward_edges[node as usize] = node_edges;
+            ranks[node as usize] = rank;
+            rank += 1;
+            if number_of_nodes >= 10 && rank as usize % (number_of_nodes / 10) == 0 {
+                println!("Contracting graph: {}%", (rank as f64 / number_of_nodes as f64 * 100.0).round() as i32);
+            }
+        }
+        let mut upward_offsets = Vec::with_capacity(number_of_nodes + 1);
+        upward_offsets.push(0);
+        let mut last_offset = 0;
+        for node_edges in &upward_edges {
+            last_offset += node_edges.len() as u32;
+            upward_offsets.push(last_offset);
+        }
+        println!("Contracted graph with {} shortcuts in {} seconds", number_of_shortcuts, start_time.elapsed().as_secs());
+        ContractedGraph {
+            ranks,
+            upward_offsets,
+            upward_edges: upward_edges.concat(),
+        }
+    }
+
+    pub fn get_nodes_count(&self) -> u32 {
+        self.ranks.len() as u32
+    }
+
+    fn get_upward_edges(&self, node: u32) -> &[ContractedEdge] {
+        &self.upward_edges[(self.upward_offsets[node as usize] as usize)..(self.upward_offsets[node as usize + 1] as usize)]
+    }
+
+    /// Appends the nodes of the original graph, which are covered by the edge from `from` to `to`, to the path.
+    /// The node `from` is expected to be the last node of the path already.
+    fn unpack_edge(&self, from: u32, to: u32, path: &mut Vec<u32>) {
+        let (lower_node, higher_node) = if self.ranks[from as usize] < self.ranks[to as usize] { (from, to) } else { (to, from) };
+        let edge = self.get_upward_edges(lower_node).iter()
+            .filter(|e| e.target == higher_node)
+            .min_by_key(|e| e.distance)
+            .expect("Invariant violated: edge of the route is not part of the contracted graph");
+        if edge.middle_node == NO_MIDDLE_NODE {
+            path.push(to);
+        } else {
+            let middle_node = edge.middle_node;
+            self.unpack_edge(from, middle_node, path);
+            self.unpack_edge(middle_node, to, path);
+        }
+    }
+}
+
+fn insert_or_decrease_edge(edges: &mut Vec<ContractedEdge>, new_edge: ContractedEdge) {
+    if let Some(edge) = edges.iter_mut().find(|e| e.target == new_edge.target) {
+        if new_edge.distance < edge.distance {
+            *edge = new_edge;
+        }
+    } else {
+        edges.push(new_edge);
+    }
+}
+
+/// Weighted sum of the edge difference, the number of already contracted neighbors and the level of the node,
+/// lower values are contracted first. Returns the priority and the shortcuts required to contract the node
+fn calculate_priority(edges: &Vec<Vec<ContractedEdge>>, witness_search: &mut WitnessSearch, contracted_neighbors: &Vec<i32>, levels: &Vec<i32>, node: u32) -> (i32, Vec<(u32, u32, u32)>) {
+    let shortcuts = find_shortcuts(edges, witness_search, node);
+    let edge_difference = shortcuts.len() as i32 - edges[node as usize].len() as i32;
+    (2 * edge_difference + contracted_neighbors[node as usize] + levels[node as usize], shortcuts)
+}
+
+/// Returns the shortcuts (source, target, distance) which are required if the node is contracted
+fn find_shortcuts(edges: &Vec<Vec<ContractedEdge>>, witness_search: &mut WitnessSearch, node: u32) -> Vec<(u32, u32, u32)> {
+    let node_edges = &edges[node as usize];
+    let mut shortcuts = Vec::new();
+    if node_edges.len() < 2 {
+        return shortcuts;
+    }
+    for (i, first_edge) in node_edges.iter().enumerate() {
+        // the edges are symmetric, so it is sufficient to check every pair of neighbors once
+        let other_edges = &node_edges[(i + 1)..];
+        if other_edges.is_empty() {
+            break;
+        }
+        let maximum_distance = first_edge.distance + other_edges.iter().map(|e| e.distance).max().unwrap();
+        witness_search.run(edges, first_edge.target, node, other_edges, maximum_distance);
+        for second_edge in other_edges {
+            let distance_via_node = first_edge.distance + second_edge.distance;
+            if witness_search.distance(second_edge.target) > distance_via_node {
+                shortcuts.push((first_edge.target, second_edge.target, distance_via_node));
+            }
+        }
+    }
+    shortcuts
+}
+
+/// Local Dijkstra search, which checks if a route between two neighbors of a node exists without this node
+struct WitnessSearch {
+    distances: Vec<u32>,
+    touched_nodes: Vec<u32>,
+    heap: BinaryHeap<Reverse<(u32, u32)>>,
+}
+
+impl WitnessSearch {
+    fn new(number_of_nodes: usize) -> WitnessSearch {
+        WitnessSearch {
+            distances: vec![u32::MAX; number_of_nodes],
+            touched_nodes: Vec::new(),
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    /// Searches routes from the source node to the targets of the given edges, which do not use the ignored node
+    fn run(&mut self, edges: &Vec<Vec<ContractedEdge>>, source_node: u32, ignored_node: u32, target_edges: &[ContractedEdge], maximum_distance: u32) {
+        for node in self.touched_nodes.drain(..) {
+            self.distances[node as usize] = u32::MAX;
+        }
+        self.heap.clear();
+        self.distances[source_node as usize] = 0;
+        self.touched_nodes.push(source_node);
+        self.heap.push(Reverse((0, source_node)));
+        let mut settled_nodes = 0;
+        let mut unsettled_targets = target_edges.len();
+        while let Some(Reverse((distance, node))) = self.heap.pop() {
+            if distance > self.distances[node as usize] {
+                continue;
+            }
+            settled_nodes += 1;
+            if distance > maximum_distance || settled_nodes > WITNESS_SEARCH_SETTLED_NODES_LIMIT {
+                break;
+            }
+            if target_edges.iter().any(|e| e.target == node) {
+                unsettled_targets -= 1;
+                if unsettled_targets == 0 {
+                    break;
+                }
+            }
+            for edge in &edges[node as usize] {
+                if edge.target == ignored_node {
+                    continue;
+                }
+                let next_distance = distance + edge.distance;
+                if next_distance < self.distances[edge.target as usize] {
+                    if self.distances[edge.target as usize] == u32::MAX {
+                        self.touched_nodes.push(edge.target);
+                    }
+                    self.distances[edge.target as usize] = next_distance;
+                    self.heap.push(Reverse((next_distance, edge.target)));
+                }
+            }
+        }
+    }
+
+    /// Length of the shortest route found by the last search or u32::MAX if the node has not been reached
+    fn distance(&self, node: u32) -> u32 {
+        self.distances[node as usize]
+    }
+}
+
+/// Bidirectional Dijkstra, which only follows edges to nodes with a higher rank in the contracted graph
+pub(crate) struct ContractionHierarchies {
+    graph_ref: Arc<ContractedGraph>,
+    heap_forward: BinaryHeap<Reverse<(u32, u32)>>,
+    heap_backward: BinaryHeap<Reverse<(u32, u32)>>,
+    distances_forward: Vec<u32>,
+    distances_backward: Vec<u32>,
+    previous_nodes_forward: Vec<u32>,
+    previous_nodes_backward: Vec<u32>,
+    // nodes with a distance in one of the searches, used to reset the search state without iterating all nodes
+    touched_nodes: Vec<u32>,
+    settled_nodes: u32,
+}
+
+impl ContractionHierarchies {
+    pub fn new(graph: Arc<ContractedGraph>) -> ContractionHierarchies {
+        let number_of_nodes = graph.get_nodes_count() as usize;
+        ContractionHierarchies {
+            graph_ref: graph,
+            heap_forward: BinaryHeap::new(),
+            heap_backward: BinaryHeap::new(),
+            distances_forward: vec![u32::MAX; number_of_nodes],
+            distances_backward: vec![u32::MAX; number_of_nodes],
+            previous_nodes_forward: vec![u32::MAX; number_of_nodes],
+            previous_nodes_backward: vec![u32::MAX; number_of_nodes],
+            touched_nodes: Vec::new(),
+            settled_nodes: 0,
+        }
+    }
+
+    /// Number of nodes settled by both searches of the last query
+    pub fn settled_nodes(&self) -> u32 {
+        self.settled_nodes
+    }
+
+    /// Route between two nodes, the queries of the navigator connect the stops to several nodes with find_route_between_nodes
+    #[allow(dead_code)]
+    pub fn find_route(&mut self, source_node: u32, destination_node: u32) -> Option<(Vec<u32>, u32)> {
+        self.find_route_between_nodes(&[(source_node, 0)], &[(destination_node, 0)])
+    }
+
+    /// Calculates the shortest route from one of the source nodes to one of the destination nodes, which have an additional distance
+    /// at the start or the end of the route. Returns the unpacked route and its distance including the additional distances
+    pub fn find_route_between_nodes(&mut self, sources: &[(u32, u32)], destinations: &[(u32, u32)]) -> Option<(Vec<u32>, u32)> {
+        for node in self.touched_nodes.drain(..) {
+            self.distances_forward[node as usize] = u32::MAX;
+            self.distances_backward[node as usize] = u32::MAX;
+        }
+        self.heap_forward.clear();
+        self.heap_backward.clear();
+        self.settled_nodes = 0;
+        // the source and destination nodes are their own previous node
+        for (source_node, source_distance) in sources {
+            if *source_distance < self.distances_forward[*source_node as usize] {
+                self.distances_forward[*source_node as usize] = *source_distance;
+                self.previous_nodes_forward[*source_node as usize] = *source_node;
+                self.touched_nodes.push(*source_node);
+                self.heap_forward.push(Reverse((*source_distance, *source_node)));
+            }
+        }
+        for (destination_node, destination_distance) in destinations {
+            if *destination_distance < self.distances_backward[*destination_node as usize] {
+                self.distances_backward[*destination_node as usize] = *destination_distance;
+                self.previous_nodes_backward[*destination_node as usize] = *destination_node;
+                self.touched_nodes.push(*destination_node);
+                self.heap_backward.push(Reverse((*destination_distance, *destination_node)));
+            }
+        }
+
+        let mut best_distance = u32::MAX;
+        let mut meeting_node = None;
+        let mut forward = true;
+        loop {
+            // each search can stop if its next node is further away than the best route found so far
+            let forward_finished = self.heap_forward.peek().map_or(true, |Reverse((distance, _))| *distance >= best_distance);
+            let backward_finished = self.heap_backward.peek().map_or(true, |Reverse((distance, _))| *distance >= best_distance);
+            if forward_finished && backward_finished {
+                break;
+            }
+            forward = if forward_finished { false } else if backward_finished { true } else { !forward };
+            let (heap, distances, previous_nodes, other_distances) = if forward {
+                (&mut self.heap_forward, &mut self.distances_forward, &mut self.previous_nodes_forward, &self.distances_backward)
+            } else {
+                (&mut self.heap_backward, &mut self.distances_backward, &mut self.previous_nodes_backward, &self.distances_forward)
+            };
+            let Reverse((distance, node)) = heap.pop().unwrap();
+            if distance > distances[node as usize] {
+                continue;
+            }
+            self.settled_nodes += 1;
+            if other_distances[node as usize] != u32::MAX && distance + other_distances[node as usize] < best_distance {
+                best_distance = distance + other_distances[node as usize];
+                meeting_node = Some(node);
+            }
+            for edge in self.graph_ref.get_upward_edges(node) {
+                let next_distance = distance + edge.distance;
+                if next_distance < distances[edge.target as usize] {
+                    if distances[edge.target as usize] == u32::MAX && other_distances[edge.target as usize] == u32::MAX {
+                        self.touched_nodes.push(edge.target);
+                    }
+                    distances[edge.target as usize] = next_distance;
+                    previous_nodes[edge.target as usize] = node;
+                    heap.push(Reverse((next_distance, edge.target)));
+                }
+            }
+        }
+        meeting_node.map(|node| (self.unpack_route(node), best_distance))
+    }
+
+    fn unpack_route(&self, meeting_node: u32) -> Vec<u32> {
+        // route through the contracted graph, containing shortcuts
+        let mut contracted_route = vec![meeting_node];
+        let mut node = meeting_node;
+        while self.previous_nodes_forward[node as usize] != node {
+            node = self.previous_nodes_forward[node as usize];
+            contracted_route.push(node);
+        }
+        contracted_route.reverse();
+        node = meeting_node;
+        while self.previous_nodes_backward[node as usize] != node {
+            node = self.previous_nodes_backward[node as usize];
+            contracted_route.push(node);
+        }
+        let mut route = vec![contracted_route[0]];
+        for i in 1..contracted_route.len() {
+            self.graph_ref.unpack_edge(contracted_route[i - 1], contracted_route[i], &mut route);
+        }
+        route
+    }
+}
@@ -0,0 +1,277 @@
+use crate::grid_graph::{distance, GridGraph, Node};
+use crate::persistence::in_memory_routing_repo::{CostModel, CostWeights, ClearanceHandling};
+use crate::ship_profile::ShipProfile;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+/// Factor of the cost of the edges, which are closer to the coast than the minimum clearance, if they are penalized
+const CLEARANCE_PENALTY_FACTOR: u32 = 10;
+
+/**
+Cost of travelling along a segment, which is minimized by the route calculation.
+The distance, the time and the fuel at the service speed and their weighted sum are proportional to the distance, so they have the same
+optimal routes. These routes are searched on the distances of the graph with every algorithm and only their costs differ.
+The costs, which depend on more than the distance, like the clearance to the coast, the avoid areas, changed passages or the weather,
+are calculated while searching, so the graph is the same for all cost models, but only Dijkstra and A* support them.
+**/
+pub trait CostFunction: Send + Sync {
+    /// Cost of the segment between both nodes, which has the given length in meters
+    fn segment_cost(&self, from: &Node, to: &Node, distance: f64) -> f64;
+    /// Lower bound of the cost of a meter, which keeps the great-circle potential of A* a lower bound of the costs
+    fn minimum_cost_per_meter(&self) -> f64;
+    /// Cost of waiting the given number of seconds, e.g. at the entrance of a canal
+    fn waiting_cost(&self, seconds: f64) -> f64;
+
+    /// Cost of an edge of the graph, rounded up like the connections of the query points
+    fn edge_cost(&self, from: &Node, to: &Node, distance: u32) -> u32 {
+        self.segment_cost(from, to, distance as f64).ceil() as u32
+    }
+
+    /// Cost of the edge between two nodes of the graph. Cost functions, which depend on the edges of the graph, use the node ids
+    fn graph_edge_cost(&self, _from_node: u32, _to_node: u32, from: &Node, to: &Node, distance: u32) -> u32 {
+        self.edge_cost(from, to, distance)
+    }
+
+    /// Converts a lower bound of the distance in meters into a lower bound of the cost
+    fn potential(&self, distance: u32) -> u32 {
+        (distance as f64 * self.minimum_cost_per_meter()) as u32
+    }
+
+    /// True if the cost of every segment is a constant multiple of its distance, so the shortest route has the lowest cost, too
+    fn is_proportional_to_distance(&self) -> bool {
+        false
+    }
+}
+
+/// Cost in meters
+pub struct DistanceCost;
+
+impl CostFunction for DistanceCost {
+    fn segment_cost(&self, _from: &Node, _to: &Node, distance: f64) -> f64 {
+        distance
+    }
+    fn minimum_cost_per_meter(&self) -> f64 {
+        1.0
+    }
+    fn waiting_cost(&self, _seconds: f64) -> f64 {
+        0.0
+    }
+    fn is_proportional_to_distance(&self) -> bool {
+        true
+    }
+}
+
+/// Cost in seconds at the service speed
+pub struct TimeCost {
+    meters_per_second: f64,
+}
+
+impl CostFunction for TimeCost {
+    fn segment_cost(&self, _from: &Node, _to: &Node, distance: f64) -> f64 {
+        distance / self.meters_per_second
+    }
+    fn minimum_cost_per_meter(&self) -> f64 {
+        1.0 / self.meters_per_second
+    }
+    fn waiting_cost(&self, seconds: f64) -> f64 {
+        seconds
+    }
+    fn is_proportional_to_distance(&self) -> bool {
+        true
+    }
+}
+
+/// Cost in kilograms of fuel burned at the service speed. A waiting ship is assumed to burn fuel at the same rate
+pub struct FuelCost {
+    kilograms_per_meter: f64,
+    kilograms_per_second: f64,
+}
+
+impl CostFunction for FuelCost {
+    fn segment_cost(&self, _from: &Node, _to: &Node, distance: f64) -> f64 {
+        distance * self.kilograms_per_meter
+    }
+    fn minimum_cost_per_meter(&self) -> f64 {
+        self.kilograms_per_meter
+    }
+    fn waiting_cost(&self, seconds: f64) -> f64 {
+        seconds * self.kilograms_per_second
+    }
+    fn is_proportional_to_distance(&self) -> bool {
+        true
+    }
+}
+
+/// Weighted sum of other cost functions
+pub struct WeightedCost {
+    cost_functions: Vec<(f64, Box<dyn CostFunction>)>,
+}
+
+impl CostFunction for WeightedCost {
+    fn segment_cost(&self, from: &Node, to: &Node, distance: f64) -> f64 {
+        self.cost_functions.iter().map(|(weight, cost_function)| weight * cost_function.segment_cost(from, to, distance)).sum()
+    }
+    fn minimum_cost_per_meter(&self) -> f64 {
+        self.cost_functions.iter().map(|(weight, cost_function)| weight * cost_function.minimum_cost_per_meter()).sum()
+    }
+    fn waiting_cost(&self, seconds: f64) -> f64 {
+        self.cost_functions.iter().map(|(weight, cost_function)| weight * cost_function.waiting_cost(seconds)).sum()
+    }
+    fn is_proportional_to_distance(&self) -> bool {
+        self.cost_functions.iter().all(|(_, cost_function)| cost_function.is_proportional_to_distance())
+    }
+}
+
+/// Cost function, which penalizes or excludes the edges of the graph closer to the coast than the minimum clearance
+pub struct ClearanceCost<'a> {
+    cost_function: Box<dyn CostFunction + 'a>,
+    graph: &'a GridGraph,
+    // minimum distance to the coast in meters
+    minimum_coast_distance: u32,
+    clearance_handling: ClearanceHandling,
+}
+
+impl<'a> ClearanceCost<'a> {
+    pub fn new(cost_function: Box<dyn CostFunction + 'a>, graph: &'a GridGraph, minimum_coast_distance: u32, clearance_handling: ClearanceHandling) -> ClearanceCost<'a> {
+        ClearanceCost { cost_function, graph, minimum_coast_distance, clearance_handling }
+    }
+}
+
+impl<'a> CostFunction for ClearanceCost<'a> {
+    fn segment_cost(&self, from: &Node, to: &Node, distance: f64) -> f64 {
+        self.cost_function.segment_cost(from, to, distance)
+    }
+    fn minimum_cost_per_meter(&self) -> f64 {
+        self.cost_function.minimum_cost_per_meter()
+    }
+    fn waiting_cost(&self, seconds: f64) -> f64 {
+        self.cost_function.waiting_cost(seconds)
+    }
+    fn graph_edge_cost(&self, from_node: u32, to_node: u32, from: &Node, to: &Node, distance: u32) -> u32 {
+        let cost = self.cost_function.graph_edge_cost(from_node, to_node, from, to, distance);
+        // edges, which are not part of the grid like the passages, have no distance to the coast
+        match self.graph.edge_coast_distance(from_node, to_node) {
+            Some(coast_distance) if coast_distance < self.minimum_coast_distance => match self.clearance_handling {
+                ClearanceHandling::Penalize => cost.saturating_mul(CLEARANCE_PENALTY_FACTOR),
+                ClearanceHandling::Exclude => u32::MAX
+            },
+            _ => cost
+        }
+    }
+}
+
+/// Creates the cost function of the cost model for the ship
+pub fn create_cost_function(cost_model: CostModel, weights: &CostWeights, ship_profile: &ShipProfile) -> Box<dyn CostFunction> {
+    let meters_per_second = ship_profile.meters_per_second();
+    match cost_model {
+        CostModel::Distance => Box::new(DistanceCost),
+        CostModel::Time => Box::new(TimeCost { meters_per_second }),
+        CostModel::Fuel => {
+            let kilograms_per_second = ship_profile.fuel_consumption * 1000.0 / SECONDS_PER_DAY;
+            Box::new(FuelCost { kilograms_per_meter: kilograms_per_second / meters_per_second, kilograms_per_second })
+        }
+        CostModel::Weighted => {
+            let cost_functions: Vec<(f64, Box<dyn CostFunction>)> = vec![
+                (weights.distance, create_cost_function(CostModel::Distance, weights, ship_profile)),
+                (weights.time, create_cost_function(CostModel::Time, weights, ship_profile)),
+                (weights.fuel, create_cost_function(CostModel::Fuel, weights, ship_profile)),
+            ];
+            // negative weights would break the lower bounds of the search
+            Box::new(WeightedCost { cost_functions: cost_functions.into_iter().filter(|(weight, _)| *weight > 0.0).collect() })
+        }
+    }
+}
+
+/// Cost of a route along great circles, which leaves out the extra costs of the edges of the graph like the penalties of the passages
+pub fn route_cost(cost_function: &dyn CostFunction, route: &Vec<Node>) -> u32 {
+    route.windows(2).map(|segment| cost_function.segment_cost(&segment[0], &segment[1], distance(segment[0].lon, segment[0].lat, segment[1].lon, segment[1].lat))).sum::<f64>() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid_graph::{Edge, GridGraph, Node};
+    use crate::persistence::in_memory_routing_repo::{ClearanceHandling, CostModel, CostWeights};
+    use crate::ship_profile::ShipProfile;
+    use super::{create_cost_function, route_cost, ClearanceCost, CostFunction, DistanceCost};
+
+    /// Ship with a service speed of 10 knots, which burns 24 tonnes of fuel per day
+    fn ship_profile() -> ShipProfile {
+        ShipProfile { name: String::from("test"), speed: 10.0, fuel_consumption: 24.0, max_draft: 0.0, min_coast_distance: 0.0 }
+    }
+
+    fn weights(distance: f64, time: f64, fuel: f64) -> CostWeights {
+        CostWeights { distance, time, fuel }
+    }
+
+    /// Path of three nodes, whose first edge passes the coast at 100 meters and whose second edge at 5 kilometers
+    fn graph() -> GridGraph {
+        let mut graph = GridGraph::default();
+        graph.number_nodes = 3;
+        graph.nodes = vec![Node { lon: 10.0, lat: 50.0 }, Node { lon: 10.1, lat: 50.0 }, Node { lon: 10.2, lat: 50.0 }];
+        graph.offsets = vec![0, 1, 3, 4];
+        graph.edges = vec![
+            Edge { source: 0, target: 1, distance: 7_150 },
+            Edge { source: 1, target: 0, distance: 7_150 },
+            Edge { source: 1, target: 2, distance: 7_150 },
+            Edge { source: 2, target: 1, distance: 7_150 },
+        ];
+        graph.edge_coast_distances = vec![100, 100, 5_000, 5_000];
+        graph
+    }
+
+    #[test]
+    fn costs_of_the_models_at_the_service_speed() {
+        let (from, to) = (Node { lon: 10.0, lat: 50.0 }, Node { lon: 10.1, lat: 50.0 });
+        let profile = ship_profile();
+        let meters_per_second = 10.0 * 1852.0 / 3600.0;
+        let no_weights = weights(0.0, 0.0, 0.0);
+        let distance = create_cost_function(CostModel::Distance, &no_weights, &profile);
+        let time = create_cost_function(CostModel::Time, &no_weights, &profile);
+        let fuel = create_cost_function(CostModel::Fuel, &no_weights, &profile);
+        assert_eq!(distance.segment_cost(&from, &to, 1852.0), 1852.0);
+        assert!((time.segment_cost(&from, &to, 1852.0) - 360.0).abs() < 1e-6);
+        // 1 kilogram per 3.6 seconds
+        assert!((fuel.segment_cost(&from, &to, 1852.0) - 100.0).abs() < 1e-6);
+        assert!((fuel.waiting_cost(3600.0) - 1000.0).abs() < 1e-6);
+        assert_eq!(time.waiting_cost(60.0), 60.0);
+        assert_eq!(distance.waiting_cost(60.0), 0.0);
+        assert!((time.minimum_cost_per_meter() - 1.0 / meters_per_second).abs() < 1e-9);
+        assert!([&distance, &time, &fuel].iter().all(|cost_function| cost_function.is_proportional_to_distance()));
+    }
+
+    #[test]
+    fn weighted_cost_sums_the_positive_weights() {
+        let (from, to) = (Node { lon: 10.0, lat: 50.0 }, Node { lon: 10.1, lat: 50.0 });
+        let weighted = create_cost_function(CostModel::Weighted, &weights(2.0, -1.0, 0.5), &ship_profile());
+        // the negative weight of the time is ignored
+        assert!((weighted.segment_cost(&from, &to, 1852.0) - (2.0 * 1852.0 + 0.5 * 100.0)).abs() < 1e-6);
+        assert!((weighted.waiting_cost(3600.0) - 500.0).abs() < 1e-6);
+        assert!(weighted.is_proportional_to_distance());
+    }
+
+    #[test]
+    fn potential_is_a_lower_bound_of_the_edge_costs() {
+        let (from, to) = (Node { lon: 10.0, lat: 50.0 }, Node { lon: 10.1, lat: 50.0 });
+        for cost_model in &[CostModel::Distance, CostModel::Time, CostModel::Fuel, CostModel::Weighted] {
+            let cost_function = create_cost_function(*cost_model, &weights(1.0, 3.0, 2.0), &ship_profile());
+            for distance in &[0, 1, 7, 1_000, 7_151, 123_457] {
+                assert!(cost_function.potential(*distance) <= cost_function.edge_cost(&from, &to, *distance), "{:?} {}", cost_model, distance);
+            }
+        }
+    }
+
+    #[test]
+    fn edges_near_the_coast_are_penalized_or_excluded() {
+        let graph = graph();
+        let (from, via, to) = (graph.nodes[0], graph.nodes[1], graph.nodes[2]);
+        let penalize = ClearanceCost::new(Box::new(DistanceCost), &graph, 1_000, ClearanceHandling::Penalize);
+        assert_eq!(penalize.graph_edge_cost(0, 1, &from, &via, 7_150), 71_500);
+        assert_eq!(penalize.graph_edge_cost(1, 2, &via, &to, 7_150), 7_150);
+        // edges outside of the grid like the passages have no distance to the coast
+        assert_eq!(penalize.graph_edge_cost(0, 2, &from, &to, 14_300), 14_300);
+        let exclude = ClearanceCost::new(Box::new(DistanceCost), &graph, 1_000, ClearanceHandling::Exclude);
+        assert_eq!(exclude.graph_edge_cost(1, 0, &via, &from, 7_150), u32::MAX);
+        assert_eq!(exclude.graph_edge_cost(2, 1, &to, &via, 7_150), 7_150);
+        // the costs along great circles are not affected
+        assert_eq!(route_cost(&exclude, &graph.nodes), route_cost(&DistanceCost, &graph.nodes));
+    }
+}
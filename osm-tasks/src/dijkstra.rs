@@ -1,6 +1,13 @@
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::fmt;
+use std::sync::Arc;
+use crate::grid_graph::{distance, Node};
+
+/// The great-circle distance is scaled down by this factor to make up for the rounding errors of the distance calculation.
+/// The edge distances are rounded up and the potential is rounded down to whole meters, so the potential of a node is at most
+/// the distance of an edge plus the potential of its target and A* and ALT never settle a node before its shortest distance is known.
+const GREAT_CIRCLE_POTENTIAL_FACTOR: f64 = 0.999;
 
 #[allow(dead_code)]
 pub(crate) struct DummyGraph {
@@ -60,14 +67,50 @@ impl AdjacencyArray {
     pub(crate) fn get_nodes_count(&self) -> u32 {
         self.edges_and_distances_offsets.len() as u32 - 1
     }
+    /// Distance of the edge between two nodes, if the nodes are adjacent
+    pub(crate) fn get_edge_distance(&self, from_node: u32, to_node: u32) -> Option<u32> {
+        let neighbors_and_distances = self.get_neighbors_of_node_and_distances(from_node);
+        (0..neighbors_and_distances.len()).step_by(2)
+            .find(|i| neighbors_and_distances[*i] == to_node)
+            .map(|i| neighbors_and_distances[i + 1])
+    }
+    /// Sum of the edge distances along a route
+    pub(crate) fn route_distance(&self, route: &Vec<u32>) -> u32 {
+        route.windows(2).map(|edge| self.get_edge_distance(edge[0], edge[1]).unwrap_or(0)).sum()
+    }
+    /// Copy of the adjacency array with additional edges, which are inserted in both directions
+    pub(crate) fn with_extra_edges(&self, extra_edges: &Vec<(u32, u32, u32)>) -> AdjacencyArray {
+        let number_of_nodes = self.get_nodes_count() as usize;
+        let mut node_extra_edges: Vec<Vec<(u32, u32)>> = vec![Vec::new(); number_of_nodes];
+        for (from_node, to_node, distance) in extra_edges {
+            node_extra_edges[*from_node as usize].push((*to_node, *distance));
+            node_extra_edges[*to_node as usize].push((*from_node, *distance));
+        }
+        let mut edges_and_distances_offsets = Vec::with_capacity(number_of_nodes + 1);
+        let mut edges_and_distances = Vec::with_capacity(self.edges_and_distances.len() + extra_edges.len() * 4);
+        edges_and_distances_offsets.push(0);
+        for (node, extra_edges_of_node) in node_extra_edges.iter().enumerate() {
+            edges_and_distances.extend_from_slice(self.get_neighbors_of_node_and_distances(node as u32));
+            for (to_node, distance) in extra_edges_of_node {
+                edges_and_distances.push(*to_node);
+                edges_and_distances.push(*distance);
+            }
+            edges_and_distances_offsets.push(edges_and_distances.len() as u32);
+        }
+        AdjacencyArray::new(edges_and_distances_offsets, edges_and_distances)
+    }
 }
 
 pub(crate) struct Dijkstra {
-    graph_ref: AdjacencyArray,
+    graph_ref: Arc<AdjacencyArray>,
     heap: BinaryHeap<HeapItem>,
     distances: Vec<u32>,
     previous_nodes: Vec<u32>,
     source_node: u32,
+    // true if the heap and the distances belong to a plain one-to-all search from the source node,
+    // which can be continued for further destination nodes
+    reusable_search_state: bool,
+    settled_nodes: u32,
 }
 
 #[derive(Debug)]
@@ -75,6 +118,8 @@ struct HeapItem {
     node_id: u32,
     distance: u32,
     previous_node: u32,
+    // seconds since the departure at the node, which are only tracked by the time dependent search
+    time: u32,
 }
 
 impl fmt::Display for HeapItem {
@@ -105,7 +150,7 @@ impl Ord for HeapItem {
 }
 
 impl Dijkstra {
-    pub fn new(graph: AdjacencyArray, source_node: u32) -> Dijkstra {
+    pub fn new(graph: Arc<AdjacencyArray>, source_node: u32) -> Dijkstra {
         //println!("New dijkstra instance with source node {}", source_node);
         let number_of_nodes = graph.get_nodes_count() as usize;
         // Todo: Ist es sinnvoll den heap mit der Anzahl der Knoten zu initialisieren?
@@ -116,31 +161,95 @@ impl Dijkstra {
             node_id: source_node,
             distance: 0,
             previous_node: source_node,
+            time: 0,
         });
-        return Dijkstra { graph_ref: graph, heap, distances, previous_nodes, source_node };
+        return Dijkstra { graph_ref: graph, heap, distances, previous_nodes, source_node, reusable_search_state: true, settled_nodes: 0 };
     }
 
     pub fn change_source_node(&mut self, source_node: u32) {
-        if source_node == self.source_node {
+        if source_node == self.source_node && self.reusable_search_state {
             return;
         }
         //println!("Reinitialized dijkstra for new source node {}", source_node);
+        self.reset_search_state(source_node);
+        self.reusable_search_state = true;
+    }
+
+    fn reset_search_state(&mut self, source_node: u32) {
         self.source_node = source_node;
         self.heap.clear();
         self.heap.push(HeapItem {
             node_id: source_node,
             distance: 0,
             previous_node: source_node,
+            time: 0,
         });
         self.distances.fill(u32::MAX);
         self.previous_nodes.fill(u32::MAX);
+        self.settled_nodes = 0;
+    }
+
+    /// Number of nodes settled by the searches since the last reset of the search state
+    pub fn settled_nodes(&self) -> u32 {
+        self.settled_nodes
+    }
+
+    /// Calculates the route with the A* algorithm. The potential has to be a consistent lower bound
+    /// of the distance from a node to the destination node, otherwise the route is not optimal.
+    /// The search state can not be reused for other destination nodes.
+    #[allow(dead_code)]
+    pub fn find_route_a_star<F: Fn(u32) -> u32>(&mut self, source_node: u32, destination_node: u32, potential: F) -> Option<(Vec<u32>, u32)> {
+        self.find_route_with_edge_costs(source_node, destination_node, potential, |_, _, distance| distance)
+    }
+
+    /// Calculates the route with the A* algorithm, but uses the cost of every edge instead of its distance.
+    /// The edge cost is called with the start node, the end node and the distance of the edge. Edges with the cost u32::MAX are not used.
+    /// The potential has to be a consistent lower bound of the costs. The returned value is the cost of the route.
+    pub fn find_route_with_edge_costs<F: Fn(u32) -> u32, C: Fn(u32, u32, u32) -> u32>(&mut self, source_node: u32, destination_node: u32, potential: F, edge_cost: C) -> Option<(Vec<u32>, u32)> {
+        self.reset_search_state(source_node);
+        self.reusable_search_state = false;
+        self.a_star(&[(source_node, 0, 0)], &[(destination_node, 0)], potential, |from_node, to_node, distance, _| (edge_cost(from_node, to_node, distance), 0), |_, cost, _| cost)
+            .map(|(destination_node, distance)| (self.traverse_route(&destination_node), distance))
+    }
+
+    /// Calculates the shortest route from one of the source nodes to one of the destination nodes with the A* algorithm.
+    /// Every source and destination node has an additional distance, which is added at the start or the end of the route,
+    /// e.g. the length of a connection from the exact query point to the node. The potential has to be a consistent lower bound
+    /// of the distance to the destination nodes including their additional distances.
+    /// Returns the route between the nodes and its distance including the additional distances.
+    pub fn find_route_between_nodes<F: Fn(u32) -> u32>(&mut self, sources: &Vec<(u32, u32)>, destinations: &Vec<(u32, u32)>, potential: F) -> Option<(Vec<u32>, u32)> {
+        self.find_route_between_nodes_with_edge_costs(sources, destinations, potential, |_, _, distance| distance)
+    }
+
+    /// Like find_route_between_nodes, but uses the cost of every edge instead of its distance.
+    /// The additional values of the sources and destinations have to be costs, too. Returns the route and its cost
+    pub fn find_route_between_nodes_with_edge_costs<F: Fn(u32) -> u32, C: Fn(u32, u32, u32) -> u32>(&mut self, sources: &Vec<(u32, u32)>, destinations: &Vec<(u32, u32)>, potential: F, edge_cost: C) -> Option<(Vec<u32>, u32)> {
+        let sources: Vec<(u32, u32, u32)> = sources.iter().map(|(node, cost)| (*node, *cost, 0)).collect();
+        self.find_route_between_nodes_time_dependent(&sources, destinations, potential, |from_node, to_node, distance, _| (edge_cost(from_node, to_node, distance), 0), |_, cost, _| cost)
+    }
+
+    /// Like find_route_between_nodes_with_edge_costs, but the costs depend on the time, which is tracked separately from the costs.
+    /// Every source is given with its additional cost and the seconds since the departure, at which the source node is reached.
+    /// The edge cost is called with the start node, the end node, the distance of the edge and the seconds at the start node
+    /// and returns the cost and the seconds of the edge. The destination cost is called with the destination node, its additional cost
+    /// and the seconds at the destination node and returns the cost at the end of the route.
+    /// The route is optimal if no route arrives earlier at a node by leaving later
+    pub fn find_route_between_nodes_time_dependent<F, C, D>(&mut self, sources: &Vec<(u32, u32, u32)>, destinations: &Vec<(u32, u32)>, potential: F, edge_cost: C, destination_cost: D) -> Option<(Vec<u32>, u32)>
+        where F: Fn(u32) -> u32, C: Fn(u32, u32, u32, u32) -> (u32, u32), D: Fn(u32, u32, u32) -> u32 {
+        if sources.is_empty() || destinations.is_empty() {
+            return None;
+        }
+        self.reset_search_state(sources[0].0);
+        self.reusable_search_state = false;
+        self.a_star(sources, destinations, potential, edge_cost, destination_cost)
+            .map(|(destination_node, distance)| (self.traverse_route(&destination_node), distance))
     }
 
     pub fn find_route(&mut self, destination_node: u32) -> Option<(Vec<u32>, u32)> {
         if self.distances[destination_node as usize] != u32::MAX {
             return Some((self.traverse_route(&destination_node), self.distances[destination_node as usize]));
         }
-        self.dijkstra(&destination_node);
+        self.dijkstra(Some(destination_node), u32::MAX);
         if self.distances[destination_node as usize] != u32::MAX {
             Some((self.traverse_route(&destination_node), self.distances[destination_node as usize]))
         } else {
@@ -148,7 +257,28 @@ impl Dijkstra {
         }
     }
 
-    fn dijkstra(&mut self, destination_node: &u32) {
+    /// Calculates the distances from the source node to all nodes of the graph. Unreachable nodes have the distance u32::MAX
+    pub fn calculate_distances_to_all_nodes(&mut self, source_node: u32) -> &Vec<u32> {
+        self.change_source_node(source_node);
+        self.dijkstra(None, u32::MAX);
+        &self.distances
+    }
+
+    /// Returns all nodes with a distance of at most the maximum distance from the source node
+    pub fn find_reachable_nodes(&mut self, source_node: u32, maximum_distance: u32) -> Vec<u32> {
+        self.change_source_node(source_node);
+        self.dijkstra(None, maximum_distance);
+        (0..self.distances.len() as u32).filter(|node| self.distances[*node as usize] <= maximum_distance).collect()
+    }
+
+    /// Previous node of every node on the route from the source node. The source node is its own previous node
+    pub fn previous_nodes(&self) -> &Vec<u32> {
+        &self.previous_nodes
+    }
+
+    // searches until the destination node is settled or all nodes are settled if no destination is given.
+    // Nodes further away than the maximum distance are not settled
+    fn dijkstra(&mut self, destination_node: Option<u32>, maximum_distance: u32) {
         loop {
             if let Some(heap_element) = self.heap.pop() {
                 //println!("Popped element from heap {}", heap_element);
@@ -156,8 +286,14 @@ impl Dijkstra {
                     //println!("Skipping heap element {:?} because lower distance is already set: {}", heap_element, self.distances[heap_element.node_id as usize]);
                     continue;
                 }
+                if heap_element.distance > maximum_distance {
+                    // keep the element, so the search can be continued later
+                    self.heap.push(heap_element);
+                    return;
+                }
                 self.previous_nodes[heap_element.node_id as usize] = heap_element.previous_node;
                 self.distances[heap_element.node_id as usize] = heap_element.distance;
+                self.settled_nodes += 1;
                 let neighbors_and_distances = self.graph_ref.get_neighbors_of_node_and_distances(heap_element.node_id);
                 for i in (0..neighbors_and_distances.len()).step_by(2) {
                     let next_node = neighbors_and_distances[i];
@@ -168,33 +304,242 @@ impl Dijkstra {
                             node_id: next_node,
                             distance: next_node_distance + heap_element.distance,
                             previous_node: heap_element.node_id,
+                            time: 0,
                         });
                     }
                 }
-                if *destination_node == heap_element.node_id {
+                if destination_node == Some(heap_element.node_id) {
                     // found dest
                     break;
                 }
             } else {
-                println!("Heap is empty but dest node not found. src {}, dest {}", self.source_node, destination_node);
+                if let Some(destination_node) = destination_node {
+                    println!("Heap is empty but dest node not found. src {}, dest {}", self.source_node, destination_node);
+                }
                 return;
             }
         }
     }
 
+    // searches from all source nodes until the shortest route to one of the destination nodes is found.
+    // The source nodes are given with an additional distance and the seconds at the start of the route, the destination nodes
+    // with an additional distance at the end of the route, which the destination cost can change depending on the seconds.
+    // The edge cost is called with the seconds at the start node of the edge as last argument and returns the distance and the seconds of the edge.
+    // Returns the reached destination node and the distance including the additional distances
+    fn a_star<F, C, D>(&mut self, sources: &[(u32, u32, u32)], destinations: &[(u32, u32)], potential: F, edge_cost: C, destination_cost: D) -> Option<(u32, u32)>
+        where F: Fn(u32) -> u32, C: Fn(u32, u32, u32, u32) -> (u32, u32), D: Fn(u32, u32, u32) -> u32 {
+        // the heap items are ordered by the distance from the source plus the potential of the node
+        self.heap.clear();
+        for (source_node, source_distance, source_time) in sources {
+            self.heap.push(HeapItem {
+                node_id: *source_node,
+                distance: source_distance.saturating_add(potential(*source_node)),
+                previous_node: *source_node,
+                time: *source_time,
+            });
+        }
+        let mut best_destination: Option<(u32, u32)> = None;
+        while let Some(heap_element) = self.heap.pop() {
+            if let Some((_, best_distance)) = best_destination {
+                // no route through the remaining nodes can be shorter
+                if heap_element.distance >= best_distance {
+                    break;
+                }
+            }
+            if self.distances[heap_element.node_id as usize] != u32::MAX {
+                continue;
+            }
+            let distance = heap_element.distance.saturating_sub(potential(heap_element.node_id));
+            self.previous_nodes[heap_element.node_id as usize] = heap_element.previous_node;
+            self.distances[heap_element.node_id as usize] = distance;
+            self.settled_nodes += 1;
+            if let Some((_, destination_distance)) = destinations.iter().find(|(node, _)| *node == heap_element.node_id) {
+                let destination_distance = destination_cost(heap_element.node_id, *destination_distance, heap_element.time);
+                let total_distance = distance.saturating_add(destination_distance);
+                if best_destination.map_or(true, |(_, best_distance)| total_distance < best_distance) {
+                    best_destination = Some((heap_element.node_id, total_distance));
+                }
+                if destinations.len() == 1 {
+                    break;
+                }
+            }
+            let neighbors_and_distances = self.graph_ref.get_neighbors_of_node_and_distances(heap_element.node_id);
+            for i in (0..neighbors_and_distances.len()).step_by(2) {
+                let next_node = neighbors_and_distances[i];
+                let (next_node_cost, next_node_time) = edge_cost(heap_element.node_id, next_node, neighbors_and_distances[i + 1], heap_element.time);
+                if self.distances[next_node as usize] == u32::MAX && next_node_cost != u32::MAX {
+                    self.heap.push(HeapItem {
+                        node_id: next_node,
+                        distance: distance.saturating_add(next_node_cost).saturating_add(potential(next_node)),
+                        previous_node: heap_element.node_id,
+                        time: heap_element.time + next_node_time,
+                    });
+                }
+            }
+        }
+        best_destination
+    }
+
     fn traverse_route(&self, destination_node: &u32) -> Vec<u32> {
-        let mut previous_node = self.previous_nodes[*destination_node as usize];
-        let mut nodes = vec![*destination_node];
-        while previous_node != self.source_node {
-            nodes.push(previous_node);
-            previous_node = self.previous_nodes[previous_node as usize];
+        // the source nodes are their own previous node
+        let mut node = *destination_node;
+        let mut nodes = vec![node];
+        while self.previous_nodes[node as usize] != node {
+            node = self.previous_nodes[node as usize];
+            nodes.push(node);
         }
-        nodes.push(self.source_node);
         nodes.reverse();
         return nodes;
     }
 }
 
+/// Dijkstra which searches forward from the source node and backward from the destination node at the same time.
+/// The edges of the grid graph are symmetric, so the backward search uses the same adjacency array.
+pub(crate) struct BidirectionalDijkstra {
+    graph_ref: Arc<AdjacencyArray>,
+    heap_forward: BinaryHeap<HeapItem>,
+    heap_backward: BinaryHeap<HeapItem>,
+    distances_forward: Vec<u32>,
+    distances_backward: Vec<u32>,
+    // the previous node in the forward search, the next node towards the destination in the backward search
+    previous_nodes_forward: Vec<u32>,
+    previous_nodes_backward: Vec<u32>,
+    settled_nodes: u32,
+}
+
+impl BidirectionalDijkstra {
+    pub fn new(graph: Arc<AdjacencyArray>) -> BidirectionalDijkstra {
+        let number_of_nodes = graph.get_nodes_count() as usize;
+        BidirectionalDijkstra {
+            graph_ref: graph,
+            heap_forward: BinaryHeap::new(),
+            heap_backward: BinaryHeap::new(),
+            distances_forward: vec![u32::MAX; number_of_nodes],
+            distances_backward: vec![u32::MAX; number_of_nodes],
+            previous_nodes_forward: vec![u32::MAX; number_of_nodes],
+            previous_nodes_backward: vec![u32::MAX; number_of_nodes],
+            settled_nodes: 0,
+        }
+    }
+
+    /// Number of nodes settled by both searches of the last query
+    pub fn settled_nodes(&self) -> u32 {
+        self.settled_nodes
+    }
+
+    /// Route between two nodes, the queries of the navigator connect the stops to several nodes with find_route_between_nodes
+    #[allow(dead_code)]
+    pub fn find_route(&mut self, source_node: u32, destination_node: u32) -> Option<(Vec<u32>, u32)> {
+        self.find_route_between_nodes(&[(source_node, 0)], &[(destination_node, 0)])
+    }
+
+    /// Calculates the shortest route from one of the source nodes to one of the destination nodes. Like in Dijkstra::find_route_between_nodes,
+    /// every source and destination node has an additional distance, with which the forward or the backward search starts.
+    /// Returns the route between the nodes and its distance including the additional distances
+    pub fn find_route_between_nodes(&mut self, sources: &[(u32, u32)], destinations: &[(u32, u32)]) -> Option<(Vec<u32>, u32)> {
+        self.settled_nodes = 0;
+        self.heap_forward.clear();
+        self.heap_backward.clear();
+        self.distances_forward.fill(u32::MAX);
+        self.distances_backward.fill(u32::MAX);
+        self.previous_nodes_forward.fill(u32::MAX);
+        self.previous_nodes_backward.fill(u32::MAX);
+        // the source and destination nodes are their own previous node
+        for (source_node, source_distance) in sources {
+            self.heap_forward.push(HeapItem { node_id: *source_node, distance: *source_distance, previous_node: *source_node, time: 0 });
+        }
+        for (destination_node, destination_distance) in destinations {
+            self.heap_backward.push(HeapItem { node_id: *destination_node, distance: *destination_distance, previous_node: *destination_node, time: 0 });
+        }
+
+        // length of the shortest route found so far and the edge where both searches met
+        let mut best_distance = u32::MAX;
+        let mut meeting_edge: Option<(u32, u32)> = None;
+        loop {
+            let top_forward = BidirectionalDijkstra::min_unsettled_distance(&mut self.heap_forward, &self.distances_forward);
+            let top_backward = BidirectionalDijkstra::min_unsettled_distance(&mut self.heap_backward, &self.distances_backward);
+            // stop as soon as no shorter route through an unsettled node can exist
+            if top_forward.saturating_add(top_backward) >= best_distance || (top_forward == u32::MAX && top_backward == u32::MAX) {
+                break;
+            }
+            let forward = top_forward <= top_backward;
+            let (heap, distances, previous_nodes, other_distances) = if forward {
+                (&mut self.heap_forward, &mut self.distances_forward, &mut self.previous_nodes_forward, &self.distances_backward)
+            } else {
+                (&mut self.heap_backward, &mut self.distances_backward, &mut self.previous_nodes_backward, &self.distances_forward)
+            };
+            let heap_element = heap.pop().unwrap();
+            distances[heap_element.node_id as usize] = heap_element.distance;
+            previous_nodes[heap_element.node_id as usize] = heap_element.previous_node;
+            self.settled_nodes += 1;
+            // both searches settle the node, e.g. if it is a source and a destination node
+            if other_distances[heap_element.node_id as usize] != u32::MAX && heap_element.distance + other_distances[heap_element.node_id as usize] < best_distance {
+                best_distance = heap_element.distance + other_distances[heap_element.node_id as usize];
+                meeting_edge = Some((heap_element.node_id, heap_element.node_id));
+            }
+            let neighbors_and_distances = self.graph_ref.get_neighbors_of_node_and_distances(heap_element.node_id);
+            for i in (0..neighbors_and_distances.len()).step_by(2) {
+                let next_node = neighbors_and_distances[i];
+                let next_node_distance = neighbors_and_distances[i + 1];
+                let distance = heap_element.distance + next_node_distance;
+                if other_distances[next_node as usize] != u32::MAX && distance + other_distances[next_node as usize] < best_distance {
+                    // the other search already settled the next node, so the edge connects both searches
+                    best_distance = distance + other_distances[next_node as usize];
+                    meeting_edge = if forward { Some((heap_element.node_id, next_node)) } else { Some((next_node, heap_element.node_id)) };
+                }
+                if distances[next_node as usize] == u32::MAX {
+                    heap.push(HeapItem { node_id: next_node, distance, previous_node: heap_element.node_id, time: 0 });
+                }
+            }
+        }
+        meeting_edge.map(|(forward_node, backward_node)| (self.traverse_route(forward_node, backward_node), best_distance))
+    }
+
+    /// Removes already settled nodes from the top of the heap and returns the distance of the next unsettled node
+    fn min_unsettled_distance(heap: &mut BinaryHeap<HeapItem>, distances: &Vec<u32>) -> u32 {
+        while let Some(heap_element) = heap.peek() {
+            if distances[heap_element.node_id as usize] == u32::MAX {
+                return heap_element.distance;
+            }
+            heap.pop();
+        }
+        u32::MAX
+    }
+
+    // the forward node and the backward node are the same node, if both searches settled the node where they met
+    fn traverse_route(&self, forward_node: u32, backward_node: u32) -> Vec<u32> {
+        let mut nodes = vec![forward_node];
+        let mut node = forward_node;
+        while self.previous_nodes_forward[node as usize] != node {
+            node = self.previous_nodes_forward[node as usize];
+            nodes.push(node);
+        }
+        nodes.reverse();
+        node = backward_node;
+        if backward_node != forward_node {
+            nodes.push(node);
+        }
+        while self.previous_nodes_backward[node as usize] != node {
+            node = self.previous_nodes_backward[node as usize];
+            nodes.push(node);
+        }
+        nodes
+    }
+}
+
+/// Returns the great-circle distance from a node to the destination node as A* potential
+pub(crate) fn great_circle_potential(nodes: &Vec<Node>, destination_node: u32) -> impl Fn(u32) -> u32 + '_ {
+    great_circle_potential_to_point(nodes, nodes[destination_node as usize])
+}
+
+/// Returns the great-circle distance from a node to a point, which does not need to be a node of the graph, as A* potential
+pub(crate) fn great_circle_potential_to_point(nodes: &Vec<Node>, destination: Node) -> impl Fn(u32) -> u32 + '_ {
+    move |node: u32| {
+        let node = &nodes[node as usize];
+        (distance(node.lon, node.lat, destination.lon, destination.lat) * GREAT_CIRCLE_POTENTIAL_FACTOR) as u32
+    }
+}
+
 #[allow(dead_code)]
 pub(crate) fn main() {
 
@@ -202,7 +547,7 @@ pub(crate) fn main() {
     let source_node = 0;
     let target_node = 0;
     let node_count = graph.get_nodes_count();
-    let adjacency_array = AdjacencyArray::new(graph.offsets, graph.edges);
+    let adjacency_array = Arc::new(AdjacencyArray::new(graph.offsets, graph.edges));
     let mut dijkstra = Dijkstra::new(adjacency_array, source_node);
     println!("Route from {} to {} is {:?}", source_node, target_node, dijkstra.find_route(target_node).unwrap());
     for i in 0..node_count {
@@ -214,4 +559,154 @@ pub(crate) fn main() {
         }
         println!("Routes from {}: {:?}", i, distances);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::contraction_hierarchies::{ContractedGraph, ContractionHierarchies};
+    use crate::grid_graph::{distance, Node};
+    use crate::landmarks::{LandmarkSelection, Landmarks};
+    use super::{great_circle_potential, AdjacencyArray, BidirectionalDijkstra, Dijkstra};
+
+    /// Lon/lat grid with a spacing of 0.1 degrees and eight neighbors per node, which has a wall of land in the middle,
+    /// and a separate pair of nodes, which can not be reached from the grid
+    fn test_graph() -> (Vec<Node>, Arc<AdjacencyArray>) {
+        test_graph_with_spacing(0.1)
+    }
+
+    /// Like test_graph, but the nodes of the grid are the given number of degrees apart
+    fn test_graph_with_spacing(spacing: f64) -> (Vec<Node>, Arc<AdjacencyArray>) {
+        let size = 7;
+        let is_land = |x: i32, y: i32| x == 3 && (1..=5).contains(&y);
+        let mut nodes = Vec::new();
+        let mut node_ids = vec![None; size * size];
+        for y in 0..size as i32 {
+            for x in 0..size as i32 {
+                if !is_land(x, y) {
+                    node_ids[y as usize * size + x as usize] = Some(nodes.len() as u32);
+                    nodes.push(Node { lon: 10.0 + x as f64 * spacing, lat: 50.0 + y as f64 * spacing });
+                }
+            }
+        }
+        let mut neighbors: Vec<Vec<u32>> = vec![Vec::new(); nodes.len()];
+        for y in 0..size as i32 {
+            for x in 0..size as i32 {
+                for (dx, dy) in &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                    let (neighbor_x, neighbor_y) = (x + dx, y + dy);
+                    if neighbor_x < 0 || neighbor_y < 0 || neighbor_x >= size as i32 || neighbor_y >= size as i32 {
+                        continue;
+                    }
+                    if let (Some(node), Some(neighbor)) = (node_ids[y as usize * size + x as usize], node_ids[neighbor_y as usize * size + neighbor_x as usize]) {
+                        neighbors[node as usize].push(neighbor);
+                    }
+                }
+            }
+        }
+        let separate_node = nodes.len() as u32;
+        nodes.push(Node { lon: 20.0, lat: 50.0 });
+        nodes.push(Node { lon: 20.0 + spacing, lat: 50.0 });
+        neighbors.push(vec![separate_node + 1]);
+        neighbors.push(vec![separate_node]);
+
+        // the distances are rounded up like the edges of the grid graph
+        let mut offsets = vec![0];
+        let mut edges_and_distances = Vec::new();
+        for (node, node_neighbors) in neighbors.iter().enumerate() {
+            for neighbor in node_neighbors {
+                let (from, to) = (&nodes[node], &nodes[*neighbor as usize]);
+                edges_and_distances.push(*neighbor);
+                edges_and_distances.push(distance(from.lon, from.lat, to.lon, to.lat).ceil() as u32);
+            }
+            offsets.push(edges_and_distances.len() as u32);
+        }
+        (nodes, Arc::new(AdjacencyArray::new(offsets, edges_and_distances)))
+    }
+
+    /// Checks that the route leads along edges of the graph from the source to the destination and has the given distance
+    fn assert_valid_route(graph: &AdjacencyArray, route: &[u32], distance: u32, source_node: u32, destination_node: u32) {
+        assert_eq!(route.first(), Some(&source_node));
+        assert_eq!(route.last(), Some(&destination_node));
+        let route_distance: u32 = route.windows(2)
+            .map(|edge| graph.get_edge_distance(edge[0], edge[1]).unwrap_or_else(|| panic!("no edge between {} and {} of the route {:?}", edge[0], edge[1], route)))
+            .sum();
+        assert_eq!(route_distance, distance, "distance of the route {:?}", route);
+    }
+
+    /// Compares the routes between all pairs of nodes with the routes of Dijkstra
+    fn assert_same_routes_as_dijkstra<F: FnMut(u32, u32) -> Option<(Vec<u32>, u32)>>(graph: &Arc<AdjacencyArray>, mut find_route: F) {
+        let number_of_nodes = graph.get_nodes_count();
+        let mut dijkstra = Dijkstra::new(Arc::clone(graph), 0);
+        for source_node in 0..number_of_nodes {
+            dijkstra.change_source_node(source_node);
+            for destination_node in 0..number_of_nodes {
+                let expected = dijkstra.find_route(destination_node).map(|(_, distance)| distance);
+                let route = find_route(source_node, destination_node);
+                assert_eq!(route.as_ref().map(|(_, distance)| *distance), expected, "distance from {} to {}", source_node, destination_node);
+                if let Some((route, distance)) = route {
+                    assert_valid_route(graph, &route, distance, source_node, destination_node);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dijkstra_routes_are_valid() {
+        let (_, graph) = test_graph();
+        let mut dijkstra = Dijkstra::new(Arc::clone(&graph), 0);
+        for source_node in 0..graph.get_nodes_count() {
+            dijkstra.change_source_node(source_node);
+            for destination_node in 0..graph.get_nodes_count() {
+                if let Some((route, distance)) = dijkstra.find_route(destination_node) {
+                    assert_valid_route(&graph, &route, distance, source_node, destination_node);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_star_finds_the_routes_of_dijkstra() {
+        let (nodes, graph) = test_graph();
+        let mut a_star = Dijkstra::new(Arc::clone(&graph), 0);
+        assert_same_routes_as_dijkstra(&graph, |source_node, destination_node| {
+            a_star.find_route_a_star(source_node, destination_node, great_circle_potential(&nodes, destination_node))
+        });
+    }
+
+    #[test]
+    fn a_star_finds_the_routes_of_dijkstra_along_short_edges() {
+        // the edges are only a few meters long, so the rounding of every edge matters
+        let (nodes, graph) = test_graph_with_spacing(0.00002);
+        let mut a_star = Dijkstra::new(Arc::clone(&graph), 0);
+        assert_same_routes_as_dijkstra(&graph, |source_node, destination_node| {
+            a_star.find_route_a_star(source_node, destination_node, great_circle_potential(&nodes, destination_node))
+        });
+    }
+
+    #[test]
+    fn bidirectional_dijkstra_finds_the_routes_of_dijkstra() {
+        let (_, graph) = test_graph();
+        let mut bidirectional_dijkstra = BidirectionalDijkstra::new(Arc::clone(&graph));
+        assert_same_routes_as_dijkstra(&graph, |source_node, destination_node| bidirectional_dijkstra.find_route(source_node, destination_node));
+    }
+
+    #[test]
+    fn contraction_hierarchies_find_the_routes_of_dijkstra() {
+        let (_, graph) = test_graph();
+        let mut contraction_hierarchies = ContractionHierarchies::new(Arc::new(ContractedGraph::new(&graph)));
+        // the routes are unpacked, so valid routes only use edges of the graph and no shortcuts
+        assert_same_routes_as_dijkstra(&graph, |source_node, destination_node| contraction_hierarchies.find_route(source_node, destination_node));
+    }
+
+    #[test]
+    fn alt_finds_the_routes_of_dijkstra() {
+        let (nodes, graph) = test_graph();
+        for selection in &[LandmarkSelection::Farthest, LandmarkSelection::Avoid] {
+            let landmarks = Landmarks::new(Arc::clone(&graph), 4, *selection);
+            let mut alt = Dijkstra::new(Arc::clone(&graph), 0);
+            assert_same_routes_as_dijkstra(&graph, |source_node, destination_node| {
+                let destinations = vec![(destination_node, 0)];
+                alt.find_route_between_nodes(&vec![(source_node, 0)], &destinations, landmarks.potential(&destinations, great_circle_potential(&nodes, destination_node)))
+            });
+        }
+    }
+}
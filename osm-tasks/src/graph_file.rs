@@ -0,0 +1,302 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use crate::config::Config;
+use crate::dijkstra::AdjacencyArray;
+use crate::grid_graph;
+use crate::region::Region;
+use crate::topology::GraphTopology;
+
+/// First bytes of every graph file
+const MAGIC: &[u8; 8] = b"OSMGRAPH";
+/// Version of the file format, which has to be increased whenever the header, one of the saved structures or the rounding of their values changes
+const FORMAT_VERSION: u32 = 3;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Size and FNV-1a hash of the coastlines file, which are calculated once for all files built from it
+#[derive(Clone, Copy, Debug)]
+pub struct SourceFingerprint {
+    size: u64,
+    hash: u64,
+}
+
+impl SourceFingerprint {
+    /// Fingerprint of the coastlines file. Without the coastlines file, the size and the hash are 0 and the source is not checked
+    pub fn new(osm_path: &Path) -> SourceFingerprint {
+        let (size, hash) = source_fingerprint(osm_path).unwrap_or((0, 0));
+        SourceFingerprint { size, hash }
+    }
+}
+
+/**
+Header of the files of the polygons, the graph, the contracted graph and the landmarks.
+A file starts with the magic bytes and the format version, followed by this header and the bincode of the saved structure.
+The header describes the coastlines file and the parameters, which the graph was built with, and holds a checksum of the saved structure.
+The contracted graph and the landmarks also hold the checksum of the adjacency array including the passages, which they were built for.
+A file is only loaded, if the magic bytes, the version, the coastlines file, the parameters and the adjacency array match and the checksum is correct.
+Otherwise the graph is built again, so changed coastlines or files of older versions are never loaded.
+**/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GraphFileHeader {
+    source_size: u64,
+    // FNV-1a hash of the coastlines file
+    source_hash: u64,
+    number_of_nodes: u32,
+    topology: String,
+    min_component_size: u32,
+    region: Option<String>,
+    // checksum of the adjacency array, which the structure was built for, or 0 if it does not depend on an adjacency array
+    parent_checksum: u64,
+    // seconds since the unix epoch
+    build_timestamp: u64,
+    // FNV-1a hash of the saved structure
+    checksum: u64,
+}
+
+impl GraphFileHeader {
+    /// Header with the current coastlines file and configuration. The timestamp and the checksum are set when the file is saved
+    pub fn new(source: SourceFingerprint, region: Option<&Region>) -> GraphFileHeader {
+        let config = Config::global();
+        let topology = config.topology();
+        let mut topology_name = topology.to_string();
+        if topology == GraphTopology::Adaptive {
+            topology_name.push_str(&config.refinement_levels().to_string());
+        }
+        GraphFileHeader {
+            source_size: source.size,
+            source_hash: source.hash,
+            number_of_nodes: grid_graph::get_maximum_number_of_nodes() as u32,
+            topology: topology_name,
+            min_component_size: config.min_component_size(),
+            region: region.map(|region| region.name().to_owned()),
+            parent_checksum: 0,
+            build_timestamp: 0,
+            checksum: 0,
+        }
+    }
+
+    /// Header of the polygons, which only depend on the coastlines file
+    pub fn polygons(source: SourceFingerprint) -> GraphFileHeader {
+        GraphFileHeader {
+            source_size: source.size,
+            source_hash: source.hash,
+            number_of_nodes: 0,
+            topology: String::from("polygons"),
+            min_component_size: 0,
+            region: None,
+            parent_checksum: 0,
+            build_timestamp: 0,
+            checksum: 0,
+        }
+    }
+
+    /// Header of a structure, which is built for the adjacency array, like the contracted graph or the landmarks
+    pub fn derived_from(mut self, adjacency_array: &AdjacencyArray) -> GraphFileHeader {
+        let hash = fnv_hash(FNV_OFFSET, &u32_bytes(adjacency_array.edges_and_distances_offsets()));
+        self.parent_checksum = fnv_hash(hash, &u32_bytes(adjacency_array.edges_and_distances()));
+        self
+    }
+
+    pub fn build_timestamp(&self) -> u64 {
+        self.build_timestamp
+    }
+
+    /// Describes the first difference of the build parameters of the saved header to the expected header
+    fn mismatch(&self, expected: &GraphFileHeader) -> Option<String> {
+        if expected.source_size != 0 && (self.source_size != expected.source_size || self.source_hash != expected.source_hash) {
+            return Some(format!("the coastlines file changed from {} bytes with hash {:016x} to {} bytes with hash {:016x}", self.source_size, self.source_hash, expected.source_size, expected.source_hash));
+        }
+        if self.number_of_nodes != expected.number_of_nodes {
+            return Some(format!("it was built with {} instead of {} nodes", self.number_of_nodes, expected.number_of_nodes));
+        }
+        if self.topology != expected.topology {
+            return Some(format!("it was built with the topology {} instead of {}", self.topology, expected.topology));
+        }
+        if self.min_component_size != expected.min_component_size {
+            return Some(format!("it was built with the minimum component size {} instead of {}", self.min_component_size, expected.min_component_size));
+        }
+        if self.region != expected.region {
+            return Some(format!("it was built for the region {} instead of {}", self.region.as_deref().unwrap_or("world"), expected.region.as_deref().unwrap_or("world")));
+        }
+        if self.parent_checksum != expected.parent_checksum {
+            return Some(format!("it was built for another graph with the checksum {:016x} instead of {:016x}", self.parent_checksum, expected.parent_checksum));
+        }
+        None
+    }
+}
+
+/// Saves the structure with the header, whose timestamp and checksum are set
+pub(crate) fn save_graph_file<T: Serialize>(path: &Path, mut header: GraphFileHeader, data: &T) -> Result<(), String> {
+    let payload = bincode::serialize(data).map_err(|error| format!("Could not serialize the graph: {:?}", error))?;
+    header.build_timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+    header.checksum = fnv_hash(FNV_OFFSET, &payload);
+    let mut f = BufWriter::new(File::create(path).map_err(|error| format!("Could not create {}: {}", path.display(), error))?);
+    f.write_all(MAGIC)
+        .and_then(|_| f.write_all(&FORMAT_VERSION.to_le_bytes()))
+        .map_err(|error| error.to_string())?;
+    bincode::serialize_into(&mut f, &header).map_err(|error| format!("{:?}", error))?;
+    f.write_all(&payload).and_then(|_| f.flush()).map_err(|error| format!("Could not write {}: {}", path.display(), error))
+}
+
+/// Loads the structure, if the header of the file matches the expected header and the checksum is correct. Otherwise the reason is returned
+pub(crate) fn load_graph_file<T: DeserializeOwned>(path: &Path, expected: &GraphFileHeader) -> Result<(GraphFileHeader, T), String> {
+    let mut f = BufReader::new(File::open(path).map_err(|error| format!("it could not be opened: {}", error))?);
+    let mut magic = [0; 8];
+    let mut version = [0; 4];
+    if f.read_exact(&mut magic).is_err() || &magic != MAGIC {
+        return Err(String::from("it is no graph file or was saved before the file format was versioned"));
+    }
+    f.read_exact(&mut version).map_err(|_| String::from("it is truncated"))?;
+    let version = u32::from_le_bytes(version);
+    if version != FORMAT_VERSION {
+        return Err(format!("it has the format version {} instead of {}", version, FORMAT_VERSION));
+    }
+    let header: GraphFileHeader = bincode::deserialize_from(&mut f).map_err(|error| format!("its header is invalid: {:?}", error))?;
+    if let Some(mismatch) = header.mismatch(expected) {
+        return Err(mismatch);
+    }
+    let mut payload = Vec::new();
+    f.read_to_end(&mut payload).map_err(|error| format!("it could not be read: {}", error))?;
+    if fnv_hash(FNV_OFFSET, &payload) != header.checksum {
+        return Err(String::from("its checksum does not match, the file is truncated or corrupted"));
+    }
+    let data = bincode::deserialize(&payload).map_err(|error| format!("it could not be deserialized: {:?}", error))?;
+    Ok((header, data))
+}
+
+/// Size and hash of the coastlines file
+fn source_fingerprint(path: &Path) -> Option<(u64, u64)> {
+    let mut f = BufReader::new(File::open(path).ok()?);
+    let mut buffer = vec![0; 1 << 20];
+    let mut size = 0;
+    let mut hash = FNV_OFFSET;
+    loop {
+        let length = f.read(&mut buffer).ok()?;
+        if length == 0 {
+            return Some((size, hash));
+        }
+        size += length as u64;
+        hash = fnv_hash(hash, &buffer[..length]);
+    }
+}
+
+fn u32_bytes(values: &Vec<u32>) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+/// Continues the FNV-1a hash with the bytes
+fn fnv_hash(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use crate::dijkstra::AdjacencyArray;
+    use super::{fnv_hash, load_graph_file, save_graph_file, GraphFileHeader, SourceFingerprint, FNV_OFFSET, FORMAT_VERSION, MAGIC};
+
+    /// Path of a file in the temporary directory, which is unique for the test and the process
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("osm-tasks-{}-{}.bin", name, std::process::id()))
+    }
+
+    fn polygons() -> Vec<Vec<(f64, f64)>> {
+        vec![vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)], vec![(-180.0, -90.0), (180.0, 90.0)]]
+    }
+
+    fn source(size: u64, hash: u64) -> SourceFingerprint {
+        SourceFingerprint { size, hash }
+    }
+
+    fn load(path: &Path, expected: &GraphFileHeader) -> Result<Vec<Vec<(f64, f64)>>, String> {
+        load_graph_file(path, expected).map(|(_, polygons)| polygons)
+    }
+
+    #[test]
+    fn saved_structures_are_loaded_again() {
+        let path = temporary_path("round-trip");
+        let header = GraphFileHeader::polygons(source(42, 7));
+        save_graph_file(&path, header.clone(), &polygons()).unwrap_or_else(|error| panic!("{}", error));
+        let loaded = load_graph_file::<Vec<Vec<(f64, f64)>>>(&path, &header);
+        fs::remove_file(&path).unwrap();
+        let (loaded_header, loaded_polygons) = loaded.unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(loaded_polygons, polygons());
+        assert!(loaded_header.build_timestamp() > 0);
+        assert_eq!(loaded_header.checksum, fnv_hash(FNV_OFFSET, &bincode::serialize(&polygons()).unwrap()));
+    }
+
+    #[test]
+    fn corrupted_files_are_rejected() {
+        let path = temporary_path("corrupted");
+        let header = GraphFileHeader::polygons(source(42, 7));
+        save_graph_file(&path, header.clone(), &polygons()).unwrap_or_else(|error| panic!("{}", error));
+        let bytes = fs::read(&path).unwrap();
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        fs::write(&path, &corrupted).unwrap();
+        let corrupted_result = load(&path, &header);
+        fs::write(&path, &bytes[..bytes.len() - 8]).unwrap();
+        let truncated_result = load(&path, &header);
+        let mut old_version = bytes.clone();
+        old_version[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(FORMAT_VERSION - 1).to_le_bytes());
+        fs::write(&path, &old_version).unwrap();
+        let old_version_result = load(&path, &header);
+        fs::write(&path, &bytes[MAGIC.len()..]).unwrap();
+        let unversioned_result = load(&path, &header);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(corrupted_result, Err(error) if error.contains("checksum does not match")));
+        assert!(matches!(truncated_result, Err(error) if error.contains("checksum does not match")));
+        assert!(matches!(old_version_result, Err(error) if error.contains(&format!("format version {} instead of {}", FORMAT_VERSION - 1, FORMAT_VERSION))));
+        assert!(matches!(unversioned_result, Err(error) if error.contains("no graph file")));
+        assert!(load(&temporary_path("missing"), &header).is_err());
+    }
+
+    #[test]
+    fn files_of_other_sources_and_parameters_are_rejected() {
+        let path = temporary_path("mismatch");
+        let header = GraphFileHeader::polygons(source(42, 7));
+        save_graph_file(&path, header.clone(), &polygons()).unwrap_or_else(|error| panic!("{}", error));
+        let changed_source = load(&path, &GraphFileHeader::polygons(source(42, 8)));
+        // without the coastlines file the source is not checked
+        let unknown_source = load(&path, &GraphFileHeader::polygons(source(0, 0)));
+        let mut other_nodes = header.clone();
+        other_nodes.number_of_nodes = 1_000;
+        let other_nodes_result = load(&path, &other_nodes);
+        let mut other_region = header.clone();
+        other_region.region = Some(String::from("region_0_0_1_1"));
+        let other_region_result = load(&path, &other_region);
+        let graph = AdjacencyArray::new(vec![0, 2, 4], vec![1, 10, 0, 10]);
+        let derived_result = load(&path, &header.clone().derived_from(&graph));
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(changed_source, Err(error) if error.contains("coastlines file changed")));
+        assert_eq!(unknown_source, Ok(polygons()));
+        assert!(matches!(other_nodes_result, Err(error) if error.contains("1000 nodes")));
+        assert!(matches!(other_region_result, Err(error) if error.contains("instead of region_0_0_1_1")));
+        assert!(matches!(derived_result, Err(error) if error.contains("another graph")));
+
+        // the checksum of the adjacency array changes with its distances
+        let other_graph = AdjacencyArray::new(vec![0, 2, 4], vec![1, 11, 0, 11]);
+        assert_ne!(header.clone().derived_from(&graph).parent_checksum, header.derived_from(&other_graph).parent_checksum);
+    }
+
+    #[test]
+    fn fingerprint_of_the_coastlines_file() {
+        let path = temporary_path("coastlines");
+        fs::write(&path, b"a").unwrap();
+        let fingerprint = SourceFingerprint::new(&path);
+        fs::remove_file(&path).unwrap();
+        // test vector of FNV-1a
+        assert_eq!((fingerprint.size, fingerprint.hash), (1, 0xaf63_dc4c_8601_ec8c));
+        let missing = SourceFingerprint::new(&temporary_path("missing-coastlines"));
+        assert_eq!((missing.size, missing.hash), (0, 0));
+    }
+}
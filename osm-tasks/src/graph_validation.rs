@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use rayon::prelude::*;
+use crate::grid_graph::{distance, GridGraph, Node};
+use crate::json_generator::JsonBuilder;
+use crate::nearest_neighbor::NearestNeighbor;
+use crate::segment_index::SegmentIndex;
+use crate::topology::GraphTopology;
+
+/// Number of nearest nodes of every node, which are checked for a missing edge
+const HOLE_CANDIDATES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GraphProblemKind {
+    /// The offsets do not match the edges or the arrays of the graph have different lengths
+    InconsistentOffsets,
+    /// The edge does not start at its node or ends at a node, which does not exist
+    InvalidEdge,
+    SelfLoop,
+    DuplicateEdge,
+    /// The reverse edge is missing or has another distance
+    AsymmetricEdge,
+    /// The node has no edges
+    IsolatedNode,
+    /// The node has more edges than the topology creates
+    UnusualDegree,
+    /// Two nodes are not connected, although they are closer than their neighbors and the water between them is free
+    Hole,
+}
+
+impl fmt::Display for GraphProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Problem of the graph at a node or an edge
+pub struct GraphProblem {
+    pub kind: GraphProblemKind,
+    pub description: String,
+    // the node or both nodes of the edge
+    pub location: Vec<Node>,
+}
+
+/**
+Checks the integrity of a generated graph.
+The offsets have to match the edges, every edge needs a reverse edge with the same distance, and there must be no self-loops or duplicate edges.
+The degrees of the nodes are compared with the maximum degree of the topology.
+A hole is a pair of nodes, which are not connected, although they are closer to each other than to any of their neighbors and no land is between them.
+**/
+pub struct GraphValidation {
+    problems: Vec<GraphProblem>,
+    // number of nodes with every degree
+    degrees: BTreeMap<usize, usize>,
+}
+
+impl GraphValidation {
+    pub fn new(graph: &GridGraph, polygons: &Vec<Vec<(f64, f64)>>, topology: GraphTopology) -> GraphValidation {
+        let mut problems = Vec::new();
+        let mut degrees = BTreeMap::new();
+        let nodes = &graph.nodes;
+        let number_nodes = nodes.len();
+        let consistency_problems = GraphValidation::check_offsets(graph);
+        if !consistency_problems.is_empty() {
+            // the edges of the nodes cannot be found without valid offsets
+            problems.extend(consistency_problems);
+            return GraphValidation { problems, degrees };
+        }
+        let node_edges = |node: usize| &graph.edges[graph.offsets[node] as usize..graph.offsets[node + 1] as usize];
+        let maximum_degree = maximum_degree(topology);
+        for node in 0..number_nodes {
+            let edges = node_edges(node);
+            *degrees.entry(edges.len()).or_insert(0) += 1;
+            if edges.is_empty() {
+                problems.push(GraphProblem { kind: GraphProblemKind::IsolatedNode, description: format!("Node {} has no edges", node), location: vec![nodes[node]] });
+            }
+            if maximum_degree.map_or(false, |maximum_degree| edges.len() > maximum_degree) {
+                problems.push(GraphProblem { kind: GraphProblemKind::UnusualDegree, description: format!("Node {} has {} edges, but the {} topology creates at most {}", node, edges.len(), topology, maximum_degree.unwrap()), location: vec![nodes[node]] });
+            }
+            for (i, edge) in edges.iter().enumerate() {
+                if edge.source as usize != node || edge.target as usize >= number_nodes {
+                    problems.push(GraphProblem { kind: GraphProblemKind::InvalidEdge, description: format!("Edge {} -> {} is stored at node {}", edge.source, edge.target, node), location: vec![nodes[node]] });
+                    continue;
+                }
+                let location = vec![nodes[node], nodes[edge.target as usize]];
+                if edge.target as usize == node {
+                    problems.push(GraphProblem { kind: GraphProblemKind::SelfLoop, description: format!("Node {} has an edge to itself", node), location });
+                    continue;
+                }
+                if edges[..i].iter().any(|other_edge| other_edge.target == edge.target) {
+                    problems.push(GraphProblem { kind: GraphProblemKind::DuplicateEdge, description: format!("Edge {} -> {} is stored more than once", node, edge.target), location });
+                    continue;
+                }
+                match node_edges(edge.target as usize).iter().find(|reverse_edge| reverse_edge.target as usize == node) {
+                    Some(reverse_edge) if reverse_edge.distance == edge.distance => {}
+                    Some(reverse_edge) => problems.push(GraphProblem { kind: GraphProblemKind::AsymmetricEdge, description: format!("Edge {} -> {} has the distance {}, but the reverse edge {}", node, edge.target, edge.distance, reverse_edge.distance), location }),
+                    None => problems.push(GraphProblem { kind: GraphProblemKind::AsymmetricEdge, description: format!("Edge {} -> {} has no reverse edge", node, edge.target), location })
+                }
+            }
+        }
+        problems.extend(GraphValidation::find_holes(graph, polygons));
+        GraphValidation { problems, degrees }
+    }
+
+    fn check_offsets(graph: &GridGraph) -> Vec<GraphProblem> {
+        let number_nodes = graph.nodes.len();
+        let mut problems = Vec::new();
+        let mut problem = |description: String| problems.push(GraphProblem { kind: GraphProblemKind::InconsistentOffsets, description, location: Vec::new() });
+        if graph.number_nodes != number_nodes as i64 {
+            problem(format!("The graph has {} nodes, but the number of nodes is {}", number_nodes, graph.number_nodes));
+        }
+        if graph.offsets.len() != number_nodes + 1 {
+            problem(format!("There are {} offsets for {} nodes", graph.offsets.len(), number_nodes));
+        } else if graph.offsets[0] != 0 || graph.offsets[number_nodes] as usize != graph.edges.len() {
+            problem(format!("The offsets range from {} to {}, but there are {} edges", graph.offsets[0], graph.offsets[number_nodes], graph.edges.len()));
+        }
+        if let Some(node) = graph.offsets.windows(2).position(|offsets| offsets[0] > offsets[1]) {
+            problem(format!("The offset of node {} is larger than the offset of the next node", node));
+        }
+        if graph.coast_distances.len() != number_nodes || graph.components.len() != number_nodes {
+            problem(format!("There are {} coast distances and {} components for {} nodes", graph.coast_distances.len(), graph.components.len(), number_nodes));
+        }
+        if graph.edge_coast_distances.len() != graph.edges.len() {
+            problem(format!("There are {} coast distances for {} edges", graph.edge_coast_distances.len(), graph.edges.len()));
+        }
+        problems
+    }
+
+    /// Finds pairs of nodes, which are closer than the shortest edges of both nodes, but not connected although no land is between them
+    fn find_holes(graph: &GridGraph, polygons: &Vec<Vec<(f64, f64)>>) -> Vec<GraphProblem> {
+        let nodes = &graph.nodes;
+        let node_edges = |node: usize| &graph.edges[graph.offsets[node] as usize..graph.offsets[node + 1] as usize];
+        let shortest_edges: Vec<u32> = (0..nodes.len()).map(|node| node_edges(node).iter().map(|edge| edge.distance).min().unwrap_or(0)).collect();
+        let nearest_neighbor = NearestNeighbor::new(nodes);
+        let segment_index = SegmentIndex::new(polygons);
+        (0..nodes.len()).into_par_iter().flat_map(|node| {
+            let mut holes = Vec::new();
+            // isolated nodes are reported on their own
+            if shortest_edges[node] == 0 {
+                return holes;
+            }
+            for neighbor in nearest_neighbor.find_nearest_neighbors(&nodes[node], HOLE_CANDIDATES) {
+                let neighbor_index = neighbor as usize;
+                // every pair is checked from the node with the smaller index
+                if neighbor_index <= node || shortest_edges[neighbor_index] == 0 || node_edges(node).iter().any(|edge| edge.target == neighbor) {
+                    continue;
+                }
+                let (from, to) = (nodes[node], nodes[neighbor_index]);
+                let neighbor_distance = distance(from.lon, from.lat, to.lon, to.lat);
+                if neighbor_distance < shortest_edges[node].min(shortest_edges[neighbor_index]) as f64 && !segment_index.crosses_polygons(&from, &to) {
+                    holes.push(GraphProblem {
+                        kind: GraphProblemKind::Hole,
+                        description: format!("Nodes {} and {} are {} m apart, but not connected", node, neighbor, neighbor_distance.round()),
+                        location: vec![from, to],
+                    });
+                }
+            }
+            holes
+        }).collect()
+    }
+
+    /// Prints the degree distribution and the number of problems of every kind with some examples
+    pub fn print_report(&self) {
+        let number_nodes: usize = self.degrees.values().sum();
+        println!("Degree distribution of {} nodes:", number_nodes);
+        for (degree, count) in &self.degrees {
+            println!("  degree {:>3}: {:>10} nodes ({:.2} %)", degree, count, *count as f64 * 100.0 / number_nodes.max(1) as f64);
+        }
+        if self.problems.is_empty() {
+            println!("The graph is valid");
+            return;
+        }
+        let mut problems_by_kind: BTreeMap<GraphProblemKind, Vec<&GraphProblem>> = BTreeMap::new();
+        for problem in &self.problems {
+            problems_by_kind.entry(problem.kind).or_insert_with(Vec::new).push(problem);
+        }
+        println!("Found {} problems:", self.problems.len());
+        for (kind, problems) in problems_by_kind {
+            println!("  {}: {}", kind, problems.len());
+            for problem in problems.iter().take(5) {
+                println!("    {}", problem.description);
+            }
+        }
+    }
+
+    /// Writes the locations of the problems as GeoJSON points and lines, which are named by the kind and the description of the problem
+    pub fn export_geojson(&self, path: &str) {
+        let mut json_builder = JsonBuilder::new(path.to_owned());
+        for problem in self.problems.iter().filter(|problem| !problem.location.is_empty()) {
+            json_builder.add_named_geometry(problem.location.iter().map(|node| (node.lon, node.lat)).collect(), format!("{}: {}", problem.kind, problem.description));
+        }
+        json_builder.build();
+        println!("Saved the locations of the problems to {}", path);
+    }
+}
+
+/// Maximum number of edges of a node, which the topology creates. The adaptive topology has no limit, since it connects cells of different sizes
+fn maximum_degree(topology: GraphTopology) -> Option<usize> {
+    match topology {
+        GraphTopology::Deserno => Some(12),
+        GraphTopology::LatLon4 => Some(4),
+        GraphTopology::LatLon8 => Some(8),
+        GraphTopology::Hexagonal => Some(6),
+        GraphTopology::Adaptive => None,
+    }
+}
@@ -6,9 +6,14 @@ use std::f64::consts::PI;
 use std::f64;
 use serde::{Deserialize, Serialize};
 use crate::polygon_test::PointInPolygonTest;
+use crate::segment_index::SegmentIndex;
 use rayon::prelude::*;
 use crate::dijkstra::AdjacencyArray;
 use crate::config::Config;
+use crate::topology::{GraphTopology, lat_lon_nodes_and_edges, hexagonal_nodes_and_edges};
+use crate::adaptive_grid::adaptive_nodes_and_edges;
+use crate::region::Region;
+use crate::components::{connected_components, component_sizes, remove_small_components};
 use std::time::Instant;
 
 /// Returns the upper bound of the number of nodes in this graph.
@@ -16,11 +21,15 @@ pub fn get_maximum_number_of_nodes() -> usize {
     Config::global().number_of_nodes() as usize
 }
 
+/// Upper bound of the calculated distances to the coast in meters. Larger distances are stored as this value
+pub const MAXIMUM_COAST_DISTANCE: u32 = 20_000;
+
 #[derive(Clone, Copy, Serialize, Deserialize, JsonSchema)]
 pub struct Edge {
     pub(crate) source: u32,
     pub(crate) target: u32,
-    distance: u32,
+    // great-circle distance in meters, rounded up so the great-circle potential of A* stays consistent
+    pub(crate) distance: u32,
 }
 
 #[derive(Clone, Copy, Serialize, Deserialize, JsonSchema, Debug)]
@@ -29,9 +38,9 @@ pub struct Node {
     pub lon: f64,
 }
 
-impl Into<(f64,f64)> for Node {
-    fn into(self) -> (f64, f64) {
-        (self.lon, self.lat)
+impl From<Node> for (f64, f64) {
+    fn from(node: Node) -> (f64, f64) {
+        (node.lon, node.lat)
     }
 }
 
@@ -44,6 +53,12 @@ pub struct GridGraph {
     pub edges: Vec<Edge>,
     // index equals node id
     pub nodes: Vec<Node>,
+    // distance of every node to the coast in meters, at most MAXIMUM_COAST_DISTANCE
+    pub coast_distances: Vec<u32>,
+    // distance of every edge to the coast in meters, at most MAXIMUM_COAST_DISTANCE. Index equals edge index
+    pub edge_coast_distances: Vec<u32>,
+    // connected component of every node, the largest component is 0
+    pub components: Vec<u32>,
 }
 
 impl GridGraph {
@@ -64,13 +79,40 @@ impl GridGraph {
             number_nodes: 0,
             offsets: Vec::new(),
             edges: Vec::new(),
-            nodes: Vec::new()
+            nodes: Vec::new(),
+            coast_distances: Vec::new(),
+            edge_coast_distances: Vec::new(),
+            components: Vec::new()
         }
     }
-    pub fn new(polygon_test: &PointInPolygonTest) -> GridGraph {
-        // mapping from virtual nodes indices (0..NUMBER_NODES) (includes nodes inside of polygons) to the actual nodes of the grid (includes only nodes of the graph)
+
+    /// Distance of the edge between both nodes to the coast in meters, if the nodes are adjacent
+    pub fn edge_coast_distance(&self, from_node: u32, to_node: u32) -> Option<u32> {
+        let first_edge = self.offsets[from_node as usize] as usize;
+        let last_edge = self.offsets[from_node as usize + 1] as usize;
+        self.edges[first_edge..last_edge].iter()
+            .position(|edge| edge.target == to_node)
+            .map(|i| self.edge_coast_distances[first_edge + i])
+    }
+    /// Generates the graph with the topology of the configuration. Points inside of the polygons or outside of the region are no nodes of the graph
+    pub fn new(polygon_test: &PointInPolygonTest, region: Option<&Region>) -> GridGraph {
         let start_time = Instant::now();
+        let topology = Config::global().topology();
+        println!("Generating graph with the {:?} topology", topology);
         let maximum_number_of_nodes = get_maximum_number_of_nodes();
+        let (nodes, edges) = match topology {
+            GraphTopology::Deserno => GridGraph::deserno_nodes_and_edges(polygon_test, region, maximum_number_of_nodes),
+            GraphTopology::LatLon4 => lat_lon_nodes_and_edges(polygon_test, region, maximum_number_of_nodes, false),
+            GraphTopology::LatLon8 => lat_lon_nodes_and_edges(polygon_test, region, maximum_number_of_nodes, true),
+            GraphTopology::Hexagonal => hexagonal_nodes_and_edges(polygon_test, region, maximum_number_of_nodes),
+            GraphTopology::Adaptive => adaptive_nodes_and_edges(polygon_test, region, maximum_number_of_nodes, Config::global().refinement_levels()),
+        };
+        GridGraph::from_nodes_and_edges(polygon_test, nodes, edges, start_time)
+    }
+
+    /// Nodes distributed equally over the sphere, which are connected to their nearest neighbors in the rounds above and below
+    pub(crate) fn deserno_nodes_and_edges(polygon_test: &PointInPolygonTest, region: Option<&Region>, maximum_number_of_nodes: usize) -> (Vec<Node>, Vec<Vec<Edge>>) {
+        // mapping from virtual nodes indices (0..NUMBER_NODES) (includes nodes inside of polygons) to the actual nodes of the grid (includes only nodes of the graph)
         let mut virtual_nodes_to_index: Vec<Option<u32>> = vec![None;maximum_number_of_nodes];
         let mut number_virtual_nodes: usize = 0;
         let mut number_graph_nodes: usize = 0;
@@ -79,7 +121,7 @@ impl GridGraph {
 
         // algorithm taken from here https://www.cmu.edu/biolphys/deserno/pdf/sphere_equi.pdf
         // number of nodes is only very close not equal to NUMBER_NODES
-        let pi = PI as f64;
+        let pi = PI;
         let radius_earth: f64 = 1.0; // in km
         let a: f64 = 4.0 * pi * (radius_earth.powf(2.0) / maximum_number_of_nodes as f64);
         let d: f64 = a.sqrt();
@@ -106,7 +148,7 @@ impl GridGraph {
                 // convert rad to degrees and lon = polar - 90; lat = azimuthal-180
                 let lon = azimuthal * (180.0 / pi) - 180.0;
 
-                if polygon_test.check_intersection(*&(lon, lat)) {
+                if !region.map_or(true, |region| region.contains((lon, lat))) || polygon_test.check_intersection((lon, lat)) {
                     (n, None)
                 } else {
                     let source_node = Node {lat, lon};
@@ -158,14 +200,12 @@ impl GridGraph {
                                                 add_edge(&mut edges, &nodes, number_graph_nodes, &virtual_nodes_to_index[calc_index_modulo(&number_virtual_nodes_before_last_round, &number_azimuth_steps_last_round, virtual_index_top_left_node - 1)]);
                                                 last_node_mid_top_node_orientation = NodeOrientation::LEFT;
                                             }
-                                        } else {
-                                            if last_node_mid_top_node_orientation == NodeOrientation::LEFT {
-                                                // insert extra edges crossed over the gap,so that no gap is produced
-                                                add_extra_edge(&mut edges, &nodes, number_graph_nodes, &virtual_nodes_to_index[calc_index_modulo(&number_virtual_nodes_before_last_round, &number_azimuth_steps_last_round, virtual_index_top_left_node - 1)]);
-                                                if let Some(left_neighbor_index) = &virtual_nodes_to_index[calc_index_modulo(&number_virtual_nodes_at_start_of_this_round, &(m_phi as usize), number_virtual_nodes + (m_phi - 1) as usize)] {
-                                                    add_extra_edge(&mut edges, &nodes, *left_neighbor_index as usize, &virtual_nodes_to_index[calc_index_modulo(&number_virtual_nodes_before_last_round, &number_azimuth_steps_last_round, virtual_index_top_right_node)]);
+                                        } else if last_node_mid_top_node_orientation == NodeOrientation::LEFT {
+                                            // insert extra edges crossed over the gap,so that no gap is produced
+                                            add_extra_edge(&mut edges, &nodes, number_graph_nodes, &virtual_nodes_to_index[calc_index_modulo(&number_virtual_nodes_before_last_round, &number_azimuth_steps_last_round, virtual_index_top_left_node - 1)]);
+                                            if let Some(left_neighbor_index) = &virtual_nodes_to_index[calc_index_modulo(&number_virtual_nodes_at_start_of_this_round, &(m_phi as usize), number_virtual_nodes + (m_phi - 1) as usize)] {
+                                                add_extra_edge(&mut edges, &nodes, *left_neighbor_index as usize, &virtual_nodes_to_index[calc_index_modulo(&number_virtual_nodes_before_last_round, &number_azimuth_steps_last_round, virtual_index_top_right_node)]);
 
-                                                }
                                             }
                                         }
                                     }
@@ -188,36 +228,85 @@ impl GridGraph {
             number_azimuth_steps_last_round = number_azimuth_steps_this_round;
             number_virtual_nodes_before_last_round = number_virtual_nodes_at_start_of_this_round;
         }
+        println!("number even distributed nodes {}", number_virtual_nodes);
+
+        // Remove unset nodes from nodes array
+        nodes.truncate(number_graph_nodes);
+        edges.truncate(number_graph_nodes);
+        (nodes, edges)
+    }
+
+    /// Removes the edges crossing land and the small components, flattens the edges and calculates the distances to the coast
+    fn from_nodes_and_edges(polygon_test: &PointInPolygonTest, nodes: Vec<Node>, mut edges: Vec<Vec<Edge>>, start_time: Instant) -> GridGraph {
+        let number_graph_nodes = nodes.len();
+        // remove the edges crossing land, which happens if the land between two nodes is narrower than their distance
+        let land_test_start_time = Instant::now();
+        let segment_index = SegmentIndex::new(polygon_test.polygons());
+        let number_edges_before_land_test: usize = edges[..number_graph_nodes].iter().map(|node_edges| node_edges.len()).sum();
+        let number_removed_edges: usize = edges[..number_graph_nodes].par_iter_mut().map(|node_edges| {
+            let number_node_edges = node_edges.len();
+            node_edges.retain(|edge| {
+                // test both directions of an edge the same way, so the graph stays symmetric
+                let (node1_idx, node2_idx) = (edge.source.min(edge.target) as usize, edge.source.max(edge.target) as usize);
+                !segment_index.crosses_polygons(&nodes[node1_idx], &nodes[node2_idx])
+            });
+            number_node_edges - node_edges.len()
+        }).sum();
+        println!("Removed {} of {} edges crossing land in {} seconds", number_removed_edges / 2, number_edges_before_land_test / 2, land_test_start_time.elapsed().as_secs());
+        edges.truncate(number_graph_nodes);
+
+        // water bodies, which are not connected by the graph, e.g. lakes or pockets between the nodes
+        let components = connected_components(&edges);
+        let sizes = component_sizes(&components);
+        println!("Found {} connected components, the largest with {} nodes", sizes.len(), sizes.first().copied().unwrap_or(0));
+        let minimum_component_size = Config::global().min_component_size();
+        let (nodes, edges, components) = if minimum_component_size > 1 {
+            remove_small_components(nodes, edges, components, minimum_component_size)
+        } else {
+            (nodes, edges, components)
+        };
+        let number_graph_nodes = nodes.len();
+
         // flatten edge array to 1 dimension and calculate offsets
         let mut offsets = Vec::with_capacity(edges.len()+1);
         offsets.push(0);
         let mut last_offset = 0;
-        for i in 0..number_graph_nodes {
-            last_offset = edges[i].len() as u32 + last_offset;
+        for node_edges in edges.iter().take(number_graph_nodes) {
+            last_offset += node_edges.len() as u32;
             offsets.push(last_offset);
         }
         let flattened_edges: Vec<Edge> = edges.concat();
-        println!("number even distributed nodes {}", number_virtual_nodes);
         println!("number placed nodes {}", number_graph_nodes);
         println!("number edges {}", flattened_edges.len());
 
-        // Remove unset nodes from nodes array
-        nodes.truncate(number_graph_nodes);
+        // distances to the coast, which keep the routes away from the shore
+        let coast_distance_start_time = Instant::now();
+        let maximum_coast_distance = MAXIMUM_COAST_DISTANCE as f64;
+        let coast_distances: Vec<u32> = nodes.par_iter().map(|node| segment_index.distance_to_polygons(node, node, maximum_coast_distance) as u32).collect();
+        let edge_coast_distances: Vec<u32> = flattened_edges.par_iter().map(|edge| {
+            // both directions of an edge have the same distance
+            let (node1_idx, node2_idx) = (edge.source.min(edge.target) as usize, edge.source.max(edge.target) as usize);
+            segment_index.distance_to_polygons(&nodes[node1_idx], &nodes[node2_idx], maximum_coast_distance) as u32
+        }).collect();
+        println!("Calculated the distances to the coast in {} seconds", coast_distance_start_time.elapsed().as_secs());
         println!("Generated graph in {} seconds", start_time.elapsed().as_secs());
         GridGraph {
             number_nodes: number_graph_nodes as i64,
             edges: flattened_edges,
             offsets,
             nodes,
+            coast_distances,
+            edge_coast_distances,
+            components,
         }
     }
 }
-fn add_edge(edges: &mut Vec<Vec<Edge>>, nodes: &Vec<Node>, node1_idx: usize, node2_idx_option: &Option<u32>) -> Option<f64>{
+pub(crate) fn add_edge(edges: &mut Vec<Vec<Edge>>, nodes: &Vec<Node>, node1_idx: usize, node2_idx_option: &Option<u32>) -> Option<f64>{
     if let Some(node2_idx) = node2_idx_option {
         // target node is part of the graph
-        let distance = calculate_length_between_points_on_sphere(&nodes[node1_idx as usize], &nodes[*node2_idx as usize]);
-        edges[node1_idx].push(Edge{source: node1_idx as u32, target: *node2_idx, distance: distance as u32});
-        edges[*node2_idx as usize].push(Edge{source: *node2_idx, target: node1_idx as u32, distance: distance as u32});
+        let distance = calculate_length_between_points_on_sphere(&nodes[node1_idx], &nodes[*node2_idx as usize]);
+        edges[node1_idx].push(Edge{source: node1_idx as u32, target: *node2_idx, distance: distance.ceil() as u32});
+        edges[*node2_idx as usize].push(Edge{source: *node2_idx, target: node1_idx as u32, distance: distance.ceil() as u32});
         return Some(distance);
     }
     return None;
@@ -226,13 +315,13 @@ fn add_edge(edges: &mut Vec<Vec<Edge>>, nodes: &Vec<Node>, node1_idx: usize, nod
 fn add_extra_edge(edges: &mut Vec<Vec<Edge>>, nodes: &Vec<Node>, node1_idx: usize, node2_idx_option: &Option<u32>) -> Option<f64>{
     if let Some(node2_idx) = node2_idx_option {
         // target node is part of the graph
-        let distance = calculate_length_between_points_on_sphere(&nodes[node1_idx as usize], &nodes[*node2_idx as usize]);
+        let distance = calculate_length_between_points_on_sphere(&nodes[node1_idx], &nodes[*node2_idx as usize]);
         // check for duplicates
         if !edges[node1_idx].iter().any(|e| {e.target == *node2_idx}) {
-            edges[node1_idx].push(Edge { source: node1_idx as u32, target: *node2_idx, distance: distance as u32 });
+            edges[node1_idx].push(Edge { source: node1_idx as u32, target: *node2_idx, distance: distance.ceil() as u32 });
         }
         if !edges[*node2_idx as usize].iter().any(|e| {e.target == node1_idx as u32}) {
-            edges[*node2_idx as usize].push(Edge { source: *node2_idx, target: node1_idx as u32, distance: distance as u32 });
+            edges[*node2_idx as usize].push(Edge { source: *node2_idx, target: node1_idx as u32, distance: distance.ceil() as u32 });
         }
         return Some(distance);
     }
@@ -241,8 +330,8 @@ fn add_extra_edge(edges: &mut Vec<Vec<Edge>>, nodes: &Vec<Node>, node1_idx: usiz
 
 fn calc_index_modulo(round_start_index: &usize, nodes_in_rounds: &usize, index_usize: usize) -> usize {
     let mut index = index_usize as isize;
-    index = index - *round_start_index as isize;
-    index = index + *nodes_in_rounds as isize;
+    index -= *round_start_index as isize;
+    index += *nodes_in_rounds as isize;
     let new_index = (index % *nodes_in_rounds as isize) + *round_start_index as isize;
     new_index as usize
 }
@@ -266,6 +355,37 @@ pub fn distance(lon1_deg: f64, lat1_deg: f64, lon2_deg: f64, lat2_deg: f64) -> f
     return EARTH_RADIUS * c;
 }
 
+/// Point at the fraction of the great-circle segment between both nodes
+pub fn intermediate_point(from: &Node, to: &Node, fraction: f64) -> Node {
+    let from_vector = to_unit_vector(from);
+    let to_vector = to_unit_vector(to);
+    let dot_product = from_vector.0 * to_vector.0 + from_vector.1 * to_vector.1 + from_vector.2 * to_vector.2;
+    let cross_product = (
+        from_vector.1 * to_vector.2 - from_vector.2 * to_vector.1,
+        from_vector.2 * to_vector.0 - from_vector.0 * to_vector.2,
+        from_vector.0 * to_vector.1 - from_vector.1 * to_vector.0
+    );
+    let angle = (cross_product.0 * cross_product.0 + cross_product.1 * cross_product.1 + cross_product.2 * cross_product.2).sqrt().atan2(dot_product);
+    if angle.abs() < f64::EPSILON {
+        return *from;
+    }
+    let from_factor = ((1.0 - fraction) * angle).sin() / angle.sin();
+    let to_factor = (fraction * angle).sin() / angle.sin();
+    let x = from_factor * from_vector.0 + to_factor * to_vector.0;
+    let y = from_factor * from_vector.1 + to_factor * to_vector.1;
+    let z = from_factor * from_vector.2 + to_factor * to_vector.2;
+    Node {
+        lat: z.atan2((x * x + y * y).sqrt()).to_degrees(),
+        lon: y.atan2(x).to_degrees()
+    }
+}
+
+fn to_unit_vector(node: &Node) -> (f64, f64, f64) {
+    let lat = node.lat.to_radians();
+    let lon = node.lon.to_radians();
+    (lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin())
+}
+
 #[derive(Clone, PartialEq, Eq, Copy)]
 enum NodeOrientation {
     LEFT, RIGHT, MID
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::fs::File;
 use std::io::Write;
 
@@ -9,10 +10,12 @@ pub struct JsonFile {
     polygons: Vec<Vec<(f64, f64)>>,
     // polygon in geojson
     points: Vec<(f64, f64)>, // multipoint in geojson
+    // points and lines, which are named in their properties
+    named_geometries: Vec<(Vec<(f64, f64)>, String)>,
 }
 
-impl JsonFile {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for JsonFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut result = String::new() + "  { \"type\": \"FeatureCollection\",
             \"features\": [";
 
@@ -25,7 +28,7 @@ impl JsonFile {
                     temp.push(*polygon.first().unwrap());
                 }
 
-                let mut coords_string = format!("{:?}", temp).replace("(", "[").replace(")", "]");
+                let mut coords_string = format!("{:?}", temp).replace('(', "[").replace(')', "]");
                 coords_string = format!("{{
               \"type\": \"Feature\",
               \"properties\": {{}},
@@ -36,12 +39,12 @@ impl JsonFile {
                 ]
               }}
              }},", coords_string);
-                result.push_str(&*coords_string);
+                result.push_str(&coords_string);
             }
         }
         // mutli points
-        if self.points.len() > 0 {
-            let mut coords_string = format!("{:?}", self.points).replace("(", "[").replace(")", "]");
+        if !self.points.is_empty() {
+            let mut coords_string = format!("{:?}", self.points).replace('(', "[").replace(')', "]");
            // println!("{}", coords_string);
             coords_string = format!("{{
               \"type\": \"Feature\",
@@ -52,12 +55,32 @@ impl JsonFile {
                     {}
               }}
              }},", coords_string);
-            result.push_str(&*coords_string);
+            result.push_str(&coords_string);
         }
-        // remove last comma
-        result.pop();
-        result = result + "]}";
-        result
+        // named points and line strings
+        for (coordinates, name) in &self.named_geometries {
+            let (geometry_type, coords_string) = if coordinates.len() == 1 {
+                ("Point", format!("{:?}", coordinates[0]))
+            } else {
+                ("LineString", format!("{:?}", coordinates))
+            };
+            let coords_string = coords_string.replace('(', "[").replace(')', "]");
+            result.push_str(&format!("{{
+              \"type\": \"Feature\",
+              \"properties\": {{ \"name\": {} }},
+              \"geometry\": {{
+                \"type\": \"{}\",
+                \"coordinates\":
+                    {}
+              }}
+             }},", serde_json::to_string(name).unwrap(), geometry_type, coords_string));
+        }
+        // remove last comma, if there is any feature
+        if result.ends_with(',') {
+            result.pop();
+        }
+        result += "]}";
+        write!(f, "{}", result)
     }
 }
 
@@ -67,12 +90,17 @@ pub struct JsonBuilder {
 
 impl JsonBuilder {
     pub fn new(file_name: String) -> JsonBuilder {
-        let file = JsonFile { file_name, polygons: Vec::new(), points: Vec::new() };
+        let file = JsonFile { file_name, polygons: Vec::new(), points: Vec::new(), named_geometries: Vec::new() };
         JsonBuilder {
             json: file
         }
     }
 
+    /// Creates a builder for GeoJSON, which is only returned as string by `build_string`
+    pub fn new_in_memory() -> JsonBuilder {
+        JsonBuilder::new(String::new())
+    }
+
     pub fn add_polygon(&mut self, polygon: Vec<(f64, f64)>) -> &mut JsonBuilder {
         self.json.polygons.push(polygon);
         self
@@ -88,6 +116,17 @@ impl JsonBuilder {
         self
     }
 
+    /// Adds a point for a single coordinate or a line string for more coordinates with the name as property
+    pub fn add_named_geometry(&mut self, coordinates: Vec<(f64, f64)>, name: String) -> &mut JsonBuilder {
+        self.json.named_geometries.push((coordinates, name));
+        self
+    }
+
+    /// Returns the GeoJSON without writing it to the file
+    pub fn build_string(&self) -> String {
+        self.json.to_string()
+    }
+
     pub fn build(&mut self) -> File {
         let mut file = File::create(&self.json.file_name).expect("could not open file");
         file.write_all(self.json.to_string().as_ref()).expect("could not write to file");
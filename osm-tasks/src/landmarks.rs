@@ -0,0 +1,183 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::dijkstra::{AdjacencyArray, Dijkstra};
+
+/// Strategy used to place the landmarks on the graph
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LandmarkSelection {
+    /// Every landmark is the node farthest away from all landmarks selected so far
+    Farthest,
+    /// Landmarks are placed in regions of the graph, where the lower bounds of the selected landmarks are bad
+    Avoid,
+}
+
+impl FromStr for LandmarkSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "farthest" => Ok(LandmarkSelection::Farthest),
+            "avoid" => Ok(LandmarkSelection::Avoid),
+            _ => Err(format!("Unknown landmark selection: {}", s))
+        }
+    }
+}
+
+/// Precomputed distances between some landmark nodes and all nodes of the graph,
+/// which are used as A* potential with the triangle inequality (ALT)
+#[derive(Serialize, Deserialize)]
+pub struct Landmarks {
+    selection: LandmarkSelection,
+    landmark_nodes: Vec<u32>,
+    // distances[node * number of landmarks + i] is the distance between landmark i and the node
+    distances: Vec<u32>,
+}
+
+impl Landmarks {
+    pub fn new(adjacency_array: Arc<AdjacencyArray>, number_of_landmarks: usize, selection: LandmarkSelection) -> Landmarks {
+        let start_time = Instant::now();
+        let number_of_nodes = adjacency_array.get_nodes_count() as usize;
+        let mut dijkstra = Dijkstra::new(adjacency_array, 0);
+        let mut landmark_nodes: Vec<u32> = Vec::with_capacity(number_of_landmarks);
+        let mut landmark_distances: Vec<Vec<u32>> = Vec::with_capacity(number_of_landmarks);
+        while landmark_nodes.len() < number_of_landmarks.min(number_of_nodes) {
+            let landmark = match selection {
+                LandmarkSelection::Farthest => Landmarks::select_farthest_node(&mut dijkstra, &landmark_distances, number_of_nodes),
+                LandmarkSelection::Avoid => Landmarks::select_avoid_node(&mut dijkstra, &landmark_nodes, &landmark_distances, number_of_nodes),
+            };
+            if landmark_nodes.contains(&landmark) {
+                // every node is covered by a landmark already
+                break;
+            }
+            landmark_nodes.push(landmark);
+            landmark_distances.push(dijkstra.calculate_distances_to_all_nodes(landmark).clone());
+            println!("Selected landmark {} of {}: node {}", landmark_nodes.len(), number_of_landmarks, landmark);
+        }
+        let mut distances = vec![u32::MAX; number_of_nodes * landmark_nodes.len()];
+        for (i, landmark_distance) in landmark_distances.iter().enumerate() {
+            for node in 0..number_of_nodes {
+                distances[node * landmark_nodes.len() + i] = landmark_distance[node];
+            }
+        }
+        println!("Selected {} landmarks in {} seconds", landmark_nodes.len(), start_time.elapsed().as_secs());
+        Landmarks { selection, landmark_nodes, distances }
+    }
+
+    pub fn selection(&self) -> LandmarkSelection {
+        self.selection
+    }
+
+    pub fn number_of_landmarks(&self) -> usize {
+        self.landmark_nodes.len()
+    }
+
+    pub fn get_nodes_count(&self) -> usize {
+        if self.landmark_nodes.is_empty() { 0 } else { self.distances.len() / self.landmark_nodes.len() }
+    }
+
+    /// Returns the lower bound of the distance from a node to the destination nodes as A* potential. Every destination node has an additional
+    /// distance at the end of the route, like in Dijkstra::find_route_between_nodes. The bound of the landmarks is the minimum over the destinations
+    /// of the maximum of the triangle inequality bounds plus the additional distance. The potential is the maximum of this bound and the given potential
+    pub fn potential<'a, F: Fn(u32) -> u32 + 'a>(&'a self, destinations: &'a [(u32, u32)], other_potential: F) -> impl Fn(u32) -> u32 + 'a {
+        move |node: u32| {
+            let landmark_bound = destinations.iter()
+                .map(|(destination_node, destination_distance)| self.lower_bound(node, *destination_node).saturating_add(*destination_distance))
+                .min()
+                .unwrap_or(0);
+            landmark_bound.max(other_potential(node))
+        }
+    }
+
+    /// Lower bound of the distance between two nodes from the triangle inequality of all landmarks
+    fn lower_bound(&self, node: u32, destination_node: u32) -> u32 {
+        let number_of_landmarks = self.landmark_nodes.len();
+        let node_distances = &self.distances[(node as usize * number_of_landmarks)..((node as usize + 1) * number_of_landmarks)];
+        let destination_distances = &self.distances[(destination_node as usize * number_of_landmarks)..((destination_node as usize + 1) * number_of_landmarks)];
+        let mut lower_bound = 0;
+        for i in 0..number_of_landmarks {
+            // the landmark does not reach both nodes, so it does not provide a bound
+            if node_distances[i] == u32::MAX || destination_distances[i] == u32::MAX {
+                continue;
+            }
+            lower_bound = lower_bound.max(if node_distances[i] > destination_distances[i] { node_distances[i] - destination_distances[i] } else { destination_distances[i] - node_distances[i] });
+        }
+        lower_bound
+    }
+
+    fn select_farthest_node(dijkstra: &mut Dijkstra, landmark_distances: &Vec<Vec<u32>>, number_of_nodes: usize) -> u32 {
+        if landmark_distances.is_empty() {
+            // start with the node farthest away from a random node
+            let start_node = rand::thread_rng().gen_range(0..number_of_nodes as u32);
+            return Landmarks::find_farthest_reachable_node(dijkstra.calculate_distances_to_all_nodes(start_node));
+        }
+        let minimum_distances: Vec<u32> = (0..number_of_nodes).map(|node| {
+            landmark_distances.iter().map(|distances| distances[node]).min().unwrap()
+        }).collect();
+        Landmarks::find_farthest_reachable_node(&minimum_distances)
+    }
+
+    fn find_farthest_reachable_node(distances: &Vec<u32>) -> u32 {
+        let mut farthest_node = 0;
+        for node in 0..distances.len() {
+            if distances[node] != u32::MAX && (distances[farthest_node] == u32::MAX || distances[node] > distances[farthest_node]) {
+                farthest_node = node;
+            }
+        }
+        farthest_node as u32
+    }
+
+    /// Avoid heuristic by Goldberg and Harrelson: grows a shortest path tree from a random root node and weights every node
+    /// by how much the selected landmarks underestimate its distance to the root. The new landmark is the leaf reached by
+    /// following the heaviest subtrees which do not contain a landmark yet.
+    fn select_avoid_node(dijkstra: &mut Dijkstra, landmark_nodes: &Vec<u32>, landmark_distances: &Vec<Vec<u32>>, number_of_nodes: usize) -> u32 {
+        let root = rand::thread_rng().gen_range(0..number_of_nodes as u32);
+        let root_distances = dijkstra.calculate_distances_to_all_nodes(root).clone();
+        let previous_nodes = dijkstra.previous_nodes();
+        let mut reachable_nodes: Vec<u32> = (0..number_of_nodes as u32).filter(|node| root_distances[*node as usize] != u32::MAX).collect();
+        // process the nodes bottom up, so every subtree is complete before its root is processed
+        reachable_nodes.sort_by(|a, b| root_distances[*b as usize].cmp(&root_distances[*a as usize]));
+        let mut sizes = vec![0u64; number_of_nodes];
+        let mut contains_landmark = vec![false; number_of_nodes];
+        for landmark in landmark_nodes {
+            contains_landmark[*landmark as usize] = true;
+        }
+        for node in &reachable_nodes {
+            let node = *node as usize;
+            let lower_bound = landmark_distances.iter()
+                .filter(|distances| distances[node] != u32::MAX && distances[root as usize] != u32::MAX)
+                .map(|distances| (distances[node] as i64 - distances[root as usize] as i64).unsigned_abs())
+                .max()
+                .unwrap_or(0);
+            sizes[node] += root_distances[node] as u64 - lower_bound.min(root_distances[node] as u64);
+            let parent = previous_nodes[node] as usize;
+            if parent != node {
+                if contains_landmark[node] {
+                    contains_landmark[parent] = true;
+                } else {
+                    sizes[parent] += sizes[node];
+                }
+            }
+        }
+        for node in &reachable_nodes {
+            if contains_landmark[*node as usize] {
+                sizes[*node as usize] = 0;
+            }
+        }
+        // collect the children of every node in the shortest path tree
+        let mut children: Vec<Vec<u32>> = vec![Vec::new(); number_of_nodes];
+        for node in &reachable_nodes {
+            let parent = previous_nodes[*node as usize];
+            if parent != *node {
+                children[parent as usize].push(*node);
+            }
+        }
+        let mut node = *reachable_nodes.iter().max_by_key(|node| sizes[**node as usize]).unwrap();
+        while let Some(child) = children[node as usize].iter().filter(|child| sizes[**child as usize] > 0).max_by_key(|child| sizes[**child as usize]) {
+            node = *child;
+        }
+        node
+    }
+}
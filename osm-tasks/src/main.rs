@@ -1,4 +1,6 @@
 #![feature(decl_macro, proc_macro_hygiene)]
+// the code passes vectors by reference, returns explicitly and names some constants in capitals
+#![allow(clippy::ptr_arg, clippy::needless_return, clippy::too_many_arguments, clippy::upper_case_acronyms)]
 
 #[macro_use]
 extern crate rocket;
@@ -7,9 +9,12 @@ extern crate rocket_okapi;
 extern crate rocket_contrib;
 
 use std::{env};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
 
 use rocket::State;
+use rocket::response::content;
 use rocket_contrib::json::Json;
 use rocket_okapi::{openapi, routes_with_openapi};
 use rocket_okapi::swagger_ui::{make_swagger_ui, SwaggerUIConfig};
@@ -17,12 +22,15 @@ use rocket_okapi::swagger_ui::{make_swagger_ui, SwaggerUIConfig};
 use crate::grid_graph::{Node};
 use crate::navigator_use_case::NavigatorUseCase;
 use crate::persistence::in_memory_navigator::InMemoryGraph;
-use crate::persistence::in_memory_routing_repo::{InMemoryRoutingRepo, RouteRequest, ShipRoute};
+use crate::persistence::in_memory_routing_repo::{InMemoryRoutingRepo, RouteRequest, ShipRoute, RoutingAlgorithm, LandEndpointHandling, CostModel, CostWeights, ClearanceHandling, DistanceMatrixRequest, DistanceMatrix};
 use crate::persistence::navigator::Navigator;
 use crate::persistence::routing_repo::RoutingRepo;
 use crate::max_testing::max_testing;
 use crate::cors::CORS;
 use crate::config::Config;
+use crate::graph_validation::GraphValidation;
+use crate::region::Region;
+use crate::graph_file::SourceFingerprint;
 
 mod grid_graph;
 mod json_generator;
@@ -36,11 +44,38 @@ mod max_testing;
 mod nearest_neighbor;
 mod cors;
 mod config;
+mod contraction_hierarchies;
+mod landmarks;
+mod concave_hull;
+mod waypoint_order;
+mod alternative_routes;
+mod route_smoothing;
+mod segment_index;
+mod cost_model;
+mod ship_profile;
+mod avoid_areas;
+mod passages;
+mod weather;
+mod topology;
+mod adaptive_grid;
+mod components;
+mod graph_validation;
+mod region;
+mod graph_file;
 
+const METERS_PER_NAUTICAL_MILE: f64 = 1852.0;
+
+// builds the graph again, optionally only inside of a region, which is either a bounding box lon_min,lat_min,lon_max,lat_max
+// or a GeoJSON polygon. Without a region, the graph of the whole world is built, even if a region is configured
 #[openapi]
-#[post("/build_graph")]
-fn build_graph(navigator_use_case: State<NavigatorUseCase>) {
-    navigator_use_case.build_graph();
+#[post("/build_graph?<region>")]
+fn build_graph(region: Option<String>, navigator_use_case: State<NavigatorUseCase>) -> Option<()> {
+    let region = match region {
+        Some(region) => Some(Arc::new(Region::parse(&region).ok()?)),
+        None => None
+    };
+    navigator_use_case.build_graph(region);
+    Some(())
 }
 
 #[openapi]
@@ -50,9 +85,35 @@ fn test(navigator_use_case: State<NavigatorUseCase>) -> Json<u32> {
 }
 
 // returns job id
+// the optional algorithm is one of "dijkstra" (default), "aStar", "bidirectionalDijkstra", "contractionHierarchies" or "alt"
+// the optional number of alternative routes, at most 5, is calculated in addition to the optimal route
+// start or end points on land either "reject" the request or are moved to the nearest node in the water body of the other stops with "relocate" (default)
+// the optional cost model is one of "distance" (default), "time" or "fuel", weighted costs are only supported by the POST request
+// the optional ship profile is selected by name, the departure time is a unix timestamp in seconds and the optional
+// position interval of at least 60 seconds adds the position of the ship at these intervals to the result.
+// the optional minimum distance to the coast in meters replaces the one of the ship profile, closer edges are
+// either penalized (default) or excluded, depending on the clearance handling "penalize" or "exclude"
 #[openapi]
-#[get("/route?<lat_start>&<lon_start>&<lat_end>&<lon_end>")]
-fn route(lat_start: f64, lon_start: f64, lat_end: f64, lon_end: f64, navigator_use_case: State<NavigatorUseCase>) -> Option<Json<Option<u32>>> {
+#[get("/route?<lat_start>&<lon_start>&<lat_end>&<lon_end>&<algorithm>&<alternatives>&<land_endpoints>&<cost_model>&<ship_profile>&<departure_time>&<position_interval>&<min_coast_distance>&<clearance_handling>")]
+fn route(lat_start: f64, lon_start: f64, lat_end: f64, lon_end: f64, algorithm: Option<String>, alternatives: Option<u32>, land_endpoints: Option<String>, cost_model: Option<String>,
+         ship_profile: Option<String>, departure_time: Option<u64>, position_interval: Option<u32>, min_coast_distance: Option<f64>, clearance_handling: Option<String>,
+         navigator_use_case: State<NavigatorUseCase>) -> Option<Json<Option<u32>>> {
+    let algorithm = match algorithm {
+        Some(algorithm) => algorithm.parse::<RoutingAlgorithm>().ok()?,
+        None => RoutingAlgorithm::default()
+    };
+    let land_endpoints = match land_endpoints {
+        Some(land_endpoints) => land_endpoints.parse::<LandEndpointHandling>().ok()?,
+        None => LandEndpointHandling::default()
+    };
+    let cost_model = match cost_model {
+        Some(cost_model) => cost_model.parse::<CostModel>().ok()?,
+        None => CostModel::default()
+    };
+    let clearance_handling = match clearance_handling {
+        Some(clearance_handling) => clearance_handling.parse::<ClearanceHandling>().ok()?,
+        None => ClearanceHandling::default()
+    };
     let route_request = RouteRequest {
         start: Node {
             lon: lon_start,
@@ -61,7 +122,21 @@ fn route(lat_start: f64, lon_start: f64, lat_end: f64, lon_end: f64, navigator_u
         end: Node {
             lon: lon_end,
             lat: lat_end
-        }
+        },
+        algorithm,
+        waypoints: Vec::new(),
+        optimize_waypoint_order: false,
+        alternatives: alternatives.unwrap_or(0),
+        land_endpoints,
+        cost_model,
+        cost_weights: CostWeights::default(),
+        ship_profile,
+        departure_time,
+        position_interval,
+        avoid_areas: Vec::new(),
+        passages: HashMap::new(),
+        min_coast_distance,
+        clearance_handling
     };
     let id = navigator_use_case.calculate_route(route_request);
     if id.is_some() {
@@ -70,21 +145,56 @@ fn route(lat_start: f64, lon_start: f64, lat_end: f64, lon_end: f64, navigator_u
     return None;
 }
 
-// true if job is finished, false if not
+// returns job id, the route visits the waypoints of the request between start and end and does not enter the avoid areas.
+// the passages of the request open, close or change the costs of the configured passages
+#[openapi]
+#[post("/route", format = "json", data = "<route_request>")]
+fn route_with_waypoints(route_request: Json<RouteRequest>, navigator_use_case: State<NavigatorUseCase>) -> Option<Json<Option<u32>>> {
+    let id = navigator_use_case.calculate_route(route_request.into_inner());
+    if id.is_some() {
+        return Some(Json(id));
+    }
+    return None;
+}
+
+// true if job is finished or failed, false if not
 #[openapi]
 #[get("/jobStatus?<id>")]
 fn job_status(id: usize, navigator_use_case: State<NavigatorUseCase>) -> Json<bool> {
-    return Json(navigator_use_case.get_route(id).is_some());
+    return Json(navigator_use_case.get_route(id).is_some() || navigator_use_case.get_error(id).is_some());
+}
+
+// reason why the job failed, e.g. a start point on land
+#[openapi]
+#[get("/jobError/<id>")]
+fn job_error(id: usize, navigator_use_case: State<NavigatorUseCase>) -> Option<Json<String>> {
+    navigator_use_case.get_error(id).map(Json)
 }
 
 #[openapi]
 #[get("/jobResult/<id>")]
 fn job_result(id: usize, navigator_use_case: State<NavigatorUseCase>) -> Option<Json<ShipRoute>> {
-    let route = navigator_use_case.get_route(id);
-    if route.is_some() {
-        return Some(Json(route.unwrap()));
-    }
-    return None;
+    navigator_use_case.get_route(id).map(Json)
+}
+
+// distances between all sources and targets, calculated synchronously
+#[openapi]
+#[post("/matrix", format = "json", data = "<distance_matrix_request>")]
+fn distance_matrix(distance_matrix_request: Json<DistanceMatrixRequest>, navigator_use_case: State<NavigatorUseCase>) -> Option<Json<DistanceMatrix>> {
+    navigator_use_case.calculate_distance_matrix(distance_matrix_request.into_inner()).map(Json)
+}
+
+// area reachable from the start as GeoJSON, limited either by a distance in km or by a duration in hours at a speed in knots
+#[openapi]
+#[get("/isochrone?<lat>&<lon>&<distance_km>&<hours>&<knots>")]
+fn isochrone(lat: f64, lon: f64, distance_km: Option<f64>, hours: Option<f64>, knots: Option<f64>, navigator_use_case: State<NavigatorUseCase>) -> Option<content::Json<String>> {
+    let maximum_distance = match (distance_km, hours, knots) {
+        (Some(distance_km), _, _) => distance_km * 1000.0,
+        (None, Some(hours), Some(knots)) => hours * knots * METERS_PER_NAUTICAL_MILE,
+        _ => return None
+    };
+    let start = Node { lat, lon };
+    navigator_use_case.calculate_isochrone(start, maximum_distance as u32).map(content::Json)
 }
 
 fn main() {
@@ -96,6 +206,16 @@ fn main() {
         return;
     }
     println!("Using file {} and a maximum number of {} nodes.", config.coastlines_file(), config.number_of_nodes());
+    if let Some(problems_path) = config.validate_graph().as_ref() {
+        println!("Validate the graph. Will not start the server");
+        let source = SourceFingerprint::new(Path::new(config.coastlines_file()));
+        let graph = pbf_reader::read_or_create_graph(config.coastlines_file(), source, config.force_rebuild_graph(), config.region().as_deref());
+        let polygons = pbf_reader::read_or_create_polygons(config.coastlines_file(), source, false);
+        let validation = GraphValidation::new(&graph, &polygons, config.topology());
+        validation.print_report();
+        validation.export_geojson(problems_path);
+        return;
+    }
     if let Some(geojson_path) = config.geojson_export_path().as_ref() {
         println!("Generate and export polygons as geoJSON");
         pbf_reader::read_file_and_export_geojson(config.coastlines_file(), geojson_path);
@@ -108,12 +228,12 @@ fn rocket() -> rocket::Rocket {
     let in_memory_routing_repo = InMemoryRoutingRepo::new();
     let routing_repo_mutex: Arc<Mutex<Box<dyn RoutingRepo>>> = Arc::new(Mutex::new(Box::new(in_memory_routing_repo)));
     let in_memory_navigator = InMemoryGraph::new();
-    let navigator_mutex: Arc<Mutex<Box<dyn Navigator>>> = Arc::new(Mutex::new(Box::new(in_memory_navigator)));
-    let navigator_use_case = NavigatorUseCase::new(Arc::clone(&navigator_mutex), Arc::clone(&routing_repo_mutex));
+    let navigator: Arc<dyn Navigator> = Arc::new(in_memory_navigator);
+    let navigator_use_case = NavigatorUseCase::new(Arc::clone(&navigator), Arc::clone(&routing_repo_mutex));
     rocket::ignite()
         .attach(CORS)
         .manage(navigator_use_case)
-        .mount("/", routes_with_openapi![job_status, job_result, route, build_graph, test])
+        .mount("/", routes_with_openapi![job_status, job_result, job_error, route, route_with_waypoints, distance_matrix, isochrone, build_graph, test])
         .mount(
             "/swagger-ui/",
             make_swagger_ui(&SwaggerUIConfig {
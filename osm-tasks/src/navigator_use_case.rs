@@ -1,26 +1,34 @@
 use crate::persistence::navigator::Navigator;
 use std::sync::{Mutex, Arc};
-use crate::persistence::in_memory_routing_repo::{RouteRequest, ShipRoute};
+use crate::persistence::in_memory_routing_repo::{RouteRequest, ShipRoute, DistanceMatrixRequest, DistanceMatrix};
 use std::thread;
 use crate::persistence::routing_repo::RoutingRepo;
+use crate::grid_graph::Node;
+use crate::json_generator::JsonBuilder;
+use crate::concave_hull::concave_hull;
+use crate::region::Region;
+
+/// Number of nearest neighbors considered for every point of the concave hull of an isochrone
+const ISOCHRONE_HULL_NEAREST_NEIGHBORS: usize = 5;
 
 pub struct NavigatorUseCase {
-    pub navigator: Arc<Mutex<Box<dyn Navigator>>>,
+    // the navigator handles concurrent queries itself, so it is not locked
+    pub navigator: Arc<dyn Navigator>,
     pub route_repo: Arc<Mutex<Box<dyn RoutingRepo>>>
 }
 
 impl NavigatorUseCase {
-    pub(crate) fn new(navigator: Arc<Mutex<Box<dyn Navigator>>>, route_repo: Arc<Mutex<Box<dyn RoutingRepo>>>) -> Self {
+    pub(crate) fn new(navigator: Arc<dyn Navigator>, route_repo: Arc<Mutex<Box<dyn RoutingRepo>>>) -> Self {
         NavigatorUseCase {
             navigator,
             route_repo
         }
     }
 
-    pub(crate) fn build_graph(&self) {
+    pub(crate) fn build_graph(&self, region: Option<Arc<Region>>) {
         let clone = self.navigator.clone();
         thread::spawn(move || {
-            clone.lock().expect("could not lock graph").build_graph();
+            clone.build_graph(region);
         });
     }
 
@@ -30,26 +38,43 @@ impl NavigatorUseCase {
         }
         let clone = self.navigator.clone();
         let repo_clone = self.route_repo.clone();
-        let job_id;
-        {
-            job_id = Some(self.route_repo.lock().unwrap().get_job_id());
-        }
+        let job_id = self.route_repo.lock().unwrap().get_job_id();
         thread::spawn(move|| {
-            let result;
-            { // extra scope to unlock navigator after route is calculated
-                let mut nav = clone.lock().unwrap();
-                result = nav.calculate_route(route);
-            }
-            if result.is_some() {
-               // save route
-                repo_clone.lock().unwrap().add_route(result.unwrap());
+            match clone.calculate_route(route) {
+                // save route
+                Ok(route) => repo_clone.lock().unwrap().add_route(job_id as usize, route),
+                Err(error) => repo_clone.lock().unwrap().add_error(job_id as usize, error)
             }
         });
-        job_id
+        Some(job_id)
+    }
+
+    pub(crate) fn calculate_distance_matrix(&self, distance_matrix_request: DistanceMatrixRequest) -> Option<DistanceMatrix> {
+        if self.get_number_nodes() == 0 {
+            return None;
+        }
+        self.navigator.calculate_distance_matrix(distance_matrix_request)
+    }
+
+    /// Returns the reachable area around the start as GeoJSON with the reached nodes as MultiPoint and their concave hull as Polygon
+    pub(crate) fn calculate_isochrone(&self, start: Node, maximum_distance: u32) -> Option<String> {
+        if self.get_number_nodes() == 0 {
+            return None;
+        }
+        let reachable_nodes = self.navigator.calculate_reachable_nodes(start, maximum_distance)?;
+        let points: Vec<(f64, f64)> = reachable_nodes.into_iter().map(|node| node.into()).collect();
+        let mut json_builder = JsonBuilder::new_in_memory();
+        let hull = concave_hull(&points, ISOCHRONE_HULL_NEAREST_NEIGHBORS);
+        // a single reachable node or nodes on a line have no area
+        if !hull.is_empty() {
+            json_builder.add_polygon(hull);
+        }
+        json_builder.add_points(points);
+        Some(json_builder.build_string())
     }
 
     pub(crate) fn get_number_nodes(&self) -> u32 {
-        self.navigator.lock().unwrap().get_number_nodes()
+        self.navigator.get_number_nodes()
     }
 
     pub(crate) fn get_route(&self, id: usize) -> Option<ShipRoute> {
@@ -60,8 +85,8 @@ impl NavigatorUseCase {
         n.get_route(id)
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn get_job_id(&self) -> u32 {
-        self.route_repo.lock().unwrap().get_job_id()
+    /// Returns the reason why the route of the job could not be calculated, if the job failed
+    pub(crate) fn get_error(&self, id: usize) -> Option<String> {
+        self.route_repo.lock().unwrap().get_error(id)
     }
 }
@@ -37,8 +37,7 @@ const Y_SIZE: usize = 100;
 impl NearestNeighbor {
     pub fn new(nodes: &Vec<Node>) -> NearestNeighbor {
         let mut grid = vec![Vec::new(); X_SIZE * Y_SIZE];
-        for i in 0..nodes.len() {
-            let node = &nodes[i];
+        for (i, node) in nodes.iter().enumerate() {
             grid[NearestNeighbor::get_cell_for_node(node)].push(NodeWithId { id: i as u32, lon: node.lon, lat: node.lat });
         }
         NearestNeighbor { grid }
@@ -60,13 +59,32 @@ impl NearestNeighbor {
         panic!("Invariant violated: Could not find nearest neighbor node for coords {} {}", node.lon, node.lat);
     }
 
+    /// Returns up to the given number of nodes near the node, ordered by their distance. The nodes are searched in the cell
+    /// of the node and the cells around it, so the result always contains the nearest neighbor but might miss farther nodes.
+    pub fn find_nearest_neighbors(&self, node: &Node, count: usize) -> Vec<u32> {
+        let nearest_neighbor = self.find_nearest_neighbor(node);
+        let (center_x, center_y) = NearestNeighbor::get_x_y_for_index(NearestNeighbor::get_cell_for_node(node));
+        let mut candidates: Vec<(&NodeWithId, f64)> = Vec::new();
+        for y in (center_y as isize - 1)..=(center_y as isize + 1) {
+            if y < 0 || y >= Y_SIZE as isize { continue; }
+            for x in (center_x as isize - 1)..=(center_x as isize + 1) {
+                let x_mod = ((x + X_SIZE as isize) % X_SIZE as isize) as usize;
+                candidates.extend(self.grid[NearestNeighbor::get_index_for_x_y(x_mod, y as usize)].iter().map(|candidate| (candidate, candidate.distance_to(node))));
+            }
+        }
+        candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        let mut nearest_neighbors = vec![nearest_neighbor];
+        nearest_neighbors.extend(candidates.into_iter().map(|(candidate, _)| candidate.id).filter(|id| *id != nearest_neighbor).take(count.saturating_sub(1)));
+        nearest_neighbors
+    }
+
     /// returns the nearest node and the distance to this node as well as the covered radius for this query
     fn find_nearest_neighbor_for_radius(&self, distance_to_center: usize, center_cell: usize, node: &Node) -> (Option<(&NodeWithId, f64)>, f64) {
         let mut nearest_node_and_distance: Option<(&NodeWithId, f64)> = None;
         // Calculate the radius through the minimal distance to the midpoints of all checked cells
         let mut radius = f64::MAX;
         let (center_x, center_y) = NearestNeighbor::get_x_y_for_index(center_cell);
-        for y in vec![center_y as isize - distance_to_center as isize, center_y as isize + distance_to_center as isize] {
+        for y in [center_y as isize - distance_to_center as isize, center_y as isize + distance_to_center as isize] {
             if y < 0 || y >= Y_SIZE as isize - 1 { continue; }
             for x in (center_x as isize - distance_to_center as isize)..(center_x as isize + distance_to_center as isize) {
                 let x_mod = ((x + X_SIZE as isize) % X_SIZE as isize) as usize; // X_SIZE that the result can not be negative
@@ -75,7 +93,7 @@ impl NearestNeighbor {
                 radius = radius.min(distance(node.lon, node.lat, cell_midpoint_lon, cell_midpoint_lat));
             }
         }
-        for x in vec![center_x as isize - distance_to_center as isize, center_x as isize + distance_to_center as isize] {
+        for x in [center_x as isize - distance_to_center as isize, center_x as isize + distance_to_center as isize] {
             let x_mod = ((x + X_SIZE as isize) % X_SIZE as isize) as usize; // X_SIZE that the result can not be negative
             // the first and last element has been taken into account when iterating the x lines
             for y in (center_y as isize - distance_to_center as isize + 1)..(center_y as isize + distance_to_center as isize - 1) {
@@ -98,11 +116,7 @@ impl NearestNeighbor {
                 nearest_node = Some(x);
             }
         }
-        if nearest_node.is_none() {
-            None
-        } else {
-            Some((nearest_node.unwrap(), distance_to_nearest_node))
-        }
+        nearest_node.map(|nearest_node| (nearest_node, distance_to_nearest_node))
     }
 
     fn calc_mid_point_of_cell(x: usize, y: usize) -> (f64, f64) {
@@ -113,7 +127,7 @@ impl NearestNeighbor {
     fn get_coords_of_x_y(x: usize, y: usize) -> (f64, f64) {
         let p_x = (x as f64 / X_SIZE as f64) * 360.0;
         let p_y = (y as f64 / Y_SIZE as f64) * 180.0;
-        (p_x - 180.0, p_y as f64 - 90.0)
+        (p_x - 180.0, p_y - 90.0)
     }
 
     #[inline]
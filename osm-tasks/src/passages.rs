@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use serde::Deserialize;
+use crate::grid_graph::{distance, Node};
+use crate::nearest_neighbor::NearestNeighbor;
+use crate::region::Region;
+use crate::cost_model::{CostFunction, DistanceCost};
+use crate::persistence::in_memory_routing_repo::PassageOptions;
+
+/// Passage as it is given in the passages file
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PassageDefinition {
+    name: String,
+    // [lon, lat] positions along the passage
+    coordinates: Vec<[f64; 2]>,
+    // waiting time before the passage in seconds
+    #[serde(default)]
+    waiting_time: f64,
+    // extra distance in meters, which is added to the length of the passage
+    #[serde(default)]
+    penalty: f64,
+    // maximum draft in meters of the ships, which can use the passage
+    #[serde(default)]
+    max_draft: Option<f64>,
+}
+
+/// Canal or narrow strait, which is too narrow for the grid graph
+pub struct Passage {
+    pub name: String,
+    // polyline of the passage from the source node to the target node
+    pub coordinates: Vec<Node>,
+    pub source_node: u32,
+    pub target_node: u32,
+    // length of the polyline including the connections to both nodes
+    pub distance: u32,
+    waiting_time: f64,
+    penalty: f64,
+    max_draft: Option<f64>,
+}
+
+impl Passage {
+    fn default_penalty(&self) -> u32 {
+        self.penalty.max(0.0).ceil() as u32
+    }
+
+    /// True if the draft of the ship is deeper than the maximum draft of the passage. Ships with an unknown draft of 0 m can use every passage
+    fn is_too_shallow(&self, draft: f64) -> bool {
+        self.max_draft.map_or(false, |max_draft| draft > max_draft)
+    }
+}
+
+/**
+Passages like canals and narrow straits, which are read from the passages file.
+Every passage is inserted into the graph as an additional edge between the nearest nodes of its ends.
+The edges are expanded into the polylines of the passages in the routes, so the routes follow the passages.
+**/
+pub struct Passages {
+    passages: Vec<Passage>,
+    // index of the passage of every edge, the searches look up the passages for every edge they relax
+    passage_edges: HashMap<(u32, u32), usize>,
+}
+
+impl Passages {
+    /// Reads the passages from a JSON file with an array of passages and connects them to the nearest nodes.
+    /// With a region, only the passages inside of the region are used
+    pub fn new(path: &Option<String>, nodes: &Vec<Node>, nearest_neighbor: &NearestNeighbor, region: Option<&Region>) -> Passages {
+        let path = match path {
+            Some(path) => path,
+            None => return Passages::from_passages(Vec::new())
+        };
+        let file = File::open(path).unwrap_or_else(|error| panic!("Could not open passages file {}: {}", path, error));
+        let definitions: Vec<PassageDefinition> = serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|error| panic!("Could not read passages file {}: {}", path, error));
+        let mut passages: Vec<Passage> = Vec::with_capacity(definitions.len());
+        for definition in definitions {
+            if definition.coordinates.len() < 2 {
+                panic!("The passage {} needs at least two positions", definition.name);
+            }
+            let coordinates: Vec<Node> = definition.coordinates.iter().map(|position| Node { lon: position[0], lat: position[1] }).collect();
+            if let Some(region) = region.filter(|region| coordinates.iter().any(|position| !region.contains((*position).into()))) {
+                println!("Ignore passage {}. It is not inside of the region {}", definition.name, region.name());
+                continue;
+            }
+            let source_node = nearest_neighbor.find_nearest_neighbor(&coordinates[0]);
+            let target_node = nearest_neighbor.find_nearest_neighbor(&coordinates[coordinates.len() - 1]);
+            if source_node == target_node {
+                println!("Ignore passage {}. Both ends are connected to node {}", definition.name, source_node);
+                continue;
+            }
+            if let Some(passage) = passages.iter().find(|passage| edge_key(passage.source_node, passage.target_node) == edge_key(source_node, target_node)) {
+                println!("Ignore passage {}. The passage {} already connects the nodes {} and {}", definition.name, passage.name, source_node, target_node);
+                continue;
+            }
+            let mut polyline = vec![nodes[source_node as usize]];
+            polyline.extend(coordinates.iter().copied());
+            polyline.push(nodes[target_node as usize]);
+            let passage_distance = polyline.windows(2).map(|segment| distance(segment[0].lon, segment[0].lat, segment[1].lon, segment[1].lat)).sum::<f64>();
+            println!("Connect passage {} with length {} m from node {} to node {}", definition.name, passage_distance.round(), source_node, target_node);
+            passages.push(Passage {
+                name: definition.name,
+                coordinates,
+                source_node,
+                target_node,
+                // rounded up, so the great-circle potential stays a lower bound
+                distance: passage_distance.ceil() as u32,
+                waiting_time: definition.waiting_time,
+                penalty: definition.penalty,
+                max_draft: definition.max_draft,
+            });
+        }
+        println!("Loaded {} passages from {}", passages.len(), path);
+        Passages::from_passages(passages)
+    }
+
+    fn from_passages(passages: Vec<Passage>) -> Passages {
+        let passage_edges = passages.iter().enumerate().map(|(i, passage)| (edge_key(passage.source_node, passage.target_node), i)).collect();
+        Passages { passages, passage_edges }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passages.is_empty()
+    }
+
+    /// Edges of the passages, which are inserted into the adjacency array. Their distance includes the penalty of the passages file,
+    /// so the searches without costs, the contraction hierarchy and the landmarks use the open passages with their default penalty
+    pub fn extra_edges(&self) -> Vec<(u32, u32, u32)> {
+        self.passages.iter().map(|passage| (passage.source_node, passage.target_node, passage.distance + passage.default_penalty())).collect()
+    }
+
+    /// Sum of the penalties of the passages file of the passages used by a route, which are part of the distances of the adjacency array
+    pub fn route_penalty(&self, route: &Vec<u32>) -> u32 {
+        route.windows(2).filter_map(|edge| self.find_passage(edge[0], edge[1])).map(|passage| passage.default_penalty()).sum()
+    }
+
+    /// Length of an edge of the adjacency array with the given distance, which does not include the penalty of a passage
+    pub fn edge_length(&self, from_node: u32, to_node: u32, distance: u32) -> u32 {
+        self.find_passage(from_node, to_node).map_or(distance, |passage| passage.distance)
+    }
+
+    fn find_passage(&self, from_node: u32, to_node: u32) -> Option<&Passage> {
+        self.passage_edges.get(&edge_key(from_node, to_node)).map(|i| &self.passages[*i])
+    }
+
+    /// Coordinates of a route of the graph, which follow the polylines of the passages
+    pub fn route_nodes(&self, route: &Vec<u32>, nodes: &Vec<Node>) -> Vec<Node> {
+        let mut route_nodes = Vec::with_capacity(route.len());
+        for i in 0..route.len() {
+            route_nodes.push(nodes[route[i] as usize]);
+            if i + 1 == route.len() {
+                break;
+            }
+            if let Some(passage) = self.find_passage(route[i], route[i + 1]) {
+                if passage.source_node == route[i] {
+                    route_nodes.extend(passage.coordinates.iter().copied());
+                } else {
+                    route_nodes.extend(passage.coordinates.iter().rev().copied());
+                }
+            }
+        }
+        route_nodes
+    }
+
+    /// Names of the passages used by a route of the graph
+    pub fn route_passages(&self, route: &Vec<u32>) -> Vec<String> {
+        route.windows(2).filter_map(|edge| self.find_passage(edge[0], edge[1])).map(|passage| passage.name.clone()).collect()
+    }
+
+    /// Adds the costs of the passages to the cost function of a route request. The passages of the adjacency array are open and have
+    /// their default penalty, so no cost function is needed as long as the request neither closes a passage nor changes its costs,
+    /// the draft of the ship fits through all passages and the distance is minimized. Fails if the request has options for an unknown passage
+    pub fn cost_function<'a>(&self, cost_function: Option<Box<dyn CostFunction + 'a>>, options: &HashMap<String, PassageOptions>, draft: f64) -> Result<Option<Box<dyn CostFunction + 'a>>, String> {
+        if let Some(name) = options.keys().find(|name| self.passages.iter().all(|passage| &passage.name != *name)) {
+            return Err(format!("Unknown passage {}", name));
+        }
+        let changes_passages = options.values().any(|passage_options| !passage_options.enabled || passage_options.waiting_time.is_some() || passage_options.penalty.is_some())
+            || self.passages.iter().any(|passage| passage.is_too_shallow(draft));
+        // the waiting times only cost something with a cost function, e.g. the time
+        let has_waiting_times = cost_function.is_some() && self.passages.iter().any(|passage| passage.waiting_time > 0.0);
+        if !changes_passages && !has_waiting_times {
+            return Ok(cost_function);
+        }
+        let cost_function = cost_function.unwrap_or_else(|| Box::new(DistanceCost));
+        let passage_costs = self.passages.iter().map(|passage| {
+            let extra_cost = match options.get(&passage.name) {
+                _ if passage.is_too_shallow(draft) => None,
+                Some(passage_options) if !passage_options.enabled => None,
+                passage_options => {
+                    let waiting_time = passage_options.and_then(|passage_options| passage_options.waiting_time).unwrap_or(passage.waiting_time);
+                    let penalty = passage_options.and_then(|passage_options| passage_options.penalty).unwrap_or(passage.penalty);
+                    Some((penalty.max(0.0), waiting_time.max(0.0)))
+                }
+            };
+            (edge_key(passage.source_node, passage.target_node), PassageEdgeCost { distance: passage.distance, extra_cost })
+        }).collect();
+        Ok(Some(Box::new(PassageCost { cost_function, passage_costs })))
+    }
+}
+
+/// Length of the edge of a passage and its penalty and waiting time, None if the passage is closed
+struct PassageEdgeCost {
+    distance: u32,
+    extra_cost: Option<(f64, f64)>,
+}
+
+/// Cost function, which adds the waiting time and the penalty of the passages to the costs of their edges and blocks the closed passages.
+/// The edges of the passages in the adjacency array include the default penalty, so their cost is calculated from the length of the passages
+struct PassageCost<'a> {
+    cost_function: Box<dyn CostFunction + 'a>,
+    // costs of the passages by their edge
+    passage_costs: HashMap<(u32, u32), PassageEdgeCost>,
+}
+
+impl<'a> CostFunction for PassageCost<'a> {
+    fn segment_cost(&self, from: &Node, to: &Node, distance: f64) -> f64 {
+        self.cost_function.segment_cost(from, to, distance)
+    }
+    fn minimum_cost_per_meter(&self) -> f64 {
+        self.cost_function.minimum_cost_per_meter()
+    }
+    fn waiting_cost(&self, seconds: f64) -> f64 {
+        self.cost_function.waiting_cost(seconds)
+    }
+    fn graph_edge_cost(&self, from_node: u32, to_node: u32, from: &Node, to: &Node, distance: u32) -> u32 {
+        match self.passage_costs.get(&edge_key(from_node, to_node)) {
+            Some(PassageEdgeCost { distance, extra_cost: Some((penalty, waiting_time)), .. }) => {
+                let extra_cost = (self.cost_function.segment_cost(from, to, *penalty) + self.cost_function.waiting_cost(*waiting_time)).max(0.0).ceil() as u32;
+                self.cost_function.graph_edge_cost(from_node, to_node, from, to, *distance).saturating_add(extra_cost)
+            }
+            // closed passages are not used
+            Some(PassageEdgeCost { extra_cost: None, .. }) => u32::MAX,
+            None => self.cost_function.graph_edge_cost(from_node, to_node, from, to, distance)
+        }
+    }
+}
+
+/// The edges of the passages are symmetric, so both directions of an edge share the same key
+fn edge_key(from_node: u32, to_node: u32) -> (u32, u32) {
+    (from_node.min(to_node), from_node.max(to_node))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::grid_graph::Node;
+    use crate::persistence::in_memory_routing_repo::PassageOptions;
+    use super::{Passage, Passages};
+
+    fn test_passage(name: &str, source_node: u32, target_node: u32, coordinates: Vec<Node>, max_draft: Option<f64>) -> Passage {
+        Passage { name: String::from(name), coordinates, source_node, target_node, distance: 7200, waiting_time: 0.0, penalty: 0.0, max_draft }
+    }
+
+    /// Canal between the nodes 0 and 1 with a maximum draft of 12 m and a strait between the nodes 3 and 2
+    fn test_passages() -> Passages {
+        Passages::from_passages(vec![
+            test_passage("canal", 0, 1, vec![Node { lon: 10.0, lat: 50.0 }, Node { lon: 10.1, lat: 50.0 }], Some(12.0)),
+            test_passage("strait", 3, 2, vec![Node { lon: 11.0, lat: 50.0 }], None),
+        ])
+    }
+
+    #[test]
+    fn routes_follow_the_passages_in_both_directions() {
+        let passages = test_passages();
+        let nodes: Vec<Node> = (0..4).map(|node| Node { lon: node as f64, lat: 0.0 }).collect();
+        let lons = |route: &Vec<u32>| passages.route_nodes(route, &nodes).iter().map(|node| node.lon).collect::<Vec<f64>>();
+        assert_eq!(lons(&vec![0, 1, 2]), vec![0.0, 10.0, 10.1, 1.0, 2.0]);
+        assert_eq!(lons(&vec![2, 1, 0]), vec![2.0, 1.0, 10.1, 10.0, 0.0]);
+        assert_eq!(lons(&vec![1, 2, 3]), vec![1.0, 2.0, 11.0, 3.0]);
+        assert_eq!(passages.route_passages(&vec![1, 0, 1, 2, 3]), vec!["canal", "canal", "strait"]);
+    }
+
+    #[test]
+    fn requests_close_passages_and_change_their_costs() {
+        let passages = test_passages();
+        let (from, to) = (Node { lon: 10.0, lat: 50.0 }, Node { lon: 10.1, lat: 50.0 });
+        let mut options = HashMap::new();
+        options.insert(String::from("canal"), PassageOptions { enabled: true, waiting_time: None, penalty: Some(1000.0) });
+        options.insert(String::from("strait"), PassageOptions { enabled: false, waiting_time: None, penalty: None });
+        let cost_function = passages.cost_function(None, &options, 0.0).unwrap().expect("cost function with the passage options");
+        assert_eq!(cost_function.graph_edge_cost(1, 0, &to, &from, 7200), 8200);
+        assert_eq!(cost_function.graph_edge_cost(2, 3, &from, &to, 7200), u32::MAX);
+        assert_eq!(cost_function.graph_edge_cost(1, 2, &from, &to, 500), 500);
+        options.insert(String::from("unknown"), PassageOptions { enabled: false, waiting_time: None, penalty: None });
+        assert!(passages.cost_function(None, &options, 0.0).is_err());
+    }
+
+    #[test]
+    fn passages_are_closed_for_deeper_ships() {
+        let passages = test_passages();
+        let (from, to) = (Node { lon: 10.0, lat: 50.0 }, Node { lon: 10.1, lat: 50.0 });
+        // ships with an unknown or a smaller draft use the passages of the graph without a cost function
+        assert!(passages.cost_function(None, &HashMap::new(), 0.0).unwrap().is_none());
+        assert!(passages.cost_function(None, &HashMap::new(), 12.0).unwrap().is_none());
+        let cost_function = passages.cost_function(None, &HashMap::new(), 15.0).unwrap().expect("cost function closing the passage");
+        assert_eq!(cost_function.graph_edge_cost(0, 1, &from, &to, 7200), u32::MAX);
+        assert_eq!(cost_function.graph_edge_cost(1, 0, &to, &from, 7200), u32::MAX);
+        assert_eq!(cost_function.graph_edge_cost(2, 3, &from, &to, 7200), 7200);
+    }
+
+    #[test]
+    fn edge_length_leaves_out_the_penalty() {
+        let mut passages = test_passages();
+        passages.passages[0].penalty = 500.0;
+        assert_eq!(passages.extra_edges(), vec![(0, 1, 7700), (3, 2, 7200)]);
+        assert_eq!(passages.edge_length(1, 0, 7700), 7200);
+        assert_eq!(passages.edge_length(1, 2, 7700), 7700);
+        assert_eq!(passages.route_penalty(&vec![3, 2, 1, 0]), 500);
+    }
+}
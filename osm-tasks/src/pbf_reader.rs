@@ -6,59 +6,159 @@ use crate::kml_exporter::KmlExport;
 use crate::polygon_test::PointInPolygonTest;
 use osmpbf::ElementReader;
 use std::fs::File;
-use std::io::{Write, BufWriter, BufReader};
+use std::io::Write;
 use core::iter;
 use std::iter::FromIterator;
 use rand::distributions::{Distribution, Uniform};
 use std::slice::Iter;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use crate::grid_graph::GridGraph;
 use crate::grid_graph;
+use crate::config::Config;
+use crate::topology::GraphTopology;
+use crate::region::Region;
+use crate::graph_file::{GraphFileHeader, SourceFingerprint, load_graph_file, save_graph_file};
 use std::ffi::OsStr;
 use crate::json_generator::JsonBuilder;
+use crate::contraction_hierarchies::ContractedGraph;
+use crate::landmarks::{Landmarks, LandmarkSelection};
+use crate::dijkstra::AdjacencyArray;
+use std::sync::Arc;
 
 /// tries to load the graph for this from disk and builds the graph if prebuild graph was found.
-pub(crate) fn read_or_create_graph<S: AsRef<OsStr> + ?Sized>(osm_path_name: &S, force_create: bool) -> GridGraph {
+/// The graph of a region only contains the nodes inside of the region and is built from the polygons overlapping the region.
+/// A graph file, which was built from another coastlines file or with other parameters, is not loaded and the graph is built again
+pub(crate) fn read_or_create_graph<S: AsRef<OsStr> + ?Sized>(osm_path_name: &S, source: SourceFingerprint, force_create: bool, region: Option<&Region>) -> GridGraph {
     let osm_path= Path::new(osm_path_name);
-    let osm_name = osm_path.file_name().unwrap();
-    let mut graph_file_name = osm_name.to_str().unwrap().to_owned();
-    graph_file_name.push_str(".");
-    graph_file_name.push_str(&*grid_graph::get_maximum_number_of_nodes().to_string());
-    graph_file_name.push_str(".bin");
-    let path = osm_path.with_file_name(graph_file_name);
-    if !force_create {
-        let disk_graph = load_graph_from_disk(&path);
-        if disk_graph.is_ok() {
-            let gra = disk_graph.unwrap();
-            println!("Loaded graph from disk \"{}\". Node count: {}", path.to_str().unwrap(), gra.nodes.len());
-            return gra;
+    let path = graph_file_path(osm_path, ".bin", region);
+    let header = GraphFileHeader::new(source, region);
+    if !force_create && path.exists() {
+        match load_graph_file::<GridGraph>(&path, &header) {
+            Ok((disk_header, gra)) => {
+                println!("Loaded graph from disk \"{}\" built at {}. Node count: {}", path.to_str().unwrap(), disk_header.build_timestamp(), gra.nodes.len());
+                return gra;
+            }
+            Err(reason) => println!("Rebuild the graph, because the file \"{}\" does not match: {}", path.to_str().unwrap(), reason)
         }
     }
-    let polygons = read_file(osm_path.to_str().unwrap());
+    let polygons = read_or_create_polygons(osm_path_name, source, force_create);
+    let polygons = match region {
+        Some(region) => region.filter_polygons(polygons),
+        None => polygons
+    };
     let polygon_test = PointInPolygonTest::new(polygons);
 
     // assign new value to the GRAPH reference
-    let gra = GridGraph::new(&polygon_test);
-    save_graph_to_disk(&path, &gra);
-    println!("Saved graph to disk at {}", path.to_str().unwrap());
+    let gra = GridGraph::new(&polygon_test, region);
+    match save_graph_file(&path, header, &gra) {
+        Ok(()) => println!("Saved graph to disk at {}", path.to_str().unwrap()),
+        Err(error) => println!("Could not save graph to disk: {}", error)
+    }
     return gra;
 }
 
-fn save_graph_to_disk(path: &Path, graph: &GridGraph) {
-    let mut f = BufWriter::new(File::create(path).unwrap());
-    if let Err(e) = bincode::serialize_into(&mut f, graph) {
-        println!("Could not save graph to disk: {:?}", e);
+/// tries to load the coastline polygons from disk and reads them from the coastlines file if they were not saved before.
+/// The polygons do not depend on the number of nodes and are stored next to the coastlines file as <coastlines_file>.polygons.bin
+pub(crate) fn read_or_create_polygons<S: AsRef<OsStr> + ?Sized>(osm_path_name: &S, source: SourceFingerprint, force_create: bool) -> Vec<Vec<(f64, f64)>> {
+    let osm_path = Path::new(osm_path_name);
+    let mut polygons_file_name = osm_path.file_name().unwrap().to_str().unwrap().to_owned();
+    polygons_file_name.push_str(".polygons.bin");
+    let path = osm_path.with_file_name(polygons_file_name);
+    let header = GraphFileHeader::polygons(source);
+    if !force_create && path.exists() {
+        match load_graph_file::<Vec<Vec<(f64, f64)>>>(&path, &header) {
+            Ok((_, polygons)) => {
+                println!("Loaded {} polygons from disk \"{}\"", polygons.len(), path.to_str().unwrap());
+                return polygons;
+            }
+            Err(reason) => println!("Read the coastlines again, because the file \"{}\" does not match: {}", path.to_str().unwrap(), reason)
+        }
     }
+    let polygons = read_file(osm_path.to_str().unwrap());
+    match save_graph_file(&path, header, &polygons) {
+        Ok(()) => println!("Saved polygons to disk at {}", path.to_str().unwrap()),
+        Err(error) => println!("Could not save polygons to disk: {}", error)
+    }
+    polygons
 }
 
-fn load_graph_from_disk(path: &Path) -> bincode::Result<GridGraph> {
-    let mut f = BufReader::new(File::open(path)?);
-    bincode::deserialize_from(&mut f)
+/// tries to load the contraction hierarchy for the graph from disk and contracts the graph if no contracted graph was found.
+/// The contracted graph is stored next to the graph in a file of the form <coastlines_file>.<number_of_nodes>.ch.bin
+pub(crate) fn read_or_create_contracted_graph<S: AsRef<OsStr> + ?Sized>(osm_path_name: &S, source: SourceFingerprint, adjacency_array: &AdjacencyArray, force_create: bool, region: Option<&Region>) -> ContractedGraph {
+    let path = graph_file_path(Path::new(osm_path_name), ".ch.bin", region);
+    let header = GraphFileHeader::new(source, region).derived_from(adjacency_array);
+    if !force_create && path.exists() {
+        match load_graph_file::<ContractedGraph>(&path, &header) {
+            Ok((_, contracted_graph)) if contracted_graph.get_nodes_count() == adjacency_array.get_nodes_count() => {
+                println!("Loaded contracted graph from disk \"{}\"", path.to_str().unwrap());
+                return contracted_graph;
+            }
+            Ok(_) => println!("Contracted graph at \"{}\" does not match the graph", path.to_str().unwrap()),
+            Err(reason) => println!("Contract the graph again, because the file \"{}\" does not match: {}", path.to_str().unwrap(), reason)
+        }
+    }
+    let contracted_graph = ContractedGraph::new(adjacency_array);
+    match save_graph_file(&path, header, &contracted_graph) {
+        Ok(()) => println!("Saved contracted graph to disk at {}", path.to_str().unwrap()),
+        Err(error) => println!("Could not save contracted graph to disk: {}", error)
+    }
+    contracted_graph
+}
+
+/// tries to load the landmarks for the graph from disk and selects new landmarks if no matching landmarks were found.
+/// The landmarks are stored next to the graph in a file of the form <coastlines_file>.<number_of_nodes>.landmarks.bin
+pub(crate) fn read_or_create_landmarks<S: AsRef<OsStr> + ?Sized>(osm_path_name: &S, source: SourceFingerprint, adjacency_array: Arc<AdjacencyArray>, number_of_landmarks: usize, selection: LandmarkSelection, force_create: bool, region: Option<&Region>) -> Landmarks {
+    let path = graph_file_path(Path::new(osm_path_name), ".landmarks.bin", region);
+    let header = GraphFileHeader::new(source, region).derived_from(&adjacency_array);
+    if !force_create && path.exists() {
+        match load_graph_file::<Landmarks>(&path, &header) {
+            Ok((_, landmarks)) if landmarks.get_nodes_count() == adjacency_array.get_nodes_count() as usize && landmarks.number_of_landmarks() == number_of_landmarks && landmarks.selection() == selection => {
+                println!("Loaded landmarks from disk \"{}\"", path.to_str().unwrap());
+                return landmarks;
+            }
+            Ok(_) => println!("Landmarks at \"{}\" do not match the graph or the landmark configuration", path.to_str().unwrap()),
+            Err(reason) => println!("Select the landmarks again, because the file \"{}\" does not match: {}", path.to_str().unwrap(), reason)
+        }
+    }
+    let landmarks = Landmarks::new(adjacency_array, number_of_landmarks, selection);
+    match save_graph_file(&path, header, &landmarks) {
+        Ok(()) => println!("Saved landmarks to disk at {}", path.to_str().unwrap()),
+        Err(error) => println!("Could not save landmarks to disk: {}", error)
+    }
+    landmarks
+}
+
+/// Returns the path <coastlines_file>.<number_of_nodes><suffix> in the directory of the coastlines file.
+/// The topology, the minimum component size and the region are added after the number of nodes, if they are not the defaults
+fn graph_file_path(osm_path: &Path, suffix: &str, region: Option<&Region>) -> PathBuf {
+    let osm_name = osm_path.file_name().unwrap();
+    let mut graph_file_name = osm_name.to_str().unwrap().to_owned();
+    graph_file_name.push('.');
+    graph_file_name.push_str(&grid_graph::get_maximum_number_of_nodes().to_string());
+    // graphs with the default topology keep the file names from before the topologies were added
+    let topology = Config::global().topology();
+    if topology != GraphTopology::Deserno {
+        graph_file_name.push('.');
+        graph_file_name.push_str(&topology.to_string());
+        if topology == GraphTopology::Adaptive {
+            graph_file_name.push_str(&Config::global().refinement_levels().to_string());
+        }
+    }
+    if Config::global().min_component_size() > 1 {
+        graph_file_name.push_str(".min");
+        graph_file_name.push_str(&Config::global().min_component_size().to_string());
+    }
+    if let Some(region) = region {
+        graph_file_name.push('.');
+        graph_file_name.push_str(region.name());
+    }
+    graph_file_name.push_str(suffix);
+    osm_path.with_file_name(graph_file_name)
 }
 
 pub fn read_file(path: &str) -> Vec<Vec<(f64, f64)>> {
     let start_time = Instant::now();
-    let reader = ElementReader::from_path(path).expect(&*format!("failed to read file {}", path));
+    let reader = ElementReader::from_path(path).unwrap_or_else(|_| panic!("failed to read file {}", path));
 
     // key is the first node of the way; value is a tuple containing the last node and the whole way
     let mut coastlines: HashMap<i64, (i64, Vec<i64>)> = HashMap::new();
@@ -72,7 +172,7 @@ pub fn read_file(path: &str) -> Vec<Vec<(f64, f64)>> {
     if let Err(e) = reader.for_each(|item| {
         match item {
             Element::Way(way) => {
-                if let Some(_) = way.tags().find(|(k, v)| *k == "natural" && *v == "coastline") {
+                if way.tags().any(|(k, v)| k == "natural" && v == "coastline") {
                     let first_node_id = way.refs().next().expect("way does not contain any nodes");
                     if let Some(last) = way.refs().last() {
                         coastlines.insert(first_node_id, (last, way.refs().collect()));
@@ -98,7 +198,7 @@ pub fn read_file(path: &str) -> Vec<Vec<(f64, f64)>> {
     check_polygons_closed(&polygons);
 
     // sort polygons by size so that we check the bigger before the smaller ones
-    polygons.sort_by(|a, b| b.len().cmp(&a.len()));
+    polygons.sort_by_key(|polygon| std::cmp::Reverse(polygon.len()));
 
     /*
     let file = "poly";
@@ -134,7 +234,7 @@ pub fn write_to_file(name: String, data: String) {
 }
 
 pub fn points_to_json(points: Vec<(f64, f64)>) -> String {
-    let points_string = format!("{:?}", points).replace("(", "[").replace(")", "]\n");
+    let points_string = format!("{:?}", points).replace('(', "[").replace(')', "]\n");
     let feature = format!("{{ \"type\": \"MultiPoint\",
     \"coordinates\": {}
 }}", points_string);
@@ -161,7 +261,7 @@ pub fn lines_to_json(lines: Vec<((f64, f64), (f64, f64))>) -> String {
       \"properties\": {{}},
       \"geometry\":  {} \
     }}\n,", geometry);
-        features = features + &*feature;
+        features += &feature;
     }
     features.pop();
     format!("{{
@@ -195,7 +295,7 @@ fn test_random_points_in_polygon(polygon_test: &PointInPolygonTest, number_of_po
         (rng_lon.sample(&mut rng), rng_lat.sample(&mut rng))
     }).collect();
     coords.into_par_iter().map(|test_point: (f64, f64)| {
-        if polygon_test.check_intersection(test_point.clone()) {
+        if polygon_test.check_intersection(test_point) {
             return test_point;
         }
         return (f64::NAN, f64::NAN);
@@ -233,7 +333,7 @@ fn merge_ways_to_polygons1(coastlines: HashMap<i64, (i64, Vec<i64>)>, node_to_lo
                 break;
             }
             if let Some(visit) = visited.get(start) {
-                if *visit == true {
+                if *visit {
                     polygons.push(poly);
                     break;
                 }
@@ -1,40 +1,107 @@
-use crate::grid_graph::{GridGraph, Node};
-use crate::pbf_reader::{read_or_create_graph};
+use crate::grid_graph::{distance, GridGraph, Node, MAXIMUM_COAST_DISTANCE};
+use crate::pbf_reader::{read_or_create_graph, read_or_create_polygons, read_or_create_contracted_graph, read_or_create_landmarks};
 use crate::persistence::navigator::Navigator;
-use crate::persistence::in_memory_routing_repo::{ShipRoute, RouteRequest};
-use crate::dijkstra::{Dijkstra};
+use crate::persistence::in_memory_routing_repo::{ShipRoute, RouteRequest, RoutingAlgorithm, LandEndpointHandling, EndpointAdjustment, CostModel, ClearanceHandling, DistanceMatrixRequest, DistanceMatrix};
+use crate::alternative_routes::find_alternative_routes;
+use crate::dijkstra::{AdjacencyArray, Dijkstra, BidirectionalDijkstra, great_circle_potential, great_circle_potential_to_point};
+use std::sync::{Arc, Mutex, RwLock};
+use crate::contraction_hierarchies::{ContractionHierarchies, ContractedGraph};
+use crate::landmarks::Landmarks;
+use crate::graph_file::SourceFingerprint;
+use std::path::Path;
 use crate::nearest_neighbor::NearestNeighbor;
+use crate::waypoint_order::optimize_waypoint_order;
+use crate::polygon_test::PointInPolygonTest;
+use crate::segment_index::SegmentIndex;
+use crate::route_smoothing::{smooth_route, route_distance};
+use crate::cost_model::{CostFunction, ClearanceCost, DistanceCost, create_cost_function, route_cost};
+use crate::avoid_areas::{AvoidAreas, AvoidAreaCost, avoid_area_polygons};
+use crate::ship_profile::{ShipProfile, read_ship_profiles, calculate_voyage, DEFAULT_SHIP_PROFILE, MINIMUM_POSITION_INTERVAL};
+use crate::passages::Passages;
+use crate::weather::Weather;
+use crate::components::ConnectedComponents;
+use crate::region::Region;
+use std::collections::HashMap;
 use crate::config::Config;
 use std::time::Instant;
 
+/// Number of nearby nodes, which the exact start, end and waypoints of a route are connected to
+const ENDPOINT_CONNECTIONS: usize = 4;
+/// Number of nearby nodes, which are tried if the nearest nodes of the stops are in different components
+const COMPONENT_CONNECTIONS: usize = 16;
+/// Number of nodes near a stop on land, which are checked for a node in the component of the other stops
+const RELOCATION_CANDIDATES: usize = 64;
+
 pub(crate) struct InMemoryGraph {
+    // replaced as a whole when the graph is rebuilt, running queries keep using the old graph
+    routing_graph: RwLock<Option<Arc<RoutingGraph>>>,
+    ship_profiles: HashMap<String, ShipProfile>,
+}
+
+/// Graph data, which is shared read-only by all queries
+struct RoutingGraph {
     graph: GridGraph,
-    dijkstra: Option<Dijkstra>,
-    nearest_neighbor: Option<NearestNeighbor>
+    adjacency_array: Arc<AdjacencyArray>,
+    contracted_graph: Option<Arc<ContractedGraph>>,
+    landmarks: Option<Landmarks>,
+    nearest_neighbor: NearestNeighbor,
+    // canals and straits, which are additional edges of the adjacency array
+    passages: Passages,
+    // connected components of the graph including the passages
+    components: ConnectedComponents,
+    // currents and winds, which change the travel times
+    weather: Option<Weather>,
+    // checks the stops for land
+    polygon_test: PointInPolygonTest,
+    // cells of the segment index over the polygons of the polygon test, which checks the connections of the query points and the smoothed routes for land
+    segment_cells: Vec<Vec<(u32, u32)>>,
+    // area of the graph, the stops of the routes have to be inside
+    region: Option<Arc<Region>>,
+    // search states which are not used by a query at the moment
+    search_states: Mutex<Vec<SearchState>>,
+}
+
+/// Departure of a leg, from which the weather is evaluated
+#[derive(Clone, Copy)]
+struct Departure {
+    // unix timestamp in seconds
+    time: u64,
+    // speed of the ship through the water
+    meters_per_second: f64,
+}
+
+/// Scratch space of the search algorithms. Every running query uses its own search state
+struct SearchState {
+    dijkstra: Dijkstra,
+    bidirectional_dijkstra: BidirectionalDijkstra,
+    contraction_hierarchies: Option<ContractionHierarchies>,
+}
+
+impl SearchState {
+    /// Number of nodes settled by the last search of the algorithm
+    fn settled_nodes(&self, algorithm: RoutingAlgorithm) -> u32 {
+        match algorithm {
+            RoutingAlgorithm::BidirectionalDijkstra => self.bidirectional_dijkstra.settled_nodes(),
+            RoutingAlgorithm::ContractionHierarchies => self.contraction_hierarchies.as_ref().map_or(0, |contraction_hierarchies| contraction_hierarchies.settled_nodes()),
+            _ => self.dijkstra.settled_nodes()
+        }
+    }
 }
 
 impl Navigator for InMemoryGraph {
     fn new() -> InMemoryGraph {
         let config = Config::global();
+        let in_memory_graph = InMemoryGraph {
+            routing_graph: RwLock::new(None),
+            ship_profiles: read_ship_profiles(config.ship_profiles()),
+        };
         if config.build_graph_on_startup() {
-            let graph =  read_or_create_graph(config.coastlines_file(), config.force_rebuild_graph());
-            let dijkstra = Some(Dijkstra::new(graph.adjacency_array(), graph.nodes.len() as u32 - 1));
-            let nearest_neighbor = Some(NearestNeighbor::new(&graph.nodes));
-            InMemoryGraph {
-                graph,
-                dijkstra,
-                nearest_neighbor
-            }
-        } else {
-            InMemoryGraph {
-                graph: GridGraph::default(),
-                dijkstra: None,
-                nearest_neighbor: None
-            }
+            in_memory_graph.build_graph(config.region());
         }
+        in_memory_graph
     }
 
-    fn build_graph(&mut self) {
+    fn build_graph(&self, region: Option<Arc<Region>>) {
         /*let polygons =
         //let polygons = read_file("./iceland-coastlines.osm.pbf");
         let polygon_test = PointInPolygonTest::new(polygons);
@@ -43,31 +110,600 @@ impl Navigator for InMemoryGraph {
         // self.graph = read_or_create_graph("./iceland-coastlines.osm.pbf");
         // self.graph = read_or_create_graph("./planet-coastlines.pbf.sec");
         let config = Config::global();
-        self.graph = read_or_create_graph(config.coastlines_file(), config.force_rebuild_graph());
-        self.dijkstra = Some(Dijkstra::new(self.graph.adjacency_array(), self.get_number_nodes() - 1));
-        self.nearest_neighbor = Some(NearestNeighbor::new(&self.graph.nodes));
-    }
-
-    fn calculate_route(&mut self, route_request: RouteRequest) -> Option<ShipRoute> {
-        if let Some(dijkstra) = self.dijkstra.as_mut() {
-            let start_node = self.nearest_neighbor.as_ref().unwrap().find_nearest_neighbor(&route_request.start());
-            let end_node = self.nearest_neighbor.as_ref().unwrap().find_nearest_neighbor(&route_request.end());
-            let start_time = Instant::now();
-            dijkstra.change_source_node(start_node);
-            if let Some(route_and_distance) = dijkstra.find_route(end_node) {
-                let route: Vec<u32> = route_and_distance.0;
-                let distance = route_and_distance.1;
-                let nodes_route: Vec<Node> = route.into_iter().map(|i| {self.graph.nodes[i as usize]}).collect();
-                println!("Calculated route from {} to {} with distance {} in {} ms", start_node, end_node, distance, start_time.elapsed().as_millis());
-                return Some(ShipRoute::new(nodes_route, distance));
-            } else {
-                println!("Could not calculate route. Dijkstra is not initialized");
-            }
+        // the coastlines file is hashed once for all files of the graph
+        let source = SourceFingerprint::new(Path::new(config.coastlines_file()));
+        let graph = read_or_create_graph(config.coastlines_file(), source, config.force_rebuild_graph(), region.as_deref());
+        let nearest_neighbor = NearestNeighbor::new(&graph.nodes);
+        let passages = Passages::new(config.passages(), &graph.nodes, &nearest_neighbor, region.as_deref());
+        // the open passages are part of the adjacency array, which the landmarks and the contraction hierarchy are built for
+        let adjacency_array = if passages.is_empty() {
+            Arc::new(graph.adjacency_array())
+        } else {
+            Arc::new(graph.adjacency_array().with_extra_edges(&passages.extra_edges()))
+        };
+        let landmarks = if config.landmarks() > 0 {
+            Some(read_or_create_landmarks(config.coastlines_file(), source, Arc::clone(&adjacency_array), config.landmarks() as usize, config.landmark_selection(), config.force_rebuild_graph(), region.as_deref()))
+        } else {
+            None
+        };
+        let contracted_graph = if config.contraction_hierarchies() {
+            Some(Arc::new(read_or_create_contracted_graph(config.coastlines_file(), source, &adjacency_array, config.force_rebuild_graph(), region.as_deref())))
+        } else {
+            None
+        };
+        let components = ConnectedComponents::new(&graph.components, &passages.extra_edges());
+        let polygons = read_or_create_polygons(config.coastlines_file(), source, false);
+        let polygon_test = PointInPolygonTest::new(match region.as_deref() {
+            Some(region) => region.filter_polygons(polygons),
+            None => polygons
+        });
+        let segment_cells = SegmentIndex::new(polygon_test.polygons()).into_cells();
+        let weather = Weather::from_config(region.as_deref()).unwrap_or_else(|error| {
+            println!("{}. Calculate the routes without currents and winds", error);
+            None
+        });
+        let routing_graph = RoutingGraph { graph, adjacency_array, contracted_graph, landmarks, nearest_neighbor, passages, components, weather, polygon_test, segment_cells, region, search_states: Mutex::new(Vec::new()) };
+        *self.routing_graph.write().unwrap() = Some(Arc::new(routing_graph));
+    }
+
+    fn calculate_route(&self, route_request: RouteRequest) -> Result<ShipRoute, String> {
+        let route = self.routing_graph().ok_or_else(|| String::from("The graph is not built")).and_then(|routing_graph| {
+            let ship_profile_name = route_request.ship_profile().as_deref().unwrap_or(DEFAULT_SHIP_PROFILE);
+            let ship_profile = self.ship_profiles.get(ship_profile_name).ok_or_else(|| format!("Unknown ship profile {}", ship_profile_name))?;
+            routing_graph.with_search_state(|search_state| routing_graph.calculate_route(search_state, &route_request, ship_profile))
+        });
+        // the failed requests are printed once with their reason, like the calculated routes
+        if let Err(error) = &route {
+            println!("Could not calculate route. {}", error);
         }
-        None
+        route
+    }
+
+    fn calculate_distance_matrix(&self, distance_matrix_request: DistanceMatrixRequest) -> Option<DistanceMatrix> {
+        let routing_graph = self.routing_graph()?;
+        let start_time = Instant::now();
+        let nodes = &routing_graph.graph.nodes;
+        let mut points = distance_matrix_request.sources().clone();
+        points.extend(distance_matrix_request.targets().iter().copied());
+        let mut point_nodes = routing_graph.connect_to_nodes_of_common_component(&points);
+        let target_nodes = point_nodes.split_off(distance_matrix_request.sources().len());
+        let mut distances = Vec::with_capacity(distance_matrix_request.sources().len());
+        let mut routes = Vec::with_capacity(distance_matrix_request.sources().len());
+        routing_graph.with_search_state(|search_state| {
+            let dijkstra = &mut search_state.dijkstra;
+            for source_node in point_nodes {
+                // the search from the source is continued for every target, so each source is searched at most once
+                if let Some(source_node) = source_node {
+                    dijkstra.change_source_node(source_node);
+                }
+                let mut source_distances = Vec::with_capacity(target_nodes.len());
+                let mut source_routes = Vec::with_capacity(target_nodes.len());
+                for target_node in &target_nodes {
+                    // targets in other components are unreachable, so the search does not have to visit the whole component of the source.
+                    // Points, which can not be connected to the graph without crossing land, are unreachable, too
+                    let route_and_distance = match (source_node, *target_node) {
+                        (Some(source_node), Some(target_node)) if routing_graph.components.component(source_node) == routing_graph.components.component(target_node) => {
+                            dijkstra.find_route(target_node)
+                        }
+                        _ => None
+                    };
+                    // the distances of the passages in the adjacency array include their penalty
+                    source_distances.push(route_and_distance.as_ref().map(|(route, distance)| distance - routing_graph.passages.route_penalty(route)));
+                    if distance_matrix_request.include_geometry() {
+                        source_routes.push(route_and_distance.map(|(route, _)| routing_graph.passages.route_nodes(&route, nodes)));
+                    }
+                }
+                distances.push(source_distances);
+                routes.push(source_routes);
+            }
+        });
+        println!("Calculated distance matrix for {} sources and {} targets in {} ms", distance_matrix_request.sources().len(), target_nodes.len(), start_time.elapsed().as_millis());
+        Some(DistanceMatrix::new(distances, if distance_matrix_request.include_geometry() { Some(routes) } else { None }))
+    }
+
+    fn calculate_reachable_nodes(&self, start: Node, maximum_distance: u32) -> Option<Vec<Node>> {
+        let routing_graph = self.routing_graph()?;
+        let start_time = Instant::now();
+        let start_node = routing_graph.nearest_neighbor.find_nearest_neighbor(&start);
+        let reachable_nodes = routing_graph.with_search_state(|search_state| search_state.dijkstra.find_reachable_nodes(start_node, maximum_distance));
+        println!("Found {} nodes within {} m of node {} in {} ms", reachable_nodes.len(), maximum_distance, start_node, start_time.elapsed().as_millis());
+        let nodes = &routing_graph.graph.nodes;
+        Some(reachable_nodes.into_iter().map(|i| nodes[i as usize]).collect())
     }
 
     fn get_number_nodes(&self) -> u32 {
-        self.graph.nodes.len() as u32
+        self.routing_graph().map_or(0, |routing_graph| routing_graph.graph.nodes.len() as u32)
+    }
+}
+
+impl InMemoryGraph {
+    fn routing_graph(&self) -> Option<Arc<RoutingGraph>> {
+        self.routing_graph.read().unwrap().as_ref().map(Arc::clone)
+    }
+}
+
+impl RoutingGraph {
+    /// Runs the function with a search state of the pool. A new search state is created if all search states are in use
+    fn with_search_state<T, F: FnOnce(&mut SearchState) -> T>(&self, function: F) -> T {
+        let search_state = self.search_states.lock().unwrap().pop();
+        let mut search_state = search_state.unwrap_or_else(|| SearchState {
+            dijkstra: Dijkstra::new(Arc::clone(&self.adjacency_array), 0),
+            bidirectional_dijkstra: BidirectionalDijkstra::new(Arc::clone(&self.adjacency_array)),
+            contraction_hierarchies: self.contracted_graph.as_ref().map(|contracted_graph| ContractionHierarchies::new(Arc::clone(contracted_graph))),
+        });
+        let result = function(&mut search_state);
+        self.search_states.lock().unwrap().push(search_state);
+        result
+    }
+
+    fn calculate_route(&self, search_state: &mut SearchState, route_request: &RouteRequest, ship_profile: &ShipProfile) -> Result<ShipRoute, String> {
+        let start_time = Instant::now();
+        if let Some(position_interval) = route_request.position_interval().filter(|interval| *interval < MINIMUM_POSITION_INTERVAL) {
+            return Err(format!("The position interval {} s is shorter than the minimum of {} s", position_interval, MINIMUM_POSITION_INTERVAL));
+        }
+        if route_request.alternatives() > 0 && !route_request.waypoints().is_empty() {
+            return Err(String::from("Alternative routes are only calculated for routes without waypoints"));
+        }
+        // the stops of the route: start, waypoints in the requested order and end
+        let mut stops = vec![route_request.start()];
+        stops.extend(route_request.waypoints().iter().copied());
+        stops.push(route_request.end());
+        if let Some(region) = self.region.as_ref() {
+            if let Some(i) = (0..stops.len()).find(|i| !region.contains(stops[*i].into())) {
+                return Err(format!("The {} ({}, {}) is outside of the region of the graph", stop_name(i, stops.len()), stops[i].lat, stops[i].lon));
+            }
+        }
+        let endpoint_adjustments = self.move_stops_off_land(&mut stops, route_request.land_endpoints())?;
+        let avoid_area_polygons = avoid_area_polygons(route_request.avoid_areas())?;
+        let avoid_areas = if avoid_area_polygons.is_empty() { None } else { Some(AvoidAreas::new(&avoid_area_polygons)?) };
+        let avoid_areas = avoid_areas.as_ref();
+        if let Some(stop) = stops.iter().find(|stop| avoid_areas.map_or(false, |avoid_areas| avoid_areas.contains(stop))) {
+            return Err(format!("The stop ({}, {}) is inside an avoid area", stop.lat, stop.lon));
+        }
+        // no cost function if the distance is minimized on the whole graph, which is supported by all algorithms
+        let cost_function: Option<Box<dyn CostFunction>> = match avoid_areas {
+            Some(avoid_areas) => Some(Box::new(AvoidAreaCost::new(create_cost_function(route_request.cost_model(), route_request.cost_weights(), ship_profile), avoid_areas))),
+            None if route_request.cost_model() != CostModel::Distance => Some(create_cost_function(route_request.cost_model(), route_request.cost_weights(), ship_profile)),
+            None => None
+        };
+        // the minimum distance to the coast of the request replaces the one of the ship profile
+        let minimum_coast_distance = route_request.min_coast_distance().unwrap_or(ship_profile.min_coast_distance).max(0.0);
+        // the distances to the coast are only calculated up to the maximum distance
+        let minimum_coast_distance = (minimum_coast_distance.ceil() as u32).min(MAXIMUM_COAST_DISTANCE);
+        let cost_function: Option<Box<dyn CostFunction>> = if minimum_coast_distance > 0 {
+            let cost_function = cost_function.unwrap_or_else(|| Box::new(DistanceCost));
+            Some(Box::new(ClearanceCost::new(cost_function, &self.graph, minimum_coast_distance, route_request.clearance_handling())))
+        } else {
+            cost_function
+        };
+        // the passages only need costs, if the request closes a passage or changes its costs
+        let cost_function = self.passages.cost_function(cost_function, route_request.passages(), ship_profile.max_draft)?;
+        let cost_function = cost_function.as_deref();
+        let cost = |route: &Vec<Node>, graph_routes: &[Vec<u32>], distance: u32| self.route_cost(cost_function, route, graph_routes, distance);
+        // the weather is evaluated from the departure, or from its first time step if the request has no departure time
+        let departure_time = route_request.departure_time().or_else(|| self.weather.as_ref().map(|weather| weather.start_time()));
+        let weather = self.weather.as_ref();
+        let voyage = |route: &Vec<Node>| calculate_voyage(ship_profile, route, departure_time, route_request.position_interval(), weather);
+        // the costs of the time cost model are seconds, so the weather is evaluated at the arrival time at every node
+        let mut departure = match departure_time {
+            Some(time) if weather.is_some() && route_request.cost_model() == CostModel::Time => Some(Departure { time, meters_per_second: ship_profile.meters_per_second() }),
+            _ => None
+        };
+        // a cost model, which only rescales the distance, has the same optimal routes as the distance. These routes are searched on the distances
+        // with the requested algorithm and only their costs are calculated with the cost function. The time dependent search always needs the costs
+        let cost_proportional_to_distance = departure.is_none() && cost_function.map_or(true, |cost_function| cost_function.is_proportional_to_distance());
+        let search_cost_function = if cost_proportional_to_distance { None } else { cost_function };
+        let algorithm = self.supported_algorithm(route_request.algorithm(), search_cost_function.is_some());
+        // the alternatives are searched in the graph, so the direct route is only used without alternatives
+        if Config::global().smooth_routes() && stops.len() == 2 && route_request.alternatives() == 0 && self.is_shortcut_free(avoid_areas, minimum_coast_distance, &stops[0], &stops[1]) {
+            let direct_route = stops.clone();
+            let distance = route_distance(&direct_route);
+            let direct_route_cost = cost(&direct_route, &[], distance);
+            let direct_route_voyage = voyage(&direct_route);
+            println!("Calculated direct route with distance {} in {} ms, start and end can see each other", distance, start_time.elapsed().as_millis());
+            return Ok(ShipRoute::new(direct_route, distance, distance, vec![distance], Vec::new())
+                .with_cost(route_request.cost_model(), direct_route_cost)
+                .with_algorithm(algorithm, cost_proportional_to_distance)
+                .with_voyage(direct_route_voyage)
+                .with_endpoint_adjustments(endpoint_adjustments));
+        }
+        let connections: Vec<Vec<(u32, u32)>> = stops.iter().map(|stop| self.connect_point(avoid_areas, stop, ENDPOINT_CONNECTIONS)).collect();
+        let connections = self.connect_to_common_component(avoid_areas, &stops, connections)?;
+        let stop_order = if route_request.optimize_waypoint_order() && route_request.waypoints().len() > 1 {
+            // the order is optimized for the nearest nodes of the stops
+            let stop_nodes = connections.iter().map(|stop_connections| stop_connections[0].0).collect();
+            optimize_waypoint_order(&self.calculate_costs_between_stops(search_state, search_cost_function, &stop_nodes))
+        } else {
+            (0..stops.len()).collect()
+        };
+        let mut routes = Vec::with_capacity(stops.len() - 1);
+        let mut nodes_route: Vec<Node> = vec![stops[stop_order[0]]];
+        let mut raw_distance = 0;
+        let mut legs = Vec::with_capacity(stops.len() - 1);
+        let mut settled_nodes = 0;
+        let mut passages = Vec::new();
+        for leg in stop_order.windows(2) {
+            let (leg_route, leg_distance) = match self.find_route(search_state, algorithm, search_cost_function, departure, (&stops[leg[0]], &connections[leg[0]]), (&stops[leg[1]], &connections[leg[1]])) {
+                Some(route_and_distance) => route_and_distance,
+                None => {
+                    // the stops are in the same component, so the route is blocked by the restrictions of the request
+                    let mut restrictions = Vec::new();
+                    if avoid_areas.is_some() {
+                        restrictions.push("the avoid areas");
+                    }
+                    if route_request.passages().values().any(|passage_options| !passage_options.enabled) {
+                        restrictions.push("the closed passages");
+                    }
+                    if minimum_coast_distance > 0 && route_request.clearance_handling() == ClearanceHandling::Exclude {
+                        restrictions.push("the excluded waters near the coast");
+                    }
+                    let reason = match restrictions.split_last() {
+                        None => String::new(),
+                        Some((last, [])) => format!(", which avoids {}", last),
+                        Some((last, others)) => format!(", which avoids {} and {}", others.join(", "), last)
+                    };
+                    return Err(format!("Unreachable: there is no route from ({}, {}) to ({}, {}){}", stops[leg[0]].lat, stops[leg[0]].lon, stops[leg[1]].lat, stops[leg[1]].lon, reason));
+                }
+            };
+            // the leg starts and ends at the exact stops
+            let mut leg_nodes = vec![stops[leg[0]]];
+            leg_nodes.extend(self.passages.route_nodes(&leg_route, &self.graph.nodes));
+            leg_nodes.push(stops[leg[1]]);
+            passages.extend(self.passages.route_passages(&leg_route));
+            // every leg is smoothed on its own, so the route still visits all waypoints
+            let (leg_nodes, smoothed_leg_distance) = self.smooth_route(avoid_areas, minimum_coast_distance, leg_nodes, leg_distance);
+            // the next leg departs on arrival at the end of this leg
+            if let Some(leg_departure) = departure.as_mut() {
+                leg_departure.time += calculate_voyage(ship_profile, &leg_nodes, Some(leg_departure.time), None, weather).duration();
+            }
+            // the first node of a leg is the last node of the previous leg
+            nodes_route.extend(leg_nodes.into_iter().skip(1));
+            raw_distance += leg_distance;
+            legs.push(smoothed_leg_distance);
+            routes.push(leg_route);
+            settled_nodes += search_state.settled_nodes(algorithm);
+        }
+        let distance = legs.iter().sum();
+        let alternatives = if route_request.alternatives() > 0 {
+            self.find_alternative_routes(search_state, algorithm, (route_request.cost_model(), cost_function), (avoid_areas, minimum_coast_distance), &routes[0], route_request.alternatives() as usize, (&stops[0], &connections[0]), (&stops[1], &connections[1]))
+        } else {
+            Vec::new()
+        };
+        // the waypoints are numbered without the start, which is the first stop
+        let waypoint_order = stop_order[1..(stop_order.len() - 1)].iter().map(|stop| stop - 1).collect();
+        let total_cost = cost(&nodes_route, &routes, distance);
+        let route_voyage = voyage(&nodes_route);
+        let coast_distance = routes.iter().flatten().map(|node| self.graph.coast_distances[*node as usize]).min();
+        println!("Calculated route with {} legs, distance {} (raw distance {}) and {:?} cost {} in {} ms using {:?} ({} settled nodes)",
+                 legs.len(), distance, raw_distance, route_request.cost_model(), total_cost, start_time.elapsed().as_millis(), algorithm, settled_nodes);
+        Ok(ShipRoute::new(nodes_route, distance, raw_distance, legs, waypoint_order)
+            .with_cost(route_request.cost_model(), total_cost)
+            .with_algorithm(algorithm, cost_proportional_to_distance)
+            .with_voyage(route_voyage)
+            .with_passages(passages)
+            .with_coast_distance(coast_distance)
+            .with_alternatives(alternatives)
+            .with_endpoint_adjustments(endpoint_adjustments))
+    }
+
+    /// Checks if any stop is on land and either rejects the route or moves the stop to the nearest node, which is in the component
+    /// the stops in the water are connected to without crossing land. Returns the moved stops
+    fn move_stops_off_land(&self, stops: &mut Vec<Node>, land_endpoints: LandEndpointHandling) -> Result<Vec<EndpointAdjustment>, String> {
+        let on_land: Vec<bool> = stops.iter().map(|stop| self.polygon_test.check_intersection((*stop).into())).collect();
+        if let (Some(i), LandEndpointHandling::Reject) = (on_land.iter().position(|on_land| *on_land), land_endpoints) {
+            return Err(format!("The {} ({}, {}) is on land", stop_name(i, stops.len()), stops[i].lat, stops[i].lon));
+        }
+        if !on_land.contains(&true) {
+            return Ok(Vec::new());
+        }
+        let water_connections: Vec<Vec<(u32, u32)>> = (0..stops.len())
+            .filter(|i| !on_land[*i])
+            .map(|i| self.connect_point(None, &stops[i], COMPONENT_CONNECTIONS))
+            .collect();
+        let water_component = self.common_component(&water_connections);
+        let mut endpoint_adjustments = Vec::new();
+        for i in (0..stops.len()).filter(|i| on_land[*i]) {
+            let candidates = self.nearest_neighbor.find_nearest_neighbors(&stops[i], RELOCATION_CANDIDATES);
+            // without stops in the water, the stop is moved to the largest component around it
+            let component = water_component.unwrap_or_else(|| candidates.iter().map(|node| self.components.component(*node)).max_by_key(|component| self.components.size(*component)).unwrap());
+            let node = match candidates.iter().find(|node| self.components.component(**node) == component) {
+                Some(node) => *node,
+                None => candidates[0]
+            };
+            let relocated = self.graph.nodes[node as usize];
+            let moved_distance = distance(stops[i].lon, stops[i].lat, relocated.lon, relocated.lat).round() as u32;
+            endpoint_adjustments.push(EndpointAdjustment::new(i, stops[i], relocated, moved_distance));
+            stops[i] = relocated;
+        }
+        Ok(endpoint_adjustments)
+    }
+
+    /// Connects a point to some of the nearest nodes, which can be reached from the point without crossing land or an avoid area.
+    /// Returns the nodes with the length of their connection, which are empty if every connection is blocked
+    fn connect_point(&self, avoid_areas: Option<&AvoidAreas>, point: &Node, number_connections: usize) -> Vec<(u32, u32)> {
+        let nearest_neighbors = self.nearest_neighbor.find_nearest_neighbors(point, number_connections);
+        let connection = |node: u32| {
+            let node_coordinates = self.graph.nodes[node as usize];
+            // rounded up, so the great-circle potential stays a lower bound
+            (node, distance(point.lon, point.lat, node_coordinates.lon, node_coordinates.lat).ceil() as u32)
+        };
+        nearest_neighbors.iter()
+            .filter(|node| self.is_line_of_sight_free(avoid_areas, point, &self.graph.nodes[**node as usize]))
+            .map(|node| connection(*node))
+            .collect()
+    }
+
+    /// Keeps the connections of the stops to the largest component, which all stops are connected to, so every leg of the route is feasible.
+    /// If the nearest nodes of the stops have no common component, more nodes around the stops are tried.
+    /// Fails with the reason, if a stop can not be connected to any node or the stops lie in water bodies, which are not connected to each other
+    fn connect_to_common_component(&self, avoid_areas: Option<&AvoidAreas>, stops: &Vec<Node>, connections: Vec<Vec<(u32, u32)>>) -> Result<Vec<Vec<(u32, u32)>>, String> {
+        let has_component = |stop_connections: &Vec<(u32, u32)>, component: u32| stop_connections.iter().any(|(node, _)| self.components.component(*node) == component);
+        let (connections, component) = match self.common_component(&connections) {
+            Some(component) => (connections, component),
+            None => {
+                let connections: Vec<Vec<(u32, u32)>> = stops.iter().map(|stop| self.connect_point(avoid_areas, stop, COMPONENT_CONNECTIONS)).collect();
+                match self.common_component(&connections) {
+                    Some(component) => (connections, component),
+                    None => {
+                        if let Some(i) = connections.iter().position(|stop_connections| stop_connections.is_empty()) {
+                            return Err(format!("Unreachable: the {} ({}, {}) can not be connected to any of the {} nearest nodes without crossing land or an avoid area",
+                                               stop_name(i, stops.len()), stops[i].lat, stops[i].lon, COMPONENT_CONNECTIONS));
+                        }
+                        // the first stop, which is not connected to the largest component around the start
+                        let start_component = connections[0].iter().map(|(node, _)| self.components.component(*node)).max_by_key(|component| self.components.size(*component)).unwrap();
+                        let i = (1..stops.len()).find(|i| !has_component(&connections[*i], start_component)).unwrap();
+                        let stop_component = self.components.component(connections[i][0].0);
+                        return Err(format!("Unreachable: the {} ({}, {}) lies in a water body with {} nodes, which is not connected to the water body with {} nodes of the start ({}, {})",
+                                           stop_name(i, stops.len()), stops[i].lat, stops[i].lon, self.components.size(stop_component), self.components.size(start_component), stops[0].lat, stops[0].lon));
+                    }
+                }
+            }
+        };
+        Ok(connections.into_iter()
+            .map(|stop_connections| stop_connections.into_iter().filter(|(node, _)| self.components.component(*node) == component).collect())
+            .collect())
+    }
+
+    /// Nearest node of every point, which is in the largest component all points are connected to without crossing land.
+    /// Points without a node of a common component use their nearest connected node, points without any connection have no node
+    fn connect_to_nodes_of_common_component(&self, points: &Vec<Node>) -> Vec<Option<u32>> {
+        let connections: Vec<Vec<(u32, u32)>> = points.iter().map(|point| self.connect_point(None, point, COMPONENT_CONNECTIONS)).collect();
+        let component = self.common_component(&connections);
+        connections.iter()
+            .map(|point_connections| point_connections.iter()
+                .find(|(node, _)| Some(self.components.component(*node)) == component)
+                .or_else(|| point_connections.first())
+                .map(|(node, _)| *node))
+            .collect()
+    }
+
+    /// Largest component, which all stops are connected to, or None if the stops have no common component
+    fn common_component(&self, connections: &Vec<Vec<(u32, u32)>>) -> Option<u32> {
+        connections.first()?.iter()
+            .map(|(node, _)| self.components.component(*node))
+            .filter(|component| connections.iter().all(|stop_connections| stop_connections.iter().any(|(node, _)| self.components.component(*node) == *component)))
+            .max_by_key(|component| self.components.size(*component))
+    }
+
+    /// Calculates the route between the connection nodes of two stops, which minimizes the costs of the cost function or the distance.
+    /// The distance includes the connections to the stops
+    fn find_route(&self, search_state: &mut SearchState, algorithm: RoutingAlgorithm, cost_function: Option<&dyn CostFunction>, departure: Option<Departure>, (start, start_connections): (&Node, &Vec<(u32, u32)>), (end, end_connections): (&Node, &Vec<(u32, u32)>)) -> Option<(Vec<u32>, u32)> {
+        if let Some(cost_function) = cost_function {
+            return self.find_route_with_costs(search_state, algorithm, cost_function, departure, (start, start_connections), (end, end_connections));
+        }
+        let nodes = &self.graph.nodes;
+        let dijkstra = &mut search_state.dijkstra;
+        let bidirectional_dijkstra = &mut search_state.bidirectional_dijkstra;
+        // every algorithm searches from all connections of the start to all connections of the end at once
+        let route_and_distance = match algorithm {
+            RoutingAlgorithm::Dijkstra => dijkstra.find_route_between_nodes(start_connections, end_connections, |_| 0),
+            RoutingAlgorithm::AStar => dijkstra.find_route_between_nodes(start_connections, end_connections, great_circle_potential_to_point(nodes, *end)),
+            RoutingAlgorithm::BidirectionalDijkstra => bidirectional_dijkstra.find_route_between_nodes(start_connections, end_connections),
+            // the supported algorithms only contain ALT with landmarks and contraction hierarchies with a contracted graph
+            RoutingAlgorithm::Alt => {
+                let landmarks = self.landmarks.as_ref().expect("ALT without landmarks");
+                dijkstra.find_route_between_nodes(start_connections, end_connections, landmarks.potential(end_connections, great_circle_potential_to_point(nodes, *end)))
+            }
+            RoutingAlgorithm::ContractionHierarchies => {
+                let contraction_hierarchies = search_state.contraction_hierarchies.as_mut().expect("Contraction hierarchies without contracted graph");
+                contraction_hierarchies.find_route_between_nodes(start_connections, end_connections)
+            }
+        };
+        // the distances of the passages in the adjacency array include their penalty
+        route_and_distance.map(|(route, distance)| {
+            let penalty = self.passages.route_penalty(&route);
+            (route, distance - penalty)
+        })
+    }
+
+    /// Calculates the route between the connection nodes of two stops, which minimizes the costs of the cost function.
+    /// With a departure, the costs are travel times, which are changed by the weather at the arrival time at every node.
+    /// The algorithm has to be Dijkstra or A*. Returns the route and its distance
+    fn find_route_with_costs(&self, search_state: &mut SearchState, algorithm: RoutingAlgorithm, cost_function: &dyn CostFunction, departure: Option<Departure>, (start, start_connections): (&Node, &Vec<(u32, u32)>), (end, end_connections): (&Node, &Vec<(u32, u32)>)) -> Option<(Vec<u32>, u32)> {
+        let nodes = &self.graph.nodes;
+        // blocked connections have the cost u32::MAX
+        let connection_costs = |point: &Node, connections: &Vec<(u32, u32)>| -> Vec<(u32, u32)> {
+            connections.iter()
+                .map(|(node, distance)| (*node, cost_function.edge_cost(point, &nodes[*node as usize], *distance)))
+                .filter(|(_, cost)| *cost != u32::MAX)
+                .collect()
+        };
+        let great_circle_potential = great_circle_potential_to_point(nodes, *end);
+        let potential = |node: u32| if algorithm == RoutingAlgorithm::Dijkstra { 0 } else { cost_function.potential(great_circle_potential(node)) };
+        let dijkstra = &mut search_state.dijkstra;
+        let (start_costs, end_costs) = (connection_costs(start, start_connections), connection_costs(end, end_connections));
+        let (route, _) = match (self.weather.as_ref(), departure) {
+            (Some(weather), Some(departure)) => {
+                // the weather changes the sailing time of a segment, which is charged with the waiting cost of the cost function.
+                // Returns the cost and the seconds of the segment, which is reached the given seconds after the departure
+                let weather_cost = |from: &Node, to: &Node, distance: u32, cost: u32, time: u32| -> (u32, u32) {
+                    let still_water_seconds = distance as f64 / departure.meters_per_second;
+                    let speed_factor = weather.speed_factor(from, to, departure.meters_per_second, departure.time + time as u64);
+                    let seconds = still_water_seconds / speed_factor;
+                    // the other costs of the segment like waiting times or penalties are not changed by the weather
+                    let cost = (cost as f64 + cost_function.waiting_cost(seconds - still_water_seconds)).max(0.0).ceil() as u32;
+                    (cost, seconds.ceil() as u32)
+                };
+                let start_costs: Vec<(u32, u32, u32)> = start_costs.iter()
+                    .map(|(node, cost)| {
+                        let distance = connection_distance(start_connections, *node);
+                        let (cost, seconds) = weather_cost(start, &nodes[*node as usize], distance, *cost, 0);
+                        (*node, cost, seconds)
+                    })
+                    .collect();
+                // the connection to the end is sailed from the node at the arrival time at the node
+                let end_cost = |node: u32, cost: u32, time: u32| weather_cost(&nodes[node as usize], end, connection_distance(end_connections, node), cost, time).0;
+                let edge_costs = edge_costs(nodes, cost_function);
+                let time_dependent_edge_costs = |from_node: u32, to_node: u32, distance: u32, time: u32| {
+                    let cost = edge_costs(from_node, to_node, distance);
+                    if cost == u32::MAX {
+                        return (cost, 0);
+                    }
+                    weather_cost(&nodes[from_node as usize], &nodes[to_node as usize], distance, cost, time)
+                };
+                // the ship may be faster than in still water, so the cost of a meter is reduced by the cost of the time,
+                // which the largest speed factor saves
+                let maximum_speed_factor = weather.maximum_speed_factor(departure.meters_per_second);
+                let minimum_cost_per_meter = (cost_function.minimum_cost_per_meter()
+                    - cost_function.waiting_cost(1.0) / departure.meters_per_second * (1.0 - 1.0 / maximum_speed_factor)).max(0.0);
+                let time_dependent_potential = |node: u32| if algorithm == RoutingAlgorithm::Dijkstra { 0 } else { (great_circle_potential(node) as f64 * minimum_cost_per_meter) as u32 };
+                dijkstra.find_route_between_nodes_time_dependent(&start_costs, &end_costs, time_dependent_potential, time_dependent_edge_costs, end_cost)?
+            }
+            _ => dijkstra.find_route_between_nodes_with_edge_costs(&start_costs, &end_costs, potential, edge_costs(nodes, cost_function))?
+        };
+        let destination_node = route[route.len() - 1];
+        let distance = connection_distance(start_connections, route[0]) + self.adjacency_array.route_distance(&route) - self.passages.route_penalty(&route) + connection_distance(end_connections, destination_node);
+        Some((route, distance))
+    }
+
+    /// Algorithm, which calculates the route instead of the requested algorithm. Only Dijkstra and A* support the costs,
+    /// so the other algorithms are replaced by A*. ALT needs landmarks and contraction hierarchies need the contracted graph.
+    /// The routes report the algorithm, which calculated them
+    fn supported_algorithm(&self, algorithm: RoutingAlgorithm, has_costs: bool) -> RoutingAlgorithm {
+        match algorithm {
+            RoutingAlgorithm::Dijkstra | RoutingAlgorithm::AStar => algorithm,
+            _ if has_costs => RoutingAlgorithm::AStar,
+            RoutingAlgorithm::Alt if self.landmarks.is_none() => RoutingAlgorithm::AStar,
+            RoutingAlgorithm::ContractionHierarchies if self.contracted_graph.is_none() => RoutingAlgorithm::BidirectionalDijkstra,
+            _ => algorithm
+        }
     }
+
+    /// Finds alternatives to the optimal route between the connection nodes, which is used. The alternatives use the same connections to the start and the end
+    fn find_alternative_routes(&self, search_state: &mut SearchState, algorithm: RoutingAlgorithm, (cost_model, cost_function): (CostModel, Option<&dyn CostFunction>), (avoid_areas, minimum_coast_distance): (Option<&AvoidAreas>, u32), optimal_route: &Vec<u32>, number_of_alternatives: usize, (start, start_connections): (&Node, &Vec<(u32, u32)>), (end, end_connections): (&Node, &Vec<(u32, u32)>)) -> Vec<ShipRoute> {
+        let nodes = &self.graph.nodes;
+        let destination_node = optimal_route[optimal_route.len() - 1];
+        let start_distance = connection_distance(start_connections, optimal_route[0]);
+        let end_distance = connection_distance(end_connections, destination_node);
+        // the penalties change the edge costs after every search, so the alternatives are searched with Dijkstra, if it was requested, and A* otherwise
+        let algorithm = if algorithm == RoutingAlgorithm::Dijkstra { RoutingAlgorithm::Dijkstra } else { RoutingAlgorithm::AStar };
+        let great_circle_potential = great_circle_potential(nodes, destination_node);
+        let great_circle_potential = |node: u32| if algorithm == RoutingAlgorithm::AStar { great_circle_potential(node) } else { 0 };
+        let limits = Config::global().alternative_route_limits();
+        // the costs, which only rescale the distance, are not needed for the search
+        let search_cost_function = cost_function.filter(|cost_function| !cost_function.is_proportional_to_distance());
+        let alternative_routes = match search_cost_function {
+            Some(cost_function) => find_alternative_routes(&mut search_state.dijkstra, &self.adjacency_array, optimal_route, number_of_alternatives, &limits,
+                                                           |node| cost_function.potential(great_circle_potential(node)), edge_costs(nodes, cost_function)),
+            None => find_alternative_routes(&mut search_state.dijkstra, &self.adjacency_array, optimal_route, number_of_alternatives, &limits,
+                                            great_circle_potential, |_, _, distance| distance)
+        };
+        alternative_routes.into_iter().map(|(route, distance)| {
+            let mut nodes_route = vec![*start];
+            nodes_route.extend(self.passages.route_nodes(&route, nodes));
+            nodes_route.push(*end);
+            let raw_distance = start_distance + distance - self.passages.route_penalty(&route) + end_distance;
+            let (nodes_route, distance) = self.smooth_route(avoid_areas, minimum_coast_distance, nodes_route, raw_distance);
+            let cost = self.route_cost(cost_function, &nodes_route, std::slice::from_ref(&route), distance);
+            let coast_distance = route.iter().map(|node| self.graph.coast_distances[*node as usize]).min();
+            ShipRoute::new(nodes_route, distance, raw_distance, vec![distance], Vec::new())
+                .with_cost(cost_model, cost)
+                .with_algorithm(algorithm, search_cost_function.is_none())
+                .with_passages(self.passages.route_passages(&route))
+                .with_coast_distance(coast_distance)
+        }).collect()
+    }
+
+    /// Cost of a route, which follows the graph routes between its stops. The cost of its segments along great circles is increased by the
+    /// extra costs of the edges of the graph routes, like the penalties and waiting times of the passages and the clearance penalty.
+    /// Without a cost function, the cost is the distance plus the penalties of the passages
+    fn route_cost(&self, cost_function: Option<&dyn CostFunction>, route: &Vec<Node>, graph_routes: &[Vec<u32>], distance: u32) -> u32 {
+        let cost_function = match cost_function {
+            Some(cost_function) => cost_function,
+            None => return graph_routes.iter().map(|graph_route| self.passages.route_penalty(graph_route)).fold(distance, u32::saturating_add)
+        };
+        let nodes = &self.graph.nodes;
+        let extra_costs = graph_routes.iter().flat_map(|graph_route| graph_route.windows(2)).map(|edge| {
+            let (from, to) = (&nodes[edge[0] as usize], &nodes[edge[1] as usize]);
+            let distance = self.adjacency_array.get_edge_distance(edge[0], edge[1]).unwrap_or(0);
+            // the segments of the route already cost the length of the edge, but the edges of the passages are longer by their penalty
+            let length = self.passages.edge_length(edge[0], edge[1], distance);
+            cost_function.graph_edge_cost(edge[0], edge[1], from, to, distance).saturating_sub(cost_function.edge_cost(from, to, length))
+        });
+        extra_costs.fold(route_cost(cost_function, route), u32::saturating_add)
+    }
+
+    /// Returns the route and its distance after smoothing, if the routes are smoothed.
+    /// The shortcuts keep the minimum distance to the coast
+    fn smooth_route(&self, avoid_areas: Option<&AvoidAreas>, minimum_coast_distance: u32, route: Vec<Node>, distance: u32) -> (Vec<Node>, u32) {
+        if !Config::global().smooth_routes() {
+            return (route, distance);
+        }
+        let smoothed_route = smooth_route(&route, |from, to| self.is_shortcut_free(avoid_areas, minimum_coast_distance, from, to));
+        if smoothed_route.len() == route.len() {
+            return (route, distance);
+        }
+        let smoothed_distance = route_distance(&smoothed_route);
+        (smoothed_route, smoothed_distance)
+    }
+
+    /// Checks if the great-circle segment between both points crosses neither land nor an avoid area
+    fn is_line_of_sight_free(&self, avoid_areas: Option<&AvoidAreas>, from: &Node, to: &Node) -> bool {
+        !self.segment_index().crosses_polygons(from, to) && avoid_areas.map_or(true, |avoid_areas| !avoid_areas.is_segment_blocked(from, to))
+    }
+
+    /// Checks if the great-circle segment between both points can replace a part of a route, because it is free and
+    /// keeps the minimum distance to the coast
+    fn is_shortcut_free(&self, avoid_areas: Option<&AvoidAreas>, minimum_coast_distance: u32, from: &Node, to: &Node) -> bool {
+        self.is_line_of_sight_free(avoid_areas, from, to)
+            && (minimum_coast_distance == 0 || self.segment_index().great_circle_distance_to_polygons(from, to, minimum_coast_distance as f64) >= minimum_coast_distance as f64)
+    }
+
+    /// Index over the segments of the polygons, which finds every crossing of land by a segment
+    fn segment_index(&self) -> SegmentIndex<'_> {
+        SegmentIndex::with_cells(self.polygon_test.polygons(), &self.segment_cells)
+    }
+
+    /// Costs between all stops of a route, or the distances if there is no cost function. Unreachable stops have the cost u32::MAX
+    fn calculate_costs_between_stops(&self, search_state: &mut SearchState, cost_function: Option<&dyn CostFunction>, stops: &Vec<u32>) -> Vec<Vec<u32>> {
+        let dijkstra = &mut search_state.dijkstra;
+        if let Some(cost_function) = cost_function {
+            // the search with edge costs can not be continued, so every pair of stops is searched
+            let nodes = &self.graph.nodes;
+            return stops.iter().map(|source| {
+                stops.iter().map(|target| {
+                    dijkstra.find_route_with_edge_costs(*source, *target, |_| 0, edge_costs(nodes, cost_function)).map_or(u32::MAX, |(_, cost)| cost)
+                }).collect()
+            }).collect();
+        }
+        stops.iter().map(|source| {
+            dijkstra.change_source_node(*source);
+            stops.iter().map(|target| dijkstra.find_route(*target).map_or(u32::MAX, |(_, distance)| distance)).collect()
+        }).collect()
+    }
+}
+
+/// Name of a stop in the messages, e.g. start or waypoint 0
+fn stop_name(i: usize, number_stops: usize) -> String {
+    if i == 0 { String::from("start") } else if i == number_stops - 1 { String::from("end") } else { format!("waypoint {}", i - 1) }
+}
+
+/// Length of the connection of a stop to the node, or 0 if the node is no connection of the stop
+fn connection_distance(connections: &Vec<(u32, u32)>, node: u32) -> u32 {
+    connections.iter().find(|(connection_node, _)| *connection_node == node).map_or(0, |(_, distance)| *distance)
+}
+
+/// Edge costs of the cost function for the nodes of the graph
+fn edge_costs<'a>(nodes: &'a Vec<Node>, cost_function: &'a dyn CostFunction) -> impl Fn(u32, u32, u32) -> u32 + 'a {
+    move |from_node: u32, to_node: u32, distance: u32| cost_function.graph_edge_cost(from_node, to_node, &nodes[from_node as usize], &nodes[to_node as usize], distance)
 }
@@ -1,36 +1,49 @@
 use crate::grid_graph::{Node};
 use serde::{Deserialize, Serialize};
 use crate::persistence::routing_repo::RoutingRepo;
+use std::str::FromStr;
+use std::collections::HashMap;
 
 pub(crate) struct InMemoryRoutingRepo {
-    routes: Vec<ShipRoute>,
+    // the jobs finish in any order, so the routes are stored by job id
+    routes: HashMap<usize, ShipRoute>,
+    errors: HashMap<usize, String>,
+    next_job_id: u32,
 }
 
 impl RoutingRepo for InMemoryRoutingRepo {
     fn new() -> InMemoryRoutingRepo {
         InMemoryRoutingRepo {
-            routes: Vec::new()
+            routes: HashMap::new(),
+            errors: HashMap::new(),
+            next_job_id: 0
         }
     }
 
-    fn add_route(&mut self, route: ShipRoute) -> usize {
-        self.routes.push(route);
-        self.routes.len()
+    fn add_route(&mut self, id: usize, route: ShipRoute) {
+        self.routes.insert(id, route);
     }
 
     fn get_route(&self, id: usize) -> Option<ShipRoute> {
-        if self.routes.get(id).is_some() {
-            return Some(self.routes[id].clone());
-        }
-        return None;
+        self.routes.get(&id).cloned()
+    }
+
+    fn add_error(&mut self, id: usize, error: String) {
+        self.errors.insert(id, error);
+    }
+
+    fn get_error(&self, id: usize) -> Option<String> {
+        self.errors.get(&id).cloned()
     }
 
     fn has_route(&self, id: usize) -> bool {
-        self.routes.len() <= id
+        self.routes.contains_key(&id)
     }
 
-    fn get_job_id(&self) -> u32 {
-        self.routes.len() as u32
+    fn get_job_id(&mut self) -> u32 {
+        let job_id = self.next_job_id;
+        self.next_job_id += 1;
+        job_id
     }
 }
 
@@ -38,12 +51,139 @@ impl RoutingRepo for InMemoryRoutingRepo {
 #[serde(rename_all = "camelCase")]
 pub struct ShipRoute {
     distance: u32,
+    // distance along the edges of the graph, before the route was smoothed
+    raw_distance: u32,
     nodes: Vec<Node>,
+    // distance of every leg between two consecutive stops of the route
+    legs: Vec<u32>,
+    // indices of the requested waypoints in the order they are visited
+    waypoint_order: Vec<usize>,
+    // meaningfully different routes between the same start and end, if requested
+    #[serde(default)]
+    alternatives: Vec<ShipRoute>,
+    // stops of the request, which were on land and have been moved to the water
+    #[serde(default)]
+    endpoint_adjustments: Vec<EndpointAdjustment>,
+    // the cost model, which was minimized, and the cost of the route in its unit including the penalties and waiting times
+    // of the passages and the clearance penalty
+    #[serde(default)]
+    cost_model: CostModel,
+    #[serde(default)]
+    cost: u32,
+    // true if the cost model only rescales the distance, so the route is the shortest route and only its cost differs
+    #[serde(default)]
+    cost_proportional_to_distance: bool,
+    // the algorithm, which calculated the route. The costs, which are not proportional to the distance, are only supported by Dijkstra and A*
+    #[serde(default)]
+    algorithm: RoutingAlgorithm,
+    // times of the route for the ship
+    #[serde(default)]
+    voyage: Option<Voyage>,
+    // names of the passages the route uses, in the order they are passed
+    #[serde(default)]
+    passages: Vec<String>,
+    // smallest distance of the graph nodes of the route to the coast in meters
+    #[serde(default)]
+    coast_distance: Option<u32>,
 }
 
 impl ShipRoute {
-    pub fn new(nodes: Vec<Node>, distance: u32) -> ShipRoute {
-        ShipRoute { nodes, distance }
+    pub fn new(nodes: Vec<Node>, distance: u32, raw_distance: u32, legs: Vec<u32>, waypoint_order: Vec<usize>) -> ShipRoute {
+        ShipRoute { nodes, distance, raw_distance, legs, waypoint_order, alternatives: Vec::new(), endpoint_adjustments: Vec::new(), cost_model: CostModel::Distance, cost: distance, cost_proportional_to_distance: true, algorithm: RoutingAlgorithm::default(), voyage: None, passages: Vec::new(), coast_distance: None }
+    }
+
+    pub fn with_voyage(mut self, voyage: Voyage) -> ShipRoute {
+        self.voyage = Some(voyage);
+        self
+    }
+
+    pub fn with_cost(mut self, cost_model: CostModel, cost: u32) -> ShipRoute {
+        self.cost_model = cost_model;
+        self.cost = cost;
+        self
+    }
+
+    pub fn with_algorithm(mut self, algorithm: RoutingAlgorithm, cost_proportional_to_distance: bool) -> ShipRoute {
+        self.algorithm = algorithm;
+        self.cost_proportional_to_distance = cost_proportional_to_distance;
+        self
+    }
+
+    pub fn with_passages(mut self, passages: Vec<String>) -> ShipRoute {
+        self.passages = passages;
+        self
+    }
+
+    pub fn with_coast_distance(mut self, coast_distance: Option<u32>) -> ShipRoute {
+        self.coast_distance = coast_distance;
+        self
+    }
+
+    pub fn with_alternatives(mut self, alternatives: Vec<ShipRoute>) -> ShipRoute {
+        self.alternatives = alternatives;
+        self
+    }
+
+    pub fn with_endpoint_adjustments(mut self, endpoint_adjustments: Vec<EndpointAdjustment>) -> ShipRoute {
+        self.endpoint_adjustments = endpoint_adjustments;
+        self
+    }
+}
+
+/// Times of a route sailed by a ship. The times are unix timestamps in seconds, or seconds since the departure if no departure time was requested
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Voyage {
+    ship_profile: String,
+    // duration of the voyage in seconds
+    duration: u64,
+    departure_time: u64,
+    eta: u64,
+    // time at every node of the route
+    timestamps: Vec<u64>,
+    // position of the ship at fixed time intervals, if requested
+    #[serde(default)]
+    positions: Vec<TimedPosition>,
+}
+
+impl Voyage {
+    pub fn new(ship_profile: String, duration: u64, departure_time: u64, eta: u64, timestamps: Vec<u64>, positions: Vec<TimedPosition>) -> Voyage {
+        Voyage { ship_profile, duration, departure_time, eta, timestamps, positions }
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TimedPosition {
+    time: u64,
+    position: Node,
+}
+
+impl TimedPosition {
+    pub fn new(time: u64, position: Node) -> TimedPosition {
+        TimedPosition { time, position }
+    }
+}
+
+/// A stop of the route request, which was moved from land to the water
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointAdjustment {
+    // index of the stop: 0 is the start, 1 to n are the waypoints and n + 1 is the end
+    stop: usize,
+    requested: Node,
+    relocated: Node,
+    // distance between the requested and the relocated position in meters
+    distance: u32,
+}
+
+impl EndpointAdjustment {
+    pub fn new(stop: usize, requested: Node, relocated: Node, distance: u32) -> EndpointAdjustment {
+        EndpointAdjustment { stop, requested, relocated, distance }
     }
 }
 
@@ -61,6 +201,47 @@ pub(crate) struct Status {
 pub struct RouteRequest {
     pub(crate) start: Node,
     pub(crate) end: Node,
+    #[serde(default)]
+    pub(crate) algorithm: RoutingAlgorithm,
+    // via-points between start and end
+    #[serde(default)]
+    pub(crate) waypoints: Vec<Node>,
+    // visit the waypoints in the order which minimizes the total distance instead of the given order
+    #[serde(default)]
+    pub(crate) optimize_waypoint_order: bool,
+    // number of alternative routes, at most 5, which are calculated in addition to the optimal route. Requests with waypoints and alternatives are rejected
+    #[serde(default)]
+    pub(crate) alternatives: u32,
+    // what happens to a start, end or waypoint on land
+    #[serde(default)]
+    pub(crate) land_endpoints: LandEndpointHandling,
+    // the cost, which is minimized
+    #[serde(default)]
+    pub(crate) cost_model: CostModel,
+    // weights of the weighted cost model
+    #[serde(default)]
+    pub(crate) cost_weights: CostWeights,
+    // name of the ship profile, the default profile is used if no profile is named
+    #[serde(default)]
+    pub(crate) ship_profile: Option<String>,
+    // unix timestamp in seconds
+    #[serde(default)]
+    pub(crate) departure_time: Option<u64>,
+    // interval in seconds, in which the position of the ship is included in the result. At least 60 seconds
+    #[serde(default)]
+    pub(crate) position_interval: Option<u32>,
+    // areas the route must not enter, only for this request
+    #[serde(default)]
+    pub(crate) avoid_areas: Vec<GeoJsonPolygon>,
+    // options of the configured passages by their name, only for this request
+    #[serde(default)]
+    pub(crate) passages: HashMap<String, PassageOptions>,
+    // minimum distance to the coast in meters, which replaces the minimum distance of the ship profile
+    #[serde(default)]
+    pub(crate) min_coast_distance: Option<f64>,
+    // what happens to the edges closer to the coast than the minimum distance
+    #[serde(default)]
+    pub(crate) clearance_handling: ClearanceHandling,
 }
 
 impl RouteRequest {
@@ -70,4 +251,230 @@ impl RouteRequest {
     pub fn end(&self) -> Node {
         self.end
     }
+    pub fn algorithm(&self) -> RoutingAlgorithm {
+        self.algorithm
+    }
+    pub fn waypoints(&self) -> &Vec<Node> {
+        &self.waypoints
+    }
+    pub fn optimize_waypoint_order(&self) -> bool {
+        self.optimize_waypoint_order
+    }
+    pub fn alternatives(&self) -> u32 {
+        self.alternatives
+    }
+    pub fn land_endpoints(&self) -> LandEndpointHandling {
+        self.land_endpoints
+    }
+    pub fn cost_model(&self) -> CostModel {
+        self.cost_model
+    }
+    pub fn cost_weights(&self) -> &CostWeights {
+        &self.cost_weights
+    }
+    pub fn ship_profile(&self) -> &Option<String> {
+        &self.ship_profile
+    }
+    pub fn departure_time(&self) -> Option<u64> {
+        self.departure_time
+    }
+    pub fn position_interval(&self) -> Option<u32> {
+        self.position_interval
+    }
+    pub fn avoid_areas(&self) -> &Vec<GeoJsonPolygon> {
+        &self.avoid_areas
+    }
+    pub fn passages(&self) -> &HashMap<String, PassageOptions> {
+        &self.passages
+    }
+    pub fn min_coast_distance(&self) -> Option<f64> {
+        self.min_coast_distance
+    }
+    pub fn clearance_handling(&self) -> ClearanceHandling {
+        self.clearance_handling
+    }
+}
+
+/// Handling of the edges of the graph, which are closer to the coast than the minimum distance of a route
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ClearanceHandling {
+    /// The edges are used with a higher cost, so the routes can still reach stops near the coast
+    #[default]
+    Penalize,
+    /// The edges are not used
+    Exclude,
+}
+
+impl FromStr for ClearanceHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "penalize" => Ok(ClearanceHandling::Penalize),
+            "exclude" => Ok(ClearanceHandling::Exclude),
+            _ => Err(format!("Unknown clearance handling: {}", s))
+        }
+    }
+}
+
+/// Options of a configured passage for a single route request. Missing values are taken from the passages file
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PassageOptions {
+    #[serde(default = "default_passage_enabled")]
+    pub enabled: bool,
+    // waiting time before the passage in seconds
+    #[serde(default)]
+    pub waiting_time: Option<f64>,
+    // extra distance in meters, which is added to the length of the passage
+    #[serde(default)]
+    pub penalty: Option<f64>,
+}
+
+fn default_passage_enabled() -> bool {
+    true
+}
+
+/// Polygon geometry of GeoJSON. The first ring is the outline of the polygon, the other rings are holes
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct GeoJsonPolygon {
+    #[serde(rename = "type")]
+    pub geometry_type: String,
+    // rings of [lon, lat] positions
+    pub coordinates: Vec<Vec<[f64; 2]>>,
+}
+
+/// Cost minimized by the route calculation
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum CostModel {
+    /// Meters
+    #[default]
+    Distance,
+    /// Seconds at the service speed of the ship
+    Time,
+    /// Kilograms of fuel burned at the service speed of the ship
+    Fuel,
+    /// Weighted sum of the distance, time and fuel costs
+    Weighted,
+}
+
+impl FromStr for CostModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "distance" => Ok(CostModel::Distance),
+            "time" => Ok(CostModel::Time),
+            "fuel" => Ok(CostModel::Fuel),
+            "weighted" => Ok(CostModel::Weighted),
+            _ => Err(format!("Unknown cost model: {}", s))
+        }
+    }
+}
+
+/// Weights of the costs in the weighted cost model. Negative weights are ignored
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CostWeights {
+    #[serde(default)]
+    pub distance: f64,
+    #[serde(default)]
+    pub time: f64,
+    #[serde(default)]
+    pub fuel: f64,
+}
+
+impl Default for CostWeights {
+    fn default() -> Self {
+        CostWeights { distance: 1.0, time: 0.0, fuel: 0.0 }
+    }
+}
+
+/// Handling of route requests with a start, end or waypoint on land
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum LandEndpointHandling {
+    /// The route request fails
+    Reject,
+    /// The point is moved to the nearest node of the graph in the water body of the other stops, which is reported in the endpoint adjustments.
+    /// The default, so the route requests with a point on land, which were answered before, still succeed
+    #[default]
+    Relocate,
+}
+
+impl FromStr for LandEndpointHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(LandEndpointHandling::Reject),
+            "relocate" => Ok(LandEndpointHandling::Relocate),
+            _ => Err(format!("Unknown land endpoint handling: {}", s))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DistanceMatrixRequest {
+    pub(crate) sources: Vec<Node>,
+    pub(crate) targets: Vec<Node>,
+    // include the route between every source and target in the result
+    #[serde(default)]
+    pub(crate) include_geometry: bool,
+}
+
+impl DistanceMatrixRequest {
+    pub fn sources(&self) -> &Vec<Node> {
+        &self.sources
+    }
+    pub fn targets(&self) -> &Vec<Node> {
+        &self.targets
+    }
+    pub fn include_geometry(&self) -> bool {
+        self.include_geometry
+    }
+}
+
+/// Distances between all sources (rows) and targets (columns). Unreachable targets have no distance.
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DistanceMatrix {
+    distances: Vec<Vec<Option<u32>>>,
+    routes: Option<Vec<Vec<Option<Vec<Node>>>>>,
+}
+
+impl DistanceMatrix {
+    pub fn new(distances: Vec<Vec<Option<u32>>>, routes: Option<Vec<Vec<Option<Vec<Node>>>>>) -> DistanceMatrix {
+        DistanceMatrix { distances, routes }
+    }
+}
+
+/// Search algorithm used to calculate a route. All algorithms return the same distance
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum RoutingAlgorithm {
+    #[default]
+    Dijkstra,
+    AStar,
+    BidirectionalDijkstra,
+    ContractionHierarchies,
+    Alt,
+}
+
+impl FromStr for RoutingAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dijkstra" => Ok(RoutingAlgorithm::Dijkstra),
+            "aStar" => Ok(RoutingAlgorithm::AStar),
+            "bidirectionalDijkstra" => Ok(RoutingAlgorithm::BidirectionalDijkstra),
+            "contractionHierarchies" => Ok(RoutingAlgorithm::ContractionHierarchies),
+            "alt" => Ok(RoutingAlgorithm::Alt),
+            _ => Err(format!("Unknown routing algorithm: {}", s))
+        }
+    }
 }
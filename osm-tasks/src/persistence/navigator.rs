@@ -1,10 +1,19 @@
-use crate::persistence::in_memory_routing_repo::{ShipRoute, RouteRequest};
+use std::sync::Arc;
+use crate::grid_graph::Node;
+use crate::region::Region;
+use crate::persistence::in_memory_routing_repo::{ShipRoute, RouteRequest, DistanceMatrixRequest, DistanceMatrix};
 
+/// Calculates routes on the graph. All methods can be called concurrently
 pub trait Navigator: Send + Sync {
     fn new() -> Self
     where
         Self: Sized;
-    fn build_graph(&mut self);
-    fn calculate_route(&mut self, route_request: RouteRequest) -> Option<ShipRoute>;
+    /// Builds or loads the graph, which is restricted to the region or covers the whole world without a region
+    fn build_graph(&self, region: Option<Arc<Region>>);
+    /// Returns the route or the reason why no route could be calculated
+    fn calculate_route(&self, route_request: RouteRequest) -> Result<ShipRoute, String>;
+    fn calculate_distance_matrix(&self, distance_matrix_request: DistanceMatrixRequest) -> Option<DistanceMatrix>;
+    /// Returns the nodes of the graph which can be reached from the start within the maximum distance
+    fn calculate_reachable_nodes(&self, start: Node, maximum_distance: u32) -> Option<Vec<Node>>;
     fn get_number_nodes(&self) -> u32;
 }
@@ -4,8 +4,12 @@ pub trait RoutingRepo: Send + Sync {
     fn new() -> Self
     where
         Self: Sized;
-    fn add_route(&mut self, route: ShipRoute) -> usize;
+    fn add_route(&mut self, id: usize, route: ShipRoute);
     fn get_route(&self, id: usize) -> Option<ShipRoute>;
+    /// Stores the reason why the route of the job could not be calculated
+    fn add_error(&mut self, id: usize, error: String);
+    fn get_error(&self, id: usize) -> Option<String>;
     fn has_route(&self, id: usize) -> bool;
-    fn get_job_id(&self) -> u32;
+    /// Reserves the id for a new job. The route of the job is added with this id once it is calculated
+    fn get_job_id(&mut self) -> u32;
 }
@@ -23,7 +23,7 @@ Uses four stages to check if a point is inside a polygon:
 impl PointInPolygonTest {
     pub fn new(polygons: Vec<Vec<(f64, f64)>>) -> PointInPolygonTest {
         // println!("Polygon test instance with {} polygons", polygons.len());
-        let bounding_boxes: Vec<(f64, f64, f64, f64)> = polygons.iter().map(|polygon| PointInPolygonTest::calculate_bounding_box(polygon)).collect();
+        let bounding_boxes: Vec<(f64, f64, f64, f64)> = polygons.iter().map(PointInPolygonTest::calculate_bounding_box).collect();
         let quadtree = PointInPolygonTest::build_quadtree(&bounding_boxes);
         let mut polygon_test = PointInPolygonTest { bounding_boxes, polygons, quadtree, grid: None};
         polygon_test.build_grid();
@@ -37,7 +37,8 @@ impl PointInPolygonTest {
             // Ignore north-south edges
             return false;
         } else if (v1_lat - v2_lat).abs() <= EPSILON {
-            return f64::min(*v1_lon, *v2_lon) <= *point_lon && *point_lon <= f64::max(*v1_lon, *v2_lon);
+            // like the vertices of the other edges, only the eastern vertex counts, so a point below the vertex of two edges hits only one
+            return f64::min(*v1_lon, *v2_lon) < *point_lon && *point_lon <= f64::max(*v1_lon, *v2_lon);
         } else if *point_lon < f64::min(*v1_lon, *v2_lon) || f64::max(*v1_lon, *v2_lon) < *point_lon {
             // Can not intersect with the edge
             return false;
@@ -86,8 +87,7 @@ impl PointInPolygonTest {
 
     fn build_quadtree(bounding_boxes: &Vec<(f64, f64, f64, f64)>) -> Quadtree<i16, i32> {
         let mut quadtree = Quadtree::<i16, i32>::new(9);
-        for i in 0..bounding_boxes.len() {
-            let bounding_box = bounding_boxes[i];
+        for (i, bounding_box) in bounding_boxes.iter().enumerate() {
             let x = bounding_box.0.floor() as i16;
             let y = bounding_box.2.floor() as i16;
             let x_size = bounding_box.1.floor() as i16 + 1 - x;
@@ -207,8 +207,7 @@ impl PointInPolygonTest {
         let mut queue = Vec::with_capacity(10);
         // traverse all
         queue.push((start_x + (start_y * x_size as i16)) as usize);
-        while !queue.is_empty() {
-            let idx = queue.pop().unwrap();
+        while let Some(idx) = queue.pop() {
             coherent_rects.push(idx);
             let y = idx / x_size;
             let x = idx - (y * x_size);
@@ -320,15 +319,19 @@ impl PointInPolygonTest {
             return true;
         }
         // shortcut: First check grid
-        let grid_entry = self.check_grid(point.clone());
+        let grid_entry = self.check_grid(point);
         if *grid_entry == GridEntry::Polygon || *grid_entry == GridEntry::Outside {
             return *grid_entry == GridEntry::Polygon;
         }
         // first get all intersecting bounding boxes
-        let polygons_to_check = self.check_intersecting_bounding_boxes(point.clone());
+        let polygons_to_check = self.check_intersecting_bounding_boxes(point);
         // check these polygons with point in polygon test
         self.check_point_in_polygons(point, polygons_to_check)
     }
+    /// Checks if the point lies in a cell of the grid, which contains points of the outline of a polygon
+    pub fn is_border(&self, point: (f64, f64)) -> bool {
+        *self.check_grid(point) == GridEntry::Border
+    }
     pub fn polygons(&self) -> &Vec<Vec<(f64, f64)>> {
         &self.polygons
     }
@@ -0,0 +1,185 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use crate::avoid_areas::avoid_area_polygons;
+use crate::persistence::in_memory_routing_repo::GeoJsonPolygon;
+use crate::polygon_test::PointInPolygonTest;
+
+/**
+Area, to which a graph is restricted, e.g. the North Sea for development or a regional deployment.
+A region is either a lon/lat bounding box or a GeoJSON polygon. Only the points inside the region become nodes
+and only the coastlines overlapping the region are used to build the graph.
+A bounding box with lon_min > lon_max wraps across the antimeridian, e.g. 170,-50,-170,-30 around New Zealand.
+**/
+pub struct Region {
+    // part of the file names of the graph, which keeps the graphs of different regions apart
+    name: String,
+    // lon min, lon max, lat min, lat max. The longitudes wrap across the antimeridian if lon min > lon max
+    bounding_box: (f64, f64, f64, f64),
+    // outline of a polygon region, None if the region is the bounding box
+    polygon_test: Option<PointInPolygonTest>,
+}
+
+impl Region {
+    /// Parses a bounding box of the form lon_min,lat_min,lon_max,lat_max or a GeoJSON polygon.
+    /// The bounding box wraps across the antimeridian if lon_min > lon_max
+    pub fn parse(region: &str) -> Result<Region, String> {
+        let region = region.trim();
+        if region.starts_with('{') {
+            let polygon: GeoJsonPolygon = serde_json::from_str(region).map_err(|error| format!("Invalid GeoJSON polygon of the region: {}", error))?;
+            return Region::from_polygon(&polygon);
+        }
+        let values: Vec<f64> = region.split(',').map(|value| value.trim().parse::<f64>()).collect::<Result<_, _>>()
+            .map_err(|_| format!("The region {} is neither a bounding box lon_min,lat_min,lon_max,lat_max nor a GeoJSON polygon", region))?;
+        if values.len() != 4 {
+            return Err(format!("The bounding box {} of the region needs four values lon_min,lat_min,lon_max,lat_max", region));
+        }
+        let (lon_min, lat_min, lon_max, lat_max) = (values[0], values[1], values[2], values[3]);
+        if lon_min == lon_max || lat_min >= lat_max || lon_min.abs() > 180.0 || lon_max.abs() > 180.0 || lat_min < -90.0 || lat_max > 90.0 {
+            return Err(format!("The bounding box {} of the region is empty or exceeds the coordinate ranges", region));
+        }
+        Ok(Region {
+            name: format!("region_{}_{}_{}_{}", lon_min, lat_min, lon_max, lat_max),
+            bounding_box: (lon_min, lon_max, lat_min, lat_max),
+            polygon_test: None,
+        })
+    }
+
+    /// Parses the region of the configuration, which may also be the path of a file with a GeoJSON polygon
+    pub fn from_argument(argument: &str) -> Result<Region, String> {
+        if Path::new(argument).is_file() {
+            let polygon = fs::read_to_string(argument).map_err(|error| format!("Could not read region file {}: {}", argument, error))?;
+            return Region::parse(&polygon);
+        }
+        Region::parse(argument)
+    }
+
+    fn from_polygon(polygon: &GeoJsonPolygon) -> Result<Region, String> {
+        let polygons = avoid_area_polygons(&vec![polygon.clone()])?;
+        let bounding_box = polygons[0].iter().fold((180.0_f64, -180.0_f64, 90.0_f64, -90.0_f64), |(lon_min, lon_max, lat_min, lat_max), (lon, lat)| {
+            (lon_min.min(*lon), lon_max.max(*lon), lat_min.min(*lat), lat_max.max(*lat))
+        });
+        // the polygon is named by a hash of its coordinates
+        let mut hasher = DefaultHasher::new();
+        polygons[0].iter().for_each(|(lon, lat)| (lon.to_bits(), lat.to_bits()).hash(&mut hasher));
+        Ok(Region {
+            name: format!("region_{:016x}", hasher.finish()),
+            bounding_box,
+            polygon_test: Some(PointInPolygonTest::new(polygons)),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Lon min, lon max, lat min and lat max of the region. The longitudes wrap across the antimeridian if lon min > lon max
+    pub fn bounding_box(&self) -> (f64, f64, f64, f64) {
+        self.bounding_box
+    }
+
+    pub fn contains(&self, (lon, lat): (f64, f64)) -> bool {
+        let (_, _, lat_min, lat_max) = self.bounding_box;
+        self.lon_ranges().iter().any(|(lon_min, lon_max)| lon >= *lon_min && lon <= *lon_max) && lat >= lat_min && lat <= lat_max
+            && self.polygon_test.as_ref().map_or(true, |polygon_test| polygon_test.check_intersection((lon, lat)))
+    }
+
+    /// Checks if the lon/lat rectangle overlaps the bounding box of the region
+    pub fn overlaps(&self, (lon_min, lon_max): (f64, f64), (lat_min, lat_max): (f64, f64)) -> bool {
+        let (_, _, region_lat_min, region_lat_max) = self.bounding_box;
+        self.lon_ranges().iter().any(|(region_lon_min, region_lon_max)| lon_min <= *region_lon_max && lon_max >= *region_lon_min)
+            && lat_min <= region_lat_max && lat_max >= region_lat_min
+    }
+
+    /// Longitude ranges of the bounding box, which is split at the antimeridian if it wraps across it
+    fn lon_ranges(&self) -> Vec<(f64, f64)> {
+        let (lon_min, lon_max, _, _) = self.bounding_box;
+        if lon_min > lon_max { vec![(lon_min, 180.0), (-180.0, lon_max)] } else { vec![(lon_min, lon_max)] }
+    }
+
+    /// Keeps the polygons, whose bounding box overlaps the region
+    pub fn filter_polygons(&self, polygons: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        let number_polygons = polygons.len();
+        let polygons: Vec<Vec<(f64, f64)>> = polygons.into_iter().filter(|polygon| {
+            let (lon_min, lon_max, lat_min, lat_max) = polygon.iter().fold((180.0_f64, -180.0_f64, 90.0_f64, -90.0_f64), |(lon_min, lon_max, lat_min, lat_max), (lon, lat)| {
+                (lon_min.min(*lon), lon_max.max(*lon), lat_min.min(*lat), lat_max.max(*lat))
+            });
+            self.overlaps((lon_min, lon_max), (lat_min, lat_max))
+        }).collect();
+        println!("Use {} of {} polygons in the region {}", polygons.len(), number_polygons, self.name);
+        polygons
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::Region;
+
+    const SQUARE: &str = r#"{"type": "Polygon", "coordinates": [[[0, 0], [4, 0], [4, 4], [0, 4], [0, 0]]]}"#;
+
+    fn parse(region: &str) -> Region {
+        Region::parse(region).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    #[test]
+    fn bounding_boxes_are_parsed() {
+        let region = parse(" 2.5, 51, 9.5,58 ");
+        assert_eq!(region.name(), "region_2.5_51_9.5_58");
+        assert_eq!(region.bounding_box(), (2.5, 9.5, 51.0, 58.0));
+        assert!(region.contains((5.0, 55.0)));
+        assert!(region.contains((2.5, 51.0)));
+        assert!(!region.contains((10.0, 55.0)));
+        assert!(!region.contains((5.0, 50.0)));
+        assert!(region.overlaps((9.0, 12.0), (40.0, 52.0)));
+        assert!(!region.overlaps((10.0, 12.0), (40.0, 52.0)));
+
+        for invalid in &["2.5,51,9.5", "2.5,51,9.5,58,1", "2.5,north,9.5,58", "2.5,58,9.5,51", "2.5,51,2.5,58", "2.5,51,190,58", "2.5,-91,9.5,58"] {
+            assert!(Region::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn bounding_boxes_wrap_across_the_antimeridian() {
+        let region = parse("170,-50,-170,-30");
+        assert!(region.contains((175.0, -40.0)));
+        assert!(region.contains((180.0, -40.0)));
+        assert!(region.contains((-180.0, -40.0)));
+        assert!(region.contains((-175.0, -40.0)));
+        assert!(!region.contains((0.0, -40.0)));
+        assert!(!region.contains((-160.0, -40.0)));
+        assert!(!region.contains((175.0, -20.0)));
+        assert!(region.overlaps((160.0, 171.0), (-45.0, -35.0)));
+        assert!(region.overlaps((-171.0, -160.0), (-45.0, -35.0)));
+        assert!(!region.overlaps((-160.0, 160.0), (-45.0, -35.0)));
+
+        let polygons = vec![
+            vec![(175.0, -41.0), (176.0, -41.0), (176.0, -40.0), (175.0, -41.0)],
+            vec![(-176.0, -44.0), (-175.0, -44.0), (-175.0, -43.0), (-176.0, -44.0)],
+            vec![(0.0, -41.0), (1.0, -41.0), (1.0, -40.0), (0.0, -41.0)],
+        ];
+        let kept = region.filter_polygons(polygons);
+        assert_eq!(kept.len(), 2);
+        assert!(kept.iter().all(|polygon| polygon[0].0.abs() > 170.0));
+    }
+
+    #[test]
+    fn polygons_are_parsed_from_the_argument_or_a_file() {
+        let region = parse(SQUARE);
+        assert_eq!(region.bounding_box(), (0.0, 4.0, 0.0, 4.0));
+        assert!(region.name().starts_with("region_"));
+        assert!(region.contains((1.3, 2.7)));
+        assert!(!region.contains((5.0, 2.0)));
+        assert!(Region::parse(r#"{"type": "Polygon", "coordinates": [[[0, 0], [4, 0]]]}"#).is_err());
+        assert!(Region::parse(r#"{"type": "Polygon"}"#).is_err());
+
+        let path = std::env::temp_dir().join(format!("osm-tasks-region-{}.geojson", std::process::id()));
+        fs::write(&path, SQUARE).unwrap();
+        let from_file = Region::from_argument(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        // the same polygon has the same name, so the graph of the region is found again
+        assert_eq!(from_file.unwrap_or_else(|error| panic!("{}", error)).name(), region.name());
+        assert!(Region::from_argument("not a region").is_err());
+    }
+}
@@ -0,0 +1,29 @@
+use crate::grid_graph::{distance, Node};
+
+/**
+Shortens a route by replacing sub-paths with direct great-circle segments.
+Starting at the first node, the route is followed as long as the direct segment from the current anchor
+is free, e.g. does not cross land. The last node which could be seen from the anchor becomes the next anchor.
+The first and the last node of the route are always kept.
+**/
+pub(crate) fn smooth_route<F: Fn(&Node, &Node) -> bool>(route: &Vec<Node>, is_line_of_sight_free: F) -> Vec<Node> {
+    if route.len() <= 2 {
+        return route.clone();
+    }
+    let mut smoothed_route = vec![route[0]];
+    let mut anchor = 0;
+    for i in 2..route.len() {
+        if !is_line_of_sight_free(&route[anchor], &route[i]) {
+            // the edges of the route are free, so the previous node is visible
+            anchor = i - 1;
+            smoothed_route.push(route[anchor]);
+        }
+    }
+    smoothed_route.push(route[route.len() - 1]);
+    smoothed_route
+}
+
+/// Length of the route along great circles in meters
+pub(crate) fn route_distance(route: &Vec<Node>) -> u32 {
+    route.windows(2).map(|segment| distance(segment[0].lon, segment[0].lat, segment[1].lon, segment[1].lat)).sum::<f64>() as u32
+}
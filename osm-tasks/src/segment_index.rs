@@ -0,0 +1,304 @@
+use std::borrow::Cow;
+use crate::grid_graph::{distance, intermediate_point, Node};
+
+/// Width and height of a cell of the segment index in degrees
+const CELL_SIZE: f64 = 0.5;
+const NUMBER_CELLS_LON: usize = (360.0 / CELL_SIZE) as usize;
+const NUMBER_CELLS_LAT: usize = (180.0 / CELL_SIZE) as usize;
+/// Maximum length in meters of the straight lon/lat pieces, which approximate a great-circle segment
+const MAXIMUM_PIECE_LENGTH: f64 = 10_000.0;
+const METERS_PER_DEGREE_LAT: f64 = 111_319.5;
+
+/**
+Index over the segments of polygons like the coastlines, which is used to check if an edge crosses the outline of a polygon.
+The segments are stored in a lon/lat aligned grid. A segment is added to every cell its bounding box overlaps,
+so only the segments in the cells around an edge have to be tested for an intersection.
+The great-circle segment of an edge is approximated by straight lon/lat pieces, which are split at the antimeridian.
+**/
+pub struct SegmentIndex<'a> {
+    polygons: &'a Vec<Vec<(f64, f64)>>,
+    // polygon index and index of the first point of every segment in the cell
+    cells: Cow<'a, Vec<Vec<(u32, u32)>>>,
+}
+
+impl<'a> SegmentIndex<'a> {
+    pub fn new(polygons: &'a Vec<Vec<(f64, f64)>>) -> SegmentIndex<'a> {
+        let mut cells: Vec<Vec<(u32, u32)>> = vec![Vec::new(); NUMBER_CELLS_LON * NUMBER_CELLS_LAT];
+        for (polygon_index, polygon) in polygons.iter().enumerate() {
+            for i in 0..polygon.len().saturating_sub(1) {
+                let (start, end) = (polygon[i], polygon[i + 1]);
+                if (start.0 - end.0).abs() > 180.0 {
+                    // the polygons are split at the antimeridian, so such segments only close a polygon around the globe
+                    continue;
+                }
+                for cell in SegmentIndex::cells_of_bounding_box(start, end) {
+                    cells[cell].push((polygon_index as u32, i as u32));
+                }
+            }
+        }
+        SegmentIndex { polygons, cells: Cow::Owned(cells) }
+    }
+
+    /// Index with the cells of an index, which was built for the same polygons before, so an owner of the polygons can keep the cells
+    pub fn with_cells(polygons: &'a Vec<Vec<(f64, f64)>>, cells: &'a Vec<Vec<(u32, u32)>>) -> SegmentIndex<'a> {
+        SegmentIndex { polygons, cells: Cow::Borrowed(cells) }
+    }
+
+    /// Cells of the index, which can be kept without borrowing the polygons and used with SegmentIndex::with_cells
+    pub fn into_cells(self) -> Vec<Vec<(u32, u32)>> {
+        self.cells.into_owned()
+    }
+
+    /// Checks if the great-circle segment between both nodes crosses or touches the outline of a polygon
+    pub fn crosses_polygons(&self, from: &Node, to: &Node) -> bool {
+        let number_pieces = ((distance(from.lon, from.lat, to.lon, to.lat) / MAXIMUM_PIECE_LENGTH).ceil() as usize).max(1);
+        let mut piece_start = *from;
+        for i in 1..=number_pieces {
+            let piece_end = if i == number_pieces { *to } else { intermediate_point(from, to, i as f64 / number_pieces as f64) };
+            if self.piece_crosses_polygons(&piece_start, &piece_end) {
+                return true;
+            }
+            piece_start = piece_end;
+        }
+        false
+    }
+
+    /// Distance in meters between the segment and the nearest segment of a polygon, at most the maximum distance.
+    /// The segments are compared in a local equirectangular projection, which is accurate for the short edges of the graph
+    pub fn distance_to_polygons(&self, from: &Node, to: &Node, maximum_distance: f64) -> f64 {
+        // longitude of the end relative to the start, so segments crossing the antimeridian stay short
+        let to_lon = from.lon + normalize_lon(to.lon - from.lon);
+        let meters_per_degree_lon = METERS_PER_DEGREE_LAT * ((from.lat + to.lat) / 2.0).to_radians().cos().max(0.01);
+        let project = |(lon, lat): (f64, f64)| (normalize_lon(lon - from.lon) * meters_per_degree_lon, (lat - from.lat) * METERS_PER_DEGREE_LAT);
+        let start = (0.0, 0.0);
+        let end = project((to.lon, to.lat));
+        let margin_lon = (maximum_distance / meters_per_degree_lon).min(180.0);
+        let margin_lat = maximum_distance / METERS_PER_DEGREE_LAT;
+        let mut minimum_distance = maximum_distance;
+        let cells = SegmentIndex::cells_around((from.lon.min(to_lon) - margin_lon, from.lon.max(to_lon) + margin_lon), (from.lat.min(to.lat) - margin_lat, from.lat.max(to.lat) + margin_lat));
+        for cell in cells {
+            for (polygon_index, i) in &self.cells[cell] {
+                let polygon = &self.polygons[*polygon_index as usize];
+                let segment_distance = segment_distance(start, end, project(polygon[*i as usize]), project(polygon[*i as usize + 1]));
+                minimum_distance = minimum_distance.min(segment_distance);
+            }
+        }
+        minimum_distance
+    }
+
+    /// Distance in meters between the great-circle segment and the nearest segment of a polygon, at most the maximum distance.
+    /// Long segments are split into pieces, which are short enough for the local projection of distance_to_polygons
+    pub fn great_circle_distance_to_polygons(&self, from: &Node, to: &Node, maximum_distance: f64) -> f64 {
+        let number_pieces = ((distance(from.lon, from.lat, to.lon, to.lat) / MAXIMUM_PIECE_LENGTH).ceil() as usize).max(1);
+        let mut piece_start = *from;
+        let mut minimum_distance = maximum_distance;
+        for i in 1..=number_pieces {
+            let piece_end = if i == number_pieces { *to } else { intermediate_point(from, to, i as f64 / number_pieces as f64) };
+            minimum_distance = minimum_distance.min(self.distance_to_polygons(&piece_start, &piece_end, minimum_distance));
+            piece_start = piece_end;
+        }
+        minimum_distance
+    }
+
+    /// Checks if the outline of a polygon passes through the lon/lat rectangle, which must not cross the antimeridian
+    pub fn intersects_rectangle(&self, (lon_min, lon_max): (f64, f64), (lat_min, lat_max): (f64, f64)) -> bool {
+        let corners = [(lon_min, lat_min), (lon_max, lat_min), (lon_max, lat_max), (lon_min, lat_max)];
+        let is_inside = |(lon, lat): (f64, f64)| lon >= lon_min && lon <= lon_max && lat >= lat_min && lat <= lat_max;
+        SegmentIndex::cells_of_bounding_box((lon_min, lat_min), (lon_max, lat_max)).into_iter().any(|cell| {
+            self.cells[cell].iter().any(|(polygon_index, i)| {
+                let polygon = &self.polygons[*polygon_index as usize];
+                let (start, end) = (polygon[*i as usize], polygon[*i as usize + 1]);
+                is_inside(start) || is_inside(end) || (0..4).any(|side| segments_intersect(start, end, corners[side], corners[(side + 1) % 4]))
+            })
+        })
+    }
+
+    /// Cells of a bounding box, whose longitudes may exceed the antimeridian
+    fn cells_around((lon_min, lon_max): (f64, f64), (lat_min, lat_max): (f64, f64)) -> Vec<usize> {
+        let min_x = ((lon_min + 180.0) / CELL_SIZE).floor() as isize;
+        let max_x = ((lon_max + 180.0) / CELL_SIZE).floor() as isize;
+        let (min_y, max_y) = (cell_y(lat_min), cell_y(lat_max));
+        // every cell of a row only once, if the box spans all longitudes
+        let max_x = max_x.min(min_x + NUMBER_CELLS_LON as isize - 1);
+        let mut cells = Vec::with_capacity((max_x - min_x + 1) as usize * (max_y - min_y + 1));
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                cells.push(y * NUMBER_CELLS_LON + x.rem_euclid(NUMBER_CELLS_LON as isize) as usize);
+            }
+        }
+        cells
+    }
+
+    fn piece_crosses_polygons(&self, start: &Node, end: &Node) -> bool {
+        if (start.lon - end.lon).abs() <= 180.0 {
+            return self.segment_crosses_polygons((start.lon, start.lat), (end.lon, end.lat));
+        }
+        // the piece crosses the antimeridian -> split it at the border
+        let border_lon = if start.lon > 0.0 { 180.0 } else { -180.0 };
+        let shifted_end_lon = end.lon + 2.0 * border_lon;
+        let border_lat = start.lat + (end.lat - start.lat) * (border_lon - start.lon) / (shifted_end_lon - start.lon);
+        self.segment_crosses_polygons((start.lon, start.lat), (border_lon, border_lat))
+            || self.segment_crosses_polygons((-border_lon, border_lat), (end.lon, end.lat))
+    }
+
+    fn segment_crosses_polygons(&self, start: (f64, f64), end: (f64, f64)) -> bool {
+        SegmentIndex::cells_of_bounding_box(start, end).into_iter().any(|cell| {
+            self.cells[cell].iter().any(|(polygon_index, i)| {
+                let polygon = &self.polygons[*polygon_index as usize];
+                segments_intersect(start, end, polygon[*i as usize], polygon[*i as usize + 1])
+            })
+        })
+    }
+
+    fn cells_of_bounding_box(start: (f64, f64), end: (f64, f64)) -> Vec<usize> {
+        let (min_x, max_x) = (cell_x(start.0.min(end.0)), cell_x(start.0.max(end.0)));
+        let (min_y, max_y) = (cell_y(start.1.min(end.1)), cell_y(start.1.max(end.1)));
+        let mut cells = Vec::with_capacity((max_x - min_x + 1) * (max_y - min_y + 1));
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                cells.push(y * NUMBER_CELLS_LON + x);
+            }
+        }
+        cells
+    }
+}
+
+fn cell_x(lon: f64) -> usize {
+    (((lon + 180.0) / CELL_SIZE).floor().max(0.0) as usize).min(NUMBER_CELLS_LON - 1)
+}
+
+fn cell_y(lat: f64) -> usize {
+    (((lat + 90.0) / CELL_SIZE).floor().max(0.0) as usize).min(NUMBER_CELLS_LAT - 1)
+}
+
+/// Checks if both lon/lat segments intersect, touching segments included
+pub(crate) fn segments_intersect(a1: (f64, f64), a2: (f64, f64), b1: (f64, f64), b2: (f64, f64)) -> bool {
+    let d1 = orientation(b1, b2, a1);
+    let d2 = orientation(b1, b2, a2);
+    let d3 = orientation(a1, a2, b1);
+    let d4 = orientation(a1, a2, b2);
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0)) && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0)) {
+        return true;
+    }
+    (d1 == 0.0 && on_segment(b1, b2, a1))
+        || (d2 == 0.0 && on_segment(b1, b2, a2))
+        || (d3 == 0.0 && on_segment(a1, a2, b1))
+        || (d4 == 0.0 && on_segment(a1, a2, b2))
+}
+
+/// Difference of two longitudes in the range from -180 to 180 degrees
+fn normalize_lon(lon_difference: f64) -> f64 {
+    if lon_difference > 180.0 {
+        lon_difference - 360.0
+    } else if lon_difference < -180.0 {
+        lon_difference + 360.0
+    } else {
+        lon_difference
+    }
+}
+
+/// Distance between two planar segments
+fn segment_distance(a1: (f64, f64), a2: (f64, f64), b1: (f64, f64), b2: (f64, f64)) -> f64 {
+    if segments_intersect(a1, a2, b1, b2) {
+        return 0.0;
+    }
+    point_segment_distance(a1, b1, b2)
+        .min(point_segment_distance(a2, b1, b2))
+        .min(point_segment_distance(b1, a1, a2))
+        .min(point_segment_distance(b2, a1, a2))
+}
+
+/// Distance between the planar point p and the segment from a to b
+fn point_segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 { 0.0 } else { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_squared).max(0.0).min(1.0) };
+    let (x, y) = (a.0 + t * dx - p.0, a.1 + t * dy - p.1);
+    (x * x + y * y).sqrt()
+}
+
+/// Twice the signed area of the triangle, positive if r lies to the left of the direction from p to q
+pub(crate) fn orientation(p: (f64, f64), q: (f64, f64), r: (f64, f64)) -> f64 {
+    (q.0 - p.0) * (r.1 - p.1) - (q.1 - p.1) * (r.0 - p.0)
+}
+
+/// Checks if the point r, which is collinear to p and q, lies between them
+fn on_segment(p: (f64, f64), q: (f64, f64), r: (f64, f64)) -> bool {
+    r.0 >= p.0.min(q.0) && r.0 <= p.0.max(q.0) && r.1 >= p.1.min(q.1) && r.1 <= p.1.max(q.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid_graph::Node;
+    use super::{segments_intersect, SegmentIndex};
+
+    /// Square island between 10 and 11 degrees east and 50 and 51 degrees north and a strip of land east of the antimeridian
+    fn polygons() -> Vec<Vec<(f64, f64)>> {
+        vec![
+            vec![(10.0, 50.0), (11.0, 50.0), (11.0, 51.0), (10.0, 51.0), (10.0, 50.0)],
+            vec![(-179.5, 0.0), (-179.0, 0.0), (-179.0, 1.0), (-179.5, 1.0), (-179.5, 0.0)],
+        ]
+    }
+
+    #[test]
+    fn edges_crossing_the_outline_are_detected() {
+        let polygons = polygons();
+        let index = SegmentIndex::new(&polygons);
+        assert!(index.crosses_polygons(&Node { lon: 9.5, lat: 50.5 }, &Node { lon: 11.5, lat: 50.5 }));
+        assert!(index.crosses_polygons(&Node { lon: 10.5, lat: 49.5 }, &Node { lon: 10.5, lat: 50.5 }));
+        assert!(!index.crosses_polygons(&Node { lon: 9.5, lat: 49.5 }, &Node { lon: 11.5, lat: 49.5 }));
+        // an edge inside the island does not cross its outline
+        assert!(!index.crosses_polygons(&Node { lon: 10.2, lat: 50.2 }, &Node { lon: 10.8, lat: 50.8 }));
+        // the cells can be kept and reused for the same polygons
+        let cells = index.into_cells();
+        let index = SegmentIndex::with_cells(&polygons, &cells);
+        assert!(index.crosses_polygons(&Node { lon: 9.5, lat: 50.5 }, &Node { lon: 11.5, lat: 50.5 }));
+    }
+
+    #[test]
+    fn edges_across_the_antimeridian_are_split() {
+        let polygons = polygons();
+        let index = SegmentIndex::new(&polygons);
+        assert!(index.crosses_polygons(&Node { lon: 179.5, lat: 0.5 }, &Node { lon: -179.2, lat: 0.5 }));
+        assert!(index.crosses_polygons(&Node { lon: -179.2, lat: 0.5 }, &Node { lon: 179.5, lat: 0.5 }));
+        assert!(!index.crosses_polygons(&Node { lon: 179.5, lat: 0.5 }, &Node { lon: -179.8, lat: 0.5 }));
+        assert!(!index.crosses_polygons(&Node { lon: 179.5, lat: 2.0 }, &Node { lon: -178.5, lat: 2.0 }));
+    }
+
+    #[test]
+    fn distance_to_the_nearest_segment_is_capped() {
+        let polygons = polygons();
+        let index = SegmentIndex::new(&polygons);
+        // 0.1 degrees south of the island
+        let (from, to) = (Node { lon: 10.4, lat: 49.9 }, Node { lon: 10.6, lat: 49.9 });
+        assert!((index.distance_to_polygons(&from, &to, 50_000.0) - 11_132.0).abs() < 10.0);
+        assert!((index.great_circle_distance_to_polygons(&from, &to, 50_000.0) - 11_132.0).abs() < 10.0);
+        assert_eq!(index.distance_to_polygons(&from, &to, 5_000.0), 5_000.0);
+        assert_eq!(index.distance_to_polygons(&Node { lon: 10.5, lat: 49.5 }, &Node { lon: 10.5, lat: 50.5 }, 5_000.0), 0.0);
+        // edge across the antimeridian, which ends 0.1 degrees west of the strip
+        let distance = index.distance_to_polygons(&Node { lon: 179.9, lat: 0.5 }, &Node { lon: -179.6, lat: 0.5 }, 50_000.0);
+        assert!((distance - 11_132.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn rectangles_intersecting_the_outline() {
+        let polygons = polygons();
+        let index = SegmentIndex::new(&polygons);
+        // with a corner of the island
+        assert!(index.intersects_rectangle((10.9, 11.1), (50.9, 51.1)));
+        // crossed by a side of the island without a corner inside
+        assert!(index.intersects_rectangle((10.4, 10.6), (50.9, 51.1)));
+        assert!(!index.intersects_rectangle((10.4, 10.6), (50.4, 50.6)));
+        assert!(!index.intersects_rectangle((12.0, 13.0), (50.0, 51.0)));
+    }
+
+    #[test]
+    fn crossing_touching_and_collinear_segments() {
+        assert!(segments_intersect((0.0, 0.0), (1.0, 1.0), (0.0, 1.0), (1.0, 0.0)));
+        assert!(!segments_intersect((0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)));
+        assert!(segments_intersect((0.0, 0.0), (1.0, 0.0), (1.0, 0.0), (1.0, 1.0)));
+        assert!(segments_intersect((0.0, 0.0), (1.0, 0.0), (0.5, 0.0), (0.5, 1.0)));
+        assert!(segments_intersect((0.0, 0.0), (2.0, 0.0), (1.0, 0.0), (3.0, 0.0)));
+        assert!(!segments_intersect((0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)));
+    }
+}
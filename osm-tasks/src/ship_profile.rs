@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::grid_graph::{distance, intermediate_point, Node};
+use crate::persistence::in_memory_routing_repo::{Voyage, TimedPosition};
+use crate::weather::Weather;
+
+const METERS_PER_NAUTICAL_MILE: f64 = 1852.0;
+/// Name of the profile built from the ship options of the command line, which is used if a request names no profile
+pub const DEFAULT_SHIP_PROFILE: &str = "default";
+/// Smallest interval in seconds, in which the positions of the ship are sampled, so the number of positions stays bounded
+pub const MINIMUM_POSITION_INTERVAL: u32 = 60;
+
+/// Vessel with its parameters, selected by name in a route request
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ShipProfile {
+    pub name: String,
+    // service speed in knots
+    pub speed: f64,
+    // fuel consumption at the service speed in tonnes per day
+    #[serde(default = "default_fuel_consumption")]
+    pub fuel_consumption: f64,
+    // maximum draft in meters, 0 if it is unknown. The coastlines have no depths, so only the passages with a smaller maximum draft are closed
+    #[serde(default)]
+    pub max_draft: f64,
+    // minimum distance to the coast in meters
+    #[serde(default)]
+    pub min_coast_distance: f64,
+}
+
+fn default_fuel_consumption() -> f64 {
+    Config::global().fuel_consumption()
+}
+
+impl ShipProfile {
+    /// Profile with the service speed and fuel consumption of the command line
+    pub fn default_profile() -> ShipProfile {
+        let config = Config::global();
+        ShipProfile { name: String::from(DEFAULT_SHIP_PROFILE), speed: config.service_speed(), fuel_consumption: config.fuel_consumption(), max_draft: 0.0, min_coast_distance: 0.0 }
+    }
+
+    pub fn meters_per_second(&self) -> f64 {
+        self.speed * METERS_PER_NAUTICAL_MILE / 3600.0
+    }
+}
+
+/// Reads the ship profiles from a JSON file with an array of profiles. The default profile is always included,
+/// but can be replaced by a profile with the same name
+pub(crate) fn read_ship_profiles(path: &Option<String>) -> HashMap<String, ShipProfile> {
+    let mut ship_profiles = HashMap::new();
+    ship_profiles.insert(String::from(DEFAULT_SHIP_PROFILE), ShipProfile::default_profile());
+    if let Some(path) = path {
+        let file = File::open(path).unwrap_or_else(|error| panic!("Could not open ship profiles file {}: {}", path, error));
+        let profiles: Vec<ShipProfile> = serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|error| panic!("Could not read ship profiles file {}: {}", path, error));
+        println!("Loaded {} ship profiles from {}", profiles.len(), path);
+        for profile in profiles {
+            if profile.speed <= 0.0 {
+                panic!("The speed of ship profile {} must be positive", profile.name);
+            }
+            if profile.max_draft < 0.0 {
+                panic!("The maximum draft of ship profile {} must not be negative", profile.name);
+            }
+            ship_profiles.insert(profile.name.clone(), profile);
+        }
+    }
+    ship_profiles
+}
+
+/**
+Calculates the timestamps of the route for a ship sailing at its service speed, which is changed by the weather.
+Without a departure time, the times are seconds since the departure.
+If a position interval is given, the position of the ship is sampled every interval seconds from the departure up to the arrival.
+The interval has to be at least MINIMUM_POSITION_INTERVAL seconds.
+**/
+pub(crate) fn calculate_voyage(ship_profile: &ShipProfile, route: &Vec<Node>, departure_time: Option<u64>, position_interval: Option<u32>, weather: Option<&Weather>) -> Voyage {
+    let departure_time = departure_time.unwrap_or(0);
+    let meters_per_second = ship_profile.meters_per_second();
+    // seconds since the departure at every node of the route
+    let mut offsets = Vec::with_capacity(route.len());
+    let mut offset = 0.0;
+    offsets.push(offset);
+    for segment in route.windows(2) {
+        let speed_factor = weather.map_or(1.0, |weather| weather.speed_factor(&segment[0], &segment[1], meters_per_second, departure_time + offset as u64));
+        offset += distance(segment[0].lon, segment[0].lat, segment[1].lon, segment[1].lat) / (meters_per_second * speed_factor);
+        offsets.push(offset);
+    }
+    let duration = offset.round() as u64;
+    let positions = match position_interval {
+        Some(interval) if interval > 0 && !route.is_empty() => {
+            let mut positions = Vec::with_capacity((duration / interval as u64) as usize + 2);
+            let mut segment = 0;
+            let mut time = 0;
+            while time < duration {
+                // find the segment the ship is on at this time
+                while segment + 2 < offsets.len() && offsets[segment + 1] <= time as f64 {
+                    segment += 1;
+                }
+                let position = if offsets[segment + 1] <= offsets[segment] {
+                    route[segment]
+                } else {
+                    let fraction = ((time as f64 - offsets[segment]) / (offsets[segment + 1] - offsets[segment])).min(1.0);
+                    intermediate_point(&route[segment], &route[segment + 1], fraction)
+                };
+                positions.push(TimedPosition::new(departure_time + time, position));
+                time += interval as u64;
+            }
+            positions.push(TimedPosition::new(departure_time + duration, route[route.len() - 1]));
+            positions
+        }
+        _ => Vec::new()
+    };
+    let timestamps = offsets.iter().map(|offset| departure_time + offset.round() as u64).collect();
+    Voyage::new(ship_profile.name.clone(), duration, departure_time, departure_time + duration, timestamps, positions)
+}
@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use rayon::prelude::*;
+use crate::grid_graph::{add_edge, Edge, Node};
+use crate::polygon_test::PointInPolygonTest;
+use crate::region::Region;
+
+/// Distribution of the nodes on the sphere and the edges between them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphTopology {
+    /// Equally distributed nodes following Deserno, which are connected to the nearest nodes of the rounds above and below
+    Deserno,
+    /// Regular lon/lat grid, every node is connected to its four neighbors
+    LatLon4,
+    /// Regular lon/lat grid, every node is connected to its eight neighbors including the diagonals
+    LatLon8,
+    /// Centers of the cells of a hexagonal grid on a subdivided icosahedron, every node has six neighbors (five at the twelve corners)
+    Hexagonal,
+    /// Equally distributed nodes following Deserno, which are refined near the coasts
+    Adaptive,
+}
+
+impl FromStr for GraphTopology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deserno" => Ok(GraphTopology::Deserno),
+            "latlon4" => Ok(GraphTopology::LatLon4),
+            "latlon8" => Ok(GraphTopology::LatLon8),
+            "hexagonal" => Ok(GraphTopology::Hexagonal),
+            "adaptive" => Ok(GraphTopology::Adaptive),
+            _ => Err(format!("Unknown graph topology: {}", s))
+        }
+    }
+}
+
+impl fmt::Display for GraphTopology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            GraphTopology::Deserno => "deserno",
+            GraphTopology::LatLon4 => "latlon4",
+            GraphTopology::LatLon8 => "latlon8",
+            GraphTopology::Hexagonal => "hexagonal",
+            GraphTopology::Adaptive => "adaptive",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Regular lon/lat grid with at most the maximum number of nodes. The rows are connected across the antimeridian
+pub(crate) fn lat_lon_nodes_and_edges(polygon_test: &PointInPolygonTest, region: Option<&Region>, maximum_number_of_nodes: usize, diagonals: bool) -> (Vec<Node>, Vec<Vec<Edge>>) {
+    let step = (360.0 * 180.0 / maximum_number_of_nodes.max(1) as f64).sqrt();
+    let number_columns = ((360.0 / step).floor() as usize).max(3);
+    let number_rows = ((180.0 / step).floor() as usize).max(1);
+    let (lon_step, lat_step) = (360.0 / number_columns as f64, 180.0 / number_rows as f64);
+    let points: Vec<Node> = (0..number_rows * number_columns).map(|i| Node {
+        lat: -90.0 + ((i / number_columns) as f64 + 0.5) * lat_step,
+        lon: -180.0 + ((i % number_columns) as f64 + 0.5) * lon_step,
+    }).collect();
+    let (nodes, points_to_index) = place_nodes(&points, polygon_test, region);
+    let mut edges: Vec<Vec<Edge>> = vec![Vec::with_capacity(if diagonals { 8 } else { 4 }); nodes.len()];
+    let point = |row: usize, column: usize| row * number_columns + column % number_columns;
+    for row in 0..number_rows {
+        for column in 0..number_columns {
+            if let Some(node) = points_to_index[point(row, column)] {
+                // every edge is added by the node to its west or south
+                add_edge(&mut edges, &nodes, node as usize, &points_to_index[point(row, column + 1)]);
+                if row + 1 < number_rows {
+                    add_edge(&mut edges, &nodes, node as usize, &points_to_index[point(row + 1, column)]);
+                    if diagonals {
+                        add_edge(&mut edges, &nodes, node as usize, &points_to_index[point(row + 1, column + 1)]);
+                        add_edge(&mut edges, &nodes, node as usize, &points_to_index[point(row + 1, column + number_columns - 1)]);
+                    }
+                }
+            }
+        }
+    }
+    println!("number grid points {} in {} rows and {} columns", points.len(), number_rows, number_columns);
+    (nodes, edges)
+}
+
+const ICOSAHEDRON_FACES: [[usize; 3]; 20] = [
+    [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+    [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+    [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+    [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+];
+
+/**
+Geodesic grid, whose nodes are the vertices of an icosahedron with subdivided faces projected onto the sphere.
+The nodes are the centers of the cells of the dual hexagonal grid, so neighboring cells are connected by the edges of the subdivided triangles.
+Each face is divided into frequency² triangles, which results in 10 * frequency² + 2 points.
+**/
+pub(crate) fn hexagonal_nodes_and_edges(polygon_test: &PointInPolygonTest, region: Option<&Region>, maximum_number_of_nodes: usize) -> (Vec<Node>, Vec<Vec<Edge>>) {
+    let frequency = ((maximum_number_of_nodes.saturating_sub(2) as f64 / 10.0).sqrt().floor() as usize).max(1);
+    let golden_ratio = (1.0 + 5.0_f64.sqrt()) / 2.0;
+    let corners = [
+        (-1.0, golden_ratio, 0.0), (1.0, golden_ratio, 0.0), (-1.0, -golden_ratio, 0.0), (1.0, -golden_ratio, 0.0),
+        (0.0, -1.0, golden_ratio), (0.0, 1.0, golden_ratio), (0.0, -1.0, -golden_ratio), (0.0, 1.0, -golden_ratio),
+        (golden_ratio, 0.0, -1.0), (golden_ratio, 0.0, 1.0), (-golden_ratio, 0.0, -1.0), (-golden_ratio, 0.0, 1.0),
+    ];
+    let mut points: Vec<Node> = Vec::with_capacity(10 * frequency * frequency + 2);
+    // points on the corners and edges of the icosahedron are shared by several faces
+    let mut point_indices: HashMap<(u8, u32, u32, u32), u32> = HashMap::new();
+    let mut point_edges: HashSet<(u32, u32)> = HashSet::new();
+    for (face_index, face) in ICOSAHEDRON_FACES.iter().enumerate() {
+        // index of the point with the barycentric coordinates a and b, the third coordinate is frequency - a - b
+        let mut face_points = vec![0; (frequency + 1) * (frequency + 1)];
+        for a in 0..=frequency {
+            for b in 0..=(frequency - a) {
+                let c = frequency - a - b;
+                let key = point_key(face_index, face, [a, b, c]);
+                let index = *point_indices.entry(key).or_insert_with(|| {
+                    let weight = |i: usize| [a, b, c][i] as f64 / frequency as f64;
+                    let x = (0..3).map(|i| corners[face[i]].0 * weight(i)).sum::<f64>();
+                    let y = (0..3).map(|i| corners[face[i]].1 * weight(i)).sum::<f64>();
+                    let z = (0..3).map(|i| corners[face[i]].2 * weight(i)).sum::<f64>();
+                    points.push(to_node(x, y, z));
+                    points.len() as u32 - 1
+                });
+                face_points[a * (frequency + 1) + b] = index;
+            }
+        }
+        let face_point = |a: usize, b: usize| face_points[a * (frequency + 1) + b];
+        for a in 0..=frequency {
+            for b in 0..=(frequency - a) {
+                let c = frequency - a - b;
+                // the three directions of the triangles, the opposite directions are added by the neighbors
+                let mut neighbors = Vec::with_capacity(3);
+                if b > 0 {
+                    neighbors.push(face_point(a + 1, b - 1));
+                }
+                if c > 0 {
+                    neighbors.push(face_point(a + 1, b));
+                    neighbors.push(face_point(a, b + 1));
+                }
+                for neighbor in neighbors {
+                    let point = face_point(a, b);
+                    point_edges.insert((point.min(neighbor), point.max(neighbor)));
+                }
+            }
+        }
+    }
+    let (nodes, points_to_index) = place_nodes(&points, polygon_test, region);
+    let mut edges: Vec<Vec<Edge>> = vec![Vec::with_capacity(6); nodes.len()];
+    // sorted, so the graph is the same for every build
+    let mut point_edges: Vec<(u32, u32)> = point_edges.into_iter().collect();
+    point_edges.sort_unstable();
+    for (point1, point2) in point_edges {
+        if let Some(node) = points_to_index[point1 as usize] {
+            add_edge(&mut edges, &nodes, node as usize, &points_to_index[point2 as usize]);
+        }
+    }
+    println!("number grid points {} with frequency {}", points.len(), frequency);
+    (nodes, edges)
+}
+
+/// Key of a point of a face, which is the same for all faces sharing the point
+fn point_key(face_index: usize, face: &[usize; 3], weights: [usize; 3]) -> (u8, u32, u32, u32) {
+    let corners: Vec<usize> = (0..3).filter(|i| weights[*i] > 0).collect();
+    match corners.len() {
+        // corner of the icosahedron
+        1 => (0, face[corners[0]] as u32, 0, 0),
+        // edge of the icosahedron, the position on the edge is counted from the corner with the smaller index
+        2 => {
+            let (corner1, corner2) = (face[corners[0]], face[corners[1]]);
+            let weight_of_smaller_corner = if corner1 < corner2 { weights[corners[0]] } else { weights[corners[1]] };
+            (1, corner1.min(corner2) as u32, corner1.max(corner2) as u32, weight_of_smaller_corner as u32)
+        }
+        _ => (2, face_index as u32, weights[0] as u32, weights[1] as u32)
+    }
+}
+
+fn to_node(x: f64, y: f64, z: f64) -> Node {
+    let length = (x * x + y * y + z * z).sqrt();
+    let lon = y.atan2(x).to_degrees();
+    Node {
+        // the midpoints of two edges of the icosahedron are the poles, which are moved slightly into the grid of the polygon test
+        lat: (z / length).asin().to_degrees().max(-89.999_999).min(89.999_999),
+        // the longitudes are in the range [-180, 180) like the nodes of the other topologies
+        lon: if lon >= 180.0 { lon - 360.0 } else { lon },
+    }
+}
+
+/// Keeps the points outside of the polygons and inside of the region as nodes. Returns the nodes and the node index of every point
+fn place_nodes(points: &Vec<Node>, polygon_test: &PointInPolygonTest, region: Option<&Region>) -> (Vec<Node>, Vec<Option<u32>>) {
+    let in_water: Vec<bool> = points.par_iter().map(|point| {
+        region.map_or(true, |region| region.contains((point.lon, point.lat))) && !polygon_test.check_intersection((point.lon, point.lat))
+    }).collect();
+    let mut nodes = Vec::with_capacity(points.len());
+    let points_to_index = points.iter().zip(in_water).map(|(point, in_water)| {
+        if in_water {
+            nodes.push(*point);
+            Some(nodes.len() as u32 - 1)
+        } else {
+            None
+        }
+    }).collect();
+    (nodes, points_to_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid_graph::{Edge, Node};
+    use crate::polygon_test::PointInPolygonTest;
+    use crate::region::Region;
+    use super::{hexagonal_nodes_and_edges, lat_lon_nodes_and_edges};
+
+    /// Square island between 0 and 20 degrees east and north, whose outline has edges of 0.5 degrees like the coastlines
+    fn island() -> PointInPolygonTest {
+        let mut polygon = Vec::new();
+        for i in 0..40 {
+            polygon.push((i as f64 * 0.5, 0.0));
+        }
+        for i in 0..40 {
+            polygon.push((20.0, i as f64 * 0.5));
+        }
+        for i in 0..40 {
+            polygon.push((20.0 - i as f64 * 0.5, 20.0));
+        }
+        for i in 0..=40 {
+            polygon.push((0.0, 20.0 - i as f64 * 0.5));
+        }
+        PointInPolygonTest::new(vec![polygon])
+    }
+
+    /// Checks if the node lies inside of the island, nodes on the outline may be nodes of the graph
+    fn is_on_island(node: &Node) -> bool {
+        node.lon > 0.0 && node.lon < 20.0 && node.lat > 0.0 && node.lat < 20.0
+    }
+
+    /// Checks that every edge has a reverse edge and that the edges between both nodes are no duplicates
+    fn assert_symmetric(edges: &[Vec<Edge>]) {
+        for (node, node_edges) in edges.iter().enumerate() {
+            for edge in node_edges {
+                assert_eq!(edge.source, node as u32);
+                assert_ne!(edge.target, edge.source);
+                assert_eq!(node_edges.iter().filter(|other| other.target == edge.target).count(), 1, "duplicate edge {} - {}", edge.source, edge.target);
+                assert!(edges[edge.target as usize].iter().any(|reverse| reverse.target == edge.source && reverse.distance == edge.distance));
+            }
+        }
+    }
+
+    #[test]
+    fn lat_lon_grid_wraps_across_the_antimeridian() {
+        // 36 columns and 18 rows with a spacing of 10 degrees
+        let (nodes, edges) = lat_lon_nodes_and_edges(&PointInPolygonTest::new(vec![]), None, 648, false);
+        assert_eq!(nodes.len(), 648);
+        assert_symmetric(&edges);
+        for (node, node_edges) in edges.iter().enumerate() {
+            let row = node / 36;
+            assert_eq!(node_edges.len(), if row == 0 || row == 17 { 3 } else { 4 }, "degree of node {}", node);
+        }
+        // the first and the last column are neighbors
+        assert_eq!((nodes[36 * 5].lon, nodes[36 * 5 + 35].lon), (-175.0, 175.0));
+        assert!(edges[36 * 5].iter().any(|edge| edge.target == 36 * 5 + 35));
+
+        let (_, edges) = lat_lon_nodes_and_edges(&PointInPolygonTest::new(vec![]), None, 648, true);
+        assert_symmetric(&edges);
+        for (node, node_edges) in edges.iter().enumerate() {
+            let row = node / 36;
+            assert_eq!(node_edges.len(), if row == 0 || row == 17 { 5 } else { 8 }, "degree of node {}", node);
+        }
+    }
+
+    #[test]
+    fn lat_lon_grid_leaves_out_land_and_points_outside_of_the_region() {
+        let polygon_test = island();
+        let (nodes, edges) = lat_lon_nodes_and_edges(&polygon_test, None, 64_800, true);
+        assert!(nodes.len() < 64_800);
+        assert!(nodes.iter().all(|node| !is_on_island(node)));
+        assert!(nodes.iter().any(|node| node.lon > 20.0 && node.lon < 21.0 && node.lat > 10.0 && node.lat < 11.0));
+        assert_symmetric(&edges);
+
+        let region = Region::parse("-30,-30,30,30").unwrap_or_else(|error| panic!("{}", error));
+        let (nodes, edges) = lat_lon_nodes_and_edges(&polygon_test, Some(&region), 64_800, true);
+        // 60 by 60 points with a spacing of 1 degree, 20 by 20 of them on the island
+        assert_eq!(nodes.len(), 3_600 - 400);
+        assert!(nodes.iter().all(|node| region.contains((node.lon, node.lat)) && !is_on_island(node)));
+        assert_symmetric(&edges);
+    }
+
+    #[test]
+    fn hexagonal_grid_has_six_neighbors_except_at_the_corners() {
+        // frequency 4
+        let (nodes, edges) = hexagonal_nodes_and_edges(&PointInPolygonTest::new(vec![]), None, 162);
+        // the point on the south pole is on the land of the polygon test, so its six neighbors lose an edge
+        assert_eq!(nodes.len(), 161);
+        assert!(nodes.iter().all(|node| (-180.0..180.0).contains(&node.lon) && (-85.0..=90.0).contains(&node.lat)));
+        assert_symmetric(&edges);
+        assert_eq!(edges.iter().filter(|node_edges| node_edges.len() == 5).count(), 12 + 6);
+        assert_eq!(edges.iter().filter(|node_edges| node_edges.len() == 6).count(), 161 - 12 - 6);
+        // the projected triangles are about equally large
+        let distances: Vec<u32> = edges.iter().flatten().map(|edge| edge.distance).collect();
+        let (minimum, maximum) = (*distances.iter().min().unwrap(), *distances.iter().max().unwrap());
+        assert!((maximum as f64) < minimum as f64 * 1.5, "edges between {} and {} meters", minimum, maximum);
+    }
+
+    #[test]
+    fn hexagonal_grid_leaves_out_land() {
+        let (nodes, edges) = hexagonal_nodes_and_edges(&island(), None, 100_000);
+        // frequency 99
+        assert!(nodes.len() < 98_012);
+        assert!(nodes.iter().all(|node| !is_on_island(node)));
+        assert!(nodes.iter().any(|node| node.lon > 20.0 && node.lon < 21.0 && node.lat > 10.0 && node.lat < 11.0));
+        assert_symmetric(&edges);
+    }
+}
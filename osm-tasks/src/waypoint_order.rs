@@ -0,0 +1,87 @@
+/**
+Solves the order in which the waypoints of a route are visited as small traveling salesman problem with fixed start and end.
+The first index of the distance matrix is the start, the last index the end of the route. The tour is constructed with the
+nearest insertion heuristic and improved with 2-opt afterwards. The distances are expected to be symmetric, which holds
+for the grid graph. Unreachable pairs have the distance u32::MAX.
+Returns the indices of the distance matrix in visiting order.
+**/
+pub fn optimize_waypoint_order(distances: &Vec<Vec<u32>>) -> Vec<usize> {
+    let number_of_points = distances.len();
+    if number_of_points <= 3 {
+        return (0..number_of_points).collect();
+    }
+    let distance = |a: usize, b: usize| distances[a][b] as u64;
+    let mut tour = vec![0, number_of_points - 1];
+    let mut unvisited: Vec<usize> = (1..(number_of_points - 1)).collect();
+    while !unvisited.is_empty() {
+        // insert the waypoint which is nearest to the tour at the position where it extends the tour the least
+        let (unvisited_index, _) = unvisited.iter().enumerate()
+            .map(|(i, point)| (i, tour.iter().map(|tour_point| distance(*tour_point, *point)).min().unwrap()))
+            .min_by_key(|(_, distance_to_tour)| *distance_to_tour)
+            .unwrap();
+        let point = unvisited.swap_remove(unvisited_index);
+        let insert_position = (1..tour.len())
+            .min_by_key(|i| (distance(tour[i - 1], point) + distance(point, tour[*i])).saturating_sub(distance(tour[i - 1], tour[*i])))
+            .unwrap();
+        tour.insert(insert_position, point);
+    }
+    // 2-opt: reverse parts of the tour as long as this shortens the tour, start and end stay in place
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 1..(tour.len() - 2) {
+            for j in (i + 1)..(tour.len() - 1) {
+                let current_length = distance(tour[i - 1], tour[i]) + distance(tour[j], tour[j + 1]);
+                let reversed_length = distance(tour[i - 1], tour[j]) + distance(tour[i], tour[j + 1]);
+                if reversed_length < current_length {
+                    tour[i..=j].reverse();
+                    improved = true;
+                }
+            }
+        }
+    }
+    tour
+}
+
+#[cfg(test)]
+mod tests {
+    use super::optimize_waypoint_order;
+
+    fn distance_matrix(positions: &[i64]) -> Vec<Vec<u32>> {
+        positions.iter().map(|a| positions.iter().map(|b| (a - b).unsigned_abs() as u32).collect()).collect()
+    }
+
+    #[test]
+    fn waypoints_on_a_line_are_visited_in_order() {
+        // start at 0, end at 100 and the waypoints in between
+        let positions = [0, 70, 20, 90, 50, 10, 100];
+        let order = optimize_waypoint_order(&distance_matrix(&positions));
+        assert_eq!(order, vec![0, 5, 2, 4, 1, 3, 6]);
+    }
+
+    #[test]
+    fn start_and_end_stay_in_place() {
+        // the end lies between the start and the waypoints, so the tour has to come back to it
+        let positions = [0, 100, 80, 90, 40];
+        let order = optimize_waypoint_order(&distance_matrix(&positions));
+        assert_eq!(order.first(), Some(&0));
+        assert_eq!(order.last(), Some(&4));
+        let mut visited = order.clone();
+        visited.sort_unstable();
+        assert_eq!(visited, vec![0, 1, 2, 3, 4]);
+        assert_eq!(order.windows(2).map(|leg| (positions[leg[0]] - positions[leg[1]]).abs()).sum::<i64>(), 160);
+    }
+
+    #[test]
+    fn unreachable_legs_are_avoided() {
+        let mut distances = distance_matrix(&[0, 10, 20, 30]);
+        distances[0][1] = u32::MAX;
+        distances[1][0] = u32::MAX;
+        assert_eq!(optimize_waypoint_order(&distances), vec![0, 2, 1, 3]);
+    }
+
+    #[test]
+    fn a_single_waypoint_keeps_its_place() {
+        assert_eq!(optimize_waypoint_order(&distance_matrix(&[0, 50, 10])), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,269 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use crate::grid_graph::{distance, intermediate_point, Node};
+use crate::config::Config;
+use crate::region::Region;
+
+/// The speed over ground does not drop below this share of the speed through the water, e.g. against a strong current
+const MINIMUM_SPEED_FACTOR: f64 = 0.1;
+
+/**
+Vector field like currents or winds, which is given on a regular lon/lat grid for several time steps.
+The field is read from a CSV file with the header time,lon,lat,u,v and a line for every grid point and time step.
+The time is a unix timestamp in seconds, u is the eastward and v the northward component in meters per second.
+The vectors are interpolated bilinearly between the grid points and linearly between the time steps.
+Outside of the grid, the field is zero. With a region, only the grid points inside of the region and the next grid lines around it are kept.
+Reading fails with the reason, if the file is missing or malformed.
+**/
+pub struct VectorField {
+    times: Vec<u64>,
+    lons: Vec<f64>,
+    lats: Vec<f64>,
+    // u and v of every time step, lat and lon in this order. Missing grid points are zero
+    vectors: Vec<(f32, f32)>,
+    // length of the largest vector
+    maximum_speed: f64,
+}
+
+impl VectorField {
+    pub fn read(path: &str, region: Option<&Region>) -> Result<VectorField, String> {
+        let file = File::open(path).map_err(|error| format!("Could not open vector field file {}: {}", path, error))?;
+        let mut rows = Vec::new();
+        for (line_number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|error| format!("Could not read vector field file {}: {}", path, error))?;
+            if line_number == 0 || line.trim().is_empty() {
+                // header
+                continue;
+            }
+            let values: Vec<&str> = line.split(',').map(|value| value.trim()).collect();
+            if values.len() != 5 {
+                return Err(format!("Line {} of vector field file {} does not have the columns time,lon,lat,u,v", line_number + 1, path));
+            }
+            let parse = |value: &str| value.parse::<f64>().map_err(|_| format!("Invalid number {} in line {} of vector field file {}", value, line_number + 1, path));
+            rows.push((parse(values[0])? as u64, parse(values[1])?, parse(values[2])?, parse(values[3])?, parse(values[4])?));
+        }
+        let mut times: Vec<u64> = rows.iter().map(|row| row.0).collect();
+        times.sort_unstable();
+        times.dedup();
+        let mut lons = sorted_values(rows.iter().map(|row| row.1).collect());
+        let mut lats = sorted_values(rows.iter().map(|row| row.2).collect());
+        if let Some(region) = region {
+            let (lon_min, lon_max, lat_min, lat_max) = region.bounding_box();
+            // the longitudes of a region across the antimeridian are not clipped
+            if lon_min <= lon_max {
+                lons = clip_grid_values(lons, lon_min, lon_max);
+            }
+            lats = clip_grid_values(lats, lat_min, lat_max);
+        }
+        let is_on_grid = |lon: f64, lat: f64| !lons.is_empty() && !lats.is_empty() && lon >= lons[0] && lon <= lons[lons.len() - 1] && lat >= lats[0] && lat <= lats[lats.len() - 1];
+        let mut vectors = vec![(0.0, 0.0); times.len() * lats.len() * lons.len()];
+        let mut maximum_speed: f64 = 0.0;
+        for (time, lon, lat, u, v) in rows.into_iter().filter(|row| is_on_grid(row.1, row.2)) {
+            let time_index = times.binary_search(&time).unwrap();
+            let lat_index = lats.partition_point(|value| *value < lat);
+            let lon_index = lons.partition_point(|value| *value < lon);
+            vectors[(time_index * lats.len() + lat_index) * lons.len() + lon_index] = (u as f32, v as f32);
+            maximum_speed = maximum_speed.max((u * u + v * v).sqrt());
+        }
+        println!("Loaded vector field from {} with {} time steps on a grid of {} x {} points", path, times.len(), lons.len(), lats.len());
+        Ok(VectorField { times, lons, lats, vectors, maximum_speed })
+    }
+
+    pub fn start_time(&self) -> Option<u64> {
+        self.times.first().copied()
+    }
+
+    /// Eastward and northward component of the field at the position and time
+    pub fn vector_at(&self, position: &Node, time: u64) -> (f64, f64) {
+        if self.times.is_empty() {
+            return (0.0, 0.0);
+        }
+        let next_time_index = self.times.partition_point(|value| *value <= time);
+        if next_time_index == 0 {
+            return self.spatial_vector_at(0, position);
+        }
+        if next_time_index == self.times.len() {
+            return self.spatial_vector_at(self.times.len() - 1, position);
+        }
+        let (time1, time2) = (self.times[next_time_index - 1], self.times[next_time_index]);
+        let fraction = (time - time1) as f64 / (time2 - time1) as f64;
+        let (u1, v1) = self.spatial_vector_at(next_time_index - 1, position);
+        let (u2, v2) = self.spatial_vector_at(next_time_index, position);
+        (u1 + (u2 - u1) * fraction, v1 + (v2 - v1) * fraction)
+    }
+
+    fn spatial_vector_at(&self, time_index: usize, position: &Node) -> (f64, f64) {
+        let (lon_index, lon_fraction) = match grid_cell(&self.lons, position.lon) {
+            Some(cell) => cell,
+            None => return (0.0, 0.0)
+        };
+        let (lat_index, lat_fraction) = match grid_cell(&self.lats, position.lat) {
+            Some(cell) => cell,
+            None => return (0.0, 0.0)
+        };
+        let vector = |lat_index: usize, lon_index: usize| {
+            let (u, v) = self.vectors[(time_index * self.lats.len() + lat_index) * self.lons.len() + lon_index];
+            (u as f64, v as f64)
+        };
+        let next_lon_index = (lon_index + 1).min(self.lons.len() - 1);
+        let next_lat_index = (lat_index + 1).min(self.lats.len() - 1);
+        let interpolate = |a: (f64, f64), b: (f64, f64), fraction: f64| (a.0 + (b.0 - a.0) * fraction, a.1 + (b.1 - a.1) * fraction);
+        let lower = interpolate(vector(lat_index, lon_index), vector(lat_index, next_lon_index), lon_fraction);
+        let upper = interpolate(vector(next_lat_index, lon_index), vector(next_lat_index, next_lon_index), lon_fraction);
+        interpolate(lower, upper, lat_fraction)
+    }
+}
+
+fn sorted_values(mut values: Vec<f64>) -> Vec<f64> {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    values.dedup();
+    values
+}
+
+/// Keeps the grid values between the minimum and the maximum and the next value outside of them, which is needed for the interpolation
+fn clip_grid_values(grid_values: Vec<f64>, minimum: f64, maximum: f64) -> Vec<f64> {
+    let start = grid_values.partition_point(|grid_value| *grid_value < minimum).saturating_sub(1);
+    let end = (grid_values.partition_point(|grid_value| *grid_value <= maximum) + 1).min(grid_values.len());
+    grid_values[start..end.max(start)].to_vec()
+}
+
+/// Index of the grid value at or below the value and the fraction of the way to the next grid value, if the value is inside the grid
+fn grid_cell(grid_values: &Vec<f64>, value: f64) -> Option<(usize, f64)> {
+    if grid_values.is_empty() || value < grid_values[0] || value > grid_values[grid_values.len() - 1] {
+        return None;
+    }
+    let index = grid_values.partition_point(|grid_value| *grid_value <= value).max(1) - 1;
+    if index + 1 == grid_values.len() {
+        return Some((index, 0.0));
+    }
+    Some((index, (value - grid_values[index]) / (grid_values[index + 1] - grid_values[index])))
+}
+
+/**
+Currents and winds, which change the speed over ground of a ship.
+A current moves the ship with its full speed, a wind with the wind factor of its speed.
+The ship keeps its heading along an edge, so the component of the drift across the edge reduces its speed along the edge.
+The drift of an edge is sampled at its midpoint, when the ship passes it at its speed through the water.
+**/
+pub struct Weather {
+    // vector fields with the share of their speed, which moves the ship
+    fields: Vec<(VectorField, f64)>,
+}
+
+impl Weather {
+    /// Reads the vector fields of the configuration, which are clipped to the region. Returns None if no field is configured
+    pub fn from_config(region: Option<&Region>) -> Result<Option<Weather>, String> {
+        let config = Config::global();
+        let mut fields = Vec::new();
+        if let Some(path) = config.current_field() {
+            fields.push((VectorField::read(path, region)?, 1.0));
+        }
+        if let Some(path) = config.wind_field() {
+            fields.push((VectorField::read(path, region)?, config.wind_factor()));
+        }
+        if fields.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(Weather { fields }))
+        }
+    }
+
+    /// First time step of the fields, which is used if a route has no departure time
+    pub fn start_time(&self) -> u64 {
+        self.fields.iter().filter_map(|(field, _)| field.start_time()).min().unwrap_or(0)
+    }
+
+    /// Speed over ground of a ship sailing from one node to the other, which departs at the time, relative to its speed through the water
+    pub fn speed_factor(&self, from: &Node, to: &Node, meters_per_second: f64, time: u64) -> f64 {
+        let midpoint = intermediate_point(from, to, 0.5);
+        let midpoint_time = time + (distance(from.lon, from.lat, to.lon, to.lat) / 2.0 / meters_per_second) as u64;
+        let (east, north) = self.fields.iter().fold((0.0, 0.0), |(east, north), (field, factor)| {
+            let (u, v) = field.vector_at(&midpoint, midpoint_time);
+            (east + u * factor, north + v * factor)
+        });
+        // the course at the midpoint of the great circle
+        let bearing = initial_bearing(&midpoint, to);
+        let along = east * bearing.sin() + north * bearing.cos();
+        let across = east * bearing.cos() - north * bearing.sin();
+        if across.abs() >= meters_per_second {
+            return MINIMUM_SPEED_FACTOR;
+        }
+        // the ship turns into the drift across the edge to stay on the edge
+        let speed_over_ground = (meters_per_second * meters_per_second - across * across).sqrt() + along;
+        (speed_over_ground / meters_per_second).max(MINIMUM_SPEED_FACTOR)
+    }
+
+    /// Upper bound of the speed factor, which keeps the potential of A* a lower bound of the travel time
+    pub fn maximum_speed_factor(&self, meters_per_second: f64) -> f64 {
+        1.0 + self.fields.iter().map(|(field, factor)| field.maximum_speed * factor).sum::<f64>() / meters_per_second
+    }
+}
+
+/// Initial course from one node to the other in radians clockwise from north
+fn initial_bearing(from: &Node, to: &Node) -> f64 {
+    let (lat1, lat2) = (from.lat.to_radians(), to.lat.to_radians());
+    let delta_lon = (to.lon - from.lon).to_radians();
+    let y = delta_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * delta_lon.cos();
+    y.atan2(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::grid_graph::Node;
+    use super::{VectorField, Weather};
+
+    /// Field on the grid points 0 and 1 degrees in both directions with two time steps 100 seconds apart.
+    /// The eastward component grows with the longitude and the time, the northward component is 1 m/s
+    fn test_field() -> VectorField {
+        let mut vectors = Vec::new();
+        for time_step in 0..2 {
+            for _lat in 0..2 {
+                for lon in 0..2 {
+                    vectors.push(((lon + time_step * 2) as f32, 1.0));
+                }
+            }
+        }
+        VectorField { times: vec![0, 100], lons: vec![0.0, 1.0], lats: vec![0.0, 1.0], vectors, maximum_speed: 10_f64.sqrt() }
+    }
+
+    #[test]
+    fn vectors_are_interpolated_in_space_and_time() {
+        let field = test_field();
+        assert_eq!(field.vector_at(&Node { lon: 0.5, lat: 0.5 }, 0), (0.5, 1.0));
+        assert_eq!(field.vector_at(&Node { lon: 0.25, lat: 1.0 }, 50), (1.25, 1.0));
+        // before the first and after the last time step, the field keeps its first and last vectors
+        assert_eq!(field.vector_at(&Node { lon: 1.0, lat: 0.0 }, 500), (3.0, 1.0));
+        // the field is zero outside of the grid
+        assert_eq!(field.vector_at(&Node { lon: 1.5, lat: 0.5 }, 0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn the_drift_is_sampled_at_the_midpoint_of_the_edge() {
+        // the current only flows east of the longitude 0.5
+        let field = VectorField { times: vec![0], lons: vec![0.0, 0.5, 0.51, 1.0], lats: vec![-1.0, 1.0], vectors: vec![(0.0, 0.0), (0.0, 0.0), (2.0, 0.0), (2.0, 0.0), (0.0, 0.0), (0.0, 0.0), (2.0, 0.0), (2.0, 0.0)], maximum_speed: 2.0 };
+        let weather = Weather { fields: vec![(field, 1.0)] };
+        let node = |lon: f64| Node { lon, lat: 0.0 };
+        // the edges start on the other side of the border of the current than their midpoints
+        assert!((weather.speed_factor(&node(0.2), &node(1.0), 4.0, 0) - 1.5).abs() < 1e-9);
+        assert!((weather.speed_factor(&node(0.6), &node(0.0), 4.0, 0) - 1.0).abs() < 1e-9);
+        assert!((weather.speed_factor(&node(1.0), &node(0.2), 4.0, 0) - 0.5).abs() < 1e-9);
+        assert!(weather.speed_factor(&node(0.2), &node(1.0), 4.0, 0) <= weather.maximum_speed_factor(4.0));
+    }
+
+    #[test]
+    fn missing_and_malformed_files_are_reported() {
+        let path = std::env::temp_dir().join(format!("osm-tasks-weather-{}.csv", std::process::id()));
+        let path_name = path.to_str().unwrap();
+        assert!(VectorField::read(path_name, None).is_err());
+        fs::write(&path, "time,lon,lat,u,v\n0,0.0,0.0,1.0\n").unwrap();
+        assert!(matches!(VectorField::read(path_name, None), Err(error) if error.contains("Line 2")));
+        fs::write(&path, "time,lon,lat,u,v\n0,0.0,0.0,1.0,x\n").unwrap();
+        assert!(matches!(VectorField::read(path_name, None), Err(error) if error.contains("Invalid number x")));
+        fs::write(&path, "time,lon,lat,u,v\n0,0.0,0.0,1.0,0.0\n0,1.0,0.0,3.0,0.0\n").unwrap();
+        let field = VectorField::read(path_name, None).unwrap_or_else(|error| panic!("{}", error));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(field.vector_at(&Node { lon: 0.5, lat: 0.0 }, 0), (2.0, 0.0));
+    }
+}